@@ -0,0 +1,849 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use serde_json::json;
+use sqlx::{Connection, Row, SqliteConnection};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::db::{insert_words, ProgressSelect, WordRecord};
+use crate::review_log::now_timestamp;
+use crate::scheduler::{today, MIN_EASE, SECONDS_PER_DAY};
+
+/// Id of the note type we export, kept fixed so re-importing a newer export updates the same notes in Anki.
+pub const NOTE_TYPE_ID: i64 = 1_718_000_000_000;
+/// Id of the exported deck. Anki's built in "Default" deck always has id 1.
+pub const DECK_ID: i64 = 1_718_000_000_001;
+/// Fields of the exported note type, in order.
+pub const NOTE_FIELDS: [&str; 4] = ["Expression", "Reading", "Meaning", "JLPT"];
+/// Tag added to notes of words the user marked as familiar.
+pub const FAMILIAR_TAG: &str = "familiar";
+/// Anki card flag used for `user_mark` (1 = red).
+pub const MARK_FLAG: i64 = 1;
+/// Anki separates note fields with the unit separator character.
+pub const FIELD_SEPARATOR: &str = "\x1f";
+
+/// The two kinds of Anki package.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnkiPackage {
+    /// `.apkg`, a deck that is merged into the user's collection.
+    Deck,
+    /// `.colpkg`, a whole collection that replaces the user's collection.
+    Collection,
+}
+
+impl AnkiPackage {
+    /// Guesses the package kind from the file extension, `.colpkg` or anything else for a deck.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("colpkg") => AnkiPackage::Collection,
+            _ => AnkiPackage::Deck,
+        }
+    }
+
+    /// Name of the collection file inside the zip.
+    fn collection_name(&self) -> &'static str {
+        match self {
+            AnkiPackage::Deck => "collection.anki2",
+            AnkiPackage::Collection => "collection.anki21",
+        }
+    }
+}
+
+/// Anki collection schema (version 11), the format every Anki client can still import.
+const COLLECTION_SCHEMA: &str = r#"
+CREATE TABLE col (
+    id     INTEGER PRIMARY KEY,
+    crt    INTEGER NOT NULL,
+    mod    INTEGER NOT NULL,
+    scm    INTEGER NOT NULL,
+    ver    INTEGER NOT NULL,
+    dty    INTEGER NOT NULL,
+    usn    INTEGER NOT NULL,
+    ls     INTEGER NOT NULL,
+    conf   TEXT NOT NULL,
+    models TEXT NOT NULL,
+    decks  TEXT NOT NULL,
+    dconf  TEXT NOT NULL,
+    tags   TEXT NOT NULL
+);
+CREATE TABLE notes (
+    id    INTEGER PRIMARY KEY,
+    guid  TEXT NOT NULL,
+    mid   INTEGER NOT NULL,
+    mod   INTEGER NOT NULL,
+    usn   INTEGER NOT NULL,
+    tags  TEXT NOT NULL,
+    flds  TEXT NOT NULL,
+    sfld  INTEGER NOT NULL,
+    csum  INTEGER NOT NULL,
+    flags INTEGER NOT NULL,
+    data  TEXT NOT NULL
+);
+CREATE TABLE cards (
+    id     INTEGER PRIMARY KEY,
+    nid    INTEGER NOT NULL,
+    did    INTEGER NOT NULL,
+    ord    INTEGER NOT NULL,
+    mod    INTEGER NOT NULL,
+    usn    INTEGER NOT NULL,
+    type   INTEGER NOT NULL,
+    queue  INTEGER NOT NULL,
+    due    INTEGER NOT NULL,
+    ivl    INTEGER NOT NULL,
+    factor INTEGER NOT NULL,
+    reps   INTEGER NOT NULL,
+    lapses INTEGER NOT NULL,
+    left   INTEGER NOT NULL,
+    odue   INTEGER NOT NULL,
+    odid   INTEGER NOT NULL,
+    flags  INTEGER NOT NULL,
+    data   TEXT NOT NULL
+);
+CREATE TABLE revlog (
+    id      INTEGER PRIMARY KEY,
+    cid     INTEGER NOT NULL,
+    usn     INTEGER NOT NULL,
+    ease    INTEGER NOT NULL,
+    ivl     INTEGER NOT NULL,
+    lastIvl INTEGER NOT NULL,
+    factor  INTEGER NOT NULL,
+    time    INTEGER NOT NULL,
+    type    INTEGER NOT NULL
+);
+CREATE TABLE graves (
+    usn  INTEGER NOT NULL,
+    oid  INTEGER NOT NULL,
+    type INTEGER NOT NULL
+);
+CREATE INDEX ix_notes_usn ON notes (usn);
+CREATE INDEX ix_cards_usn ON cards (usn);
+CREATE INDEX ix_revlog_usn ON revlog (usn);
+CREATE INDEX ix_cards_nid ON cards (nid);
+CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+CREATE INDEX ix_revlog_cid ON revlog (cid);
+CREATE INDEX ix_notes_csum ON notes (csum);
+"#;
+
+/// Returns a path in the system temp dir that is unique to this process and call.
+pub(crate) fn temp_file_path(name: &str) -> PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    std::env::temp_dir().join(format!("dxgui-{}-{}-{}", std::process::id(), nanos, name))
+}
+
+/// Anki's checksum of the sort field: the first 8 hex digits of its sha1 as a number.
+fn field_checksum(field: &str) -> i64 {
+    let digest = sha1_smol::Sha1::from(field).digest().bytes();
+    i64::from(u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]))
+}
+
+/// A stable note guid, so exporting the same word again updates the note instead of duplicating it.
+fn note_guid(word: &WordRecord) -> String {
+    let hex = sha1_smol::Sha1::from(format!("dxgui:{}:{}", word.expression, word.reading)).hexdigest();
+    hex[..16].to_string()
+}
+
+/// Anki stores tags as one space separated string with a leading and trailing space.
+fn note_tags(word: &WordRecord) -> String {
+    let mut tags: Vec<String> = word.tags.clone();
+    if word.familiar {
+        tags.push(FAMILIAR_TAG.to_string());
+    }
+    if tags.is_empty() {
+        String::new()
+    } else {
+        format!(" {} ", tags.join(" "))
+    }
+}
+
+fn note_type_json(modified: i64) -> serde_json::Value {
+    let fields: Vec<serde_json::Value> = NOTE_FIELDS
+        .iter()
+        .enumerate()
+        .map(|(ord, name)| json!({
+            "name": name, "ord": ord, "sticky": false, "rtl": false,
+            "font": "Arial", "size": 20, "media": []
+        }))
+        .collect();
+
+    json!({
+        "id": NOTE_TYPE_ID,
+        "name": "JLPT Vocabulary",
+        "type": 0,
+        "mod": modified,
+        "usn": -1,
+        "sortf": 0,
+        "did": DECK_ID,
+        "tmpls": [{
+            "name": "Recognition",
+            "ord": 0,
+            "qfmt": "<div class=\"expression\">{{Expression}}</div>",
+            "afmt": "{{FrontSide}}<hr id=answer><div class=\"reading\">{{Reading}}</div><div>{{Meaning}}</div><div class=\"jlpt\">{{JLPT}}</div>",
+            "bqfmt": "", "bafmt": "", "did": null, "bfont": "", "bsize": 0
+        }],
+        "flds": fields,
+        "css": ".card { font-family: arial; font-size: 20px; text-align: center; }\n.expression { font-size: 48px; }\n.reading { font-size: 28px; }\n.jlpt { color: grey; font-size: 14px; }",
+        "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+        "latexPost": "\\end{document}",
+        "latexsvg": false,
+        "req": [[0, "any", [0]]],
+        "tags": [],
+        "vers": []
+    })
+}
+
+fn deck_json(id: i64, name: &str, modified: i64) -> serde_json::Value {
+    json!({
+        "id": id, "name": name, "desc": "", "mod": modified, "usn": -1,
+        "collapsed": false, "browserCollapsed": false, "dyn": 0, "conf": 1,
+        "newToday": [0, 0], "revToday": [0, 0], "lrnToday": [0, 0], "timeToday": [0, 0],
+        "extendNew": 10, "extendRev": 50
+    })
+}
+
+fn deck_config_json(modified: i64) -> serde_json::Value {
+    json!({
+        "id": 1, "name": "Default", "mod": modified, "usn": -1, "maxTaken": 60,
+        "autoplay": true, "timer": 0, "replayq": true, "dyn": false,
+        "new": { "delays": [1.0, 10.0], "ints": [1, 4, 7], "initialFactor": 2500, "order": 1, "perDay": 20, "bury": true, "separate": true },
+        "rev": { "perDay": 200, "ease4": 1.3, "fuzz": 0.05, "ivlFct": 1.0, "maxIvl": 36500, "bury": true, "minSpace": 1 },
+        "lapse": { "delays": [10.0], "mult": 0.0, "minInt": 1, "leechFails": 8, "leechAction": 0 }
+    })
+}
+
+/// Writes `words` as an Anki package to `path` and returns the number of notes written.
+/// Every word becomes one note of the "JLPT Vocabulary" note type (Expression/Reading/Meaning/JLPT)
+/// with a single recognition card in the deck `deck_name`.
+/// - `user_mark` becomes the red card flag
+/// - familiar words get the `familiar` tag, the word's own tags are kept
+/// - practiced words keep their schedule (interval, ease, due day, lapses), new words stay new
+pub async fn export_anki_package(
+    words: &[WordRecord],
+    deck_name: &str,
+    path: &Path,
+    package: AnkiPackage,
+) -> Result<usize, Box<dyn Error>> {
+    // build the collection in a temp file, Anki packages are a zip of the sqlite file
+    let collection_path = temp_file_path(package.collection_name());
+    let result = write_collection(words, deck_name, &collection_path).await;
+    let collection = result.and_then(|_| Ok(fs::read(&collection_path)?));
+    let _ = fs::remove_file(&collection_path);
+    let collection = collection?;
+
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(fs::File::create(path)?);
+    zip.start_file(package.collection_name(), options)?;
+    zip.write_all(&collection)?;
+    // media manifest, we don't export any media files
+    zip.start_file("media", options)?;
+    zip.write_all(b"{}")?;
+    zip.finish()?;
+
+    Ok(words.len())
+}
+
+/// Selects words with `select` and exports them, see `export_anki_package`.
+pub async fn export_selection(
+    pool: &sqlx::SqlitePool,
+    select: ProgressSelect,
+    deck_name: &str,
+    path: &Path,
+) -> Result<usize, Box<dyn Error>> {
+    let words = select.execute(pool).await?;
+    export_anki_package(&words, deck_name, path, AnkiPackage::from_path(path)).await
+}
+
+async fn write_collection(words: &[WordRecord], deck_name: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+    let options = SqliteConnectOptions::new().filename(path).create_if_missing(true);
+    let mut conn = SqliteConnection::connect_with(&options).await?;
+
+    let now_ms = now_timestamp() * 1000;
+    let now = now_timestamp();
+    // Anki counts card due days from the collection creation day, we create the collection today
+    let created_day = today();
+
+    let mut transaction = conn.begin().await?;
+    sqlx::raw_sql(COLLECTION_SCHEMA).execute(&mut *transaction).await?;
+
+    let conf = json!({
+        "activeDecks": [DECK_ID], "curDeck": DECK_ID, "newSpread": 0, "collapseTime": 1200,
+        "timeLim": 0, "estTimes": true, "dueCounts": true, "curModel": NOTE_TYPE_ID,
+        "nextPos": words.len() + 1, "sortType": "noteFld", "sortBackwards": false, "addToCur": true
+    });
+    let models = json!({ NOTE_TYPE_ID.to_string(): note_type_json(now) });
+    let decks = json!({
+        "1": deck_json(1, "Default", now),
+        DECK_ID.to_string(): deck_json(DECK_ID, deck_name, now),
+    });
+    let dconf = json!({ "1": deck_config_json(now) });
+
+    sqlx::query(
+        r#"
+        INSERT INTO col (id, crt, mod, scm, ver, dty, usn, ls, conf, models, decks, dconf, tags)
+        VALUES (1, ?, ?, ?, 11, 0, 0, 0, ?, ?, ?, ?, '{}')
+        "#,
+    )
+    .bind(created_day * SECONDS_PER_DAY)
+    .bind(now_ms)
+    .bind(now_ms)
+    .bind(conf.to_string())
+    .bind(models.to_string())
+    .bind(decks.to_string())
+    .bind(dconf.to_string())
+    .execute(&mut *transaction)
+    .await?;
+
+    for (position, word) in words.iter().enumerate() {
+        let id = now_ms + position as i64;
+        let fields = [word.expression.as_str(), word.reading.as_str(), word.meaning.as_str(), word.jlpt.as_str()];
+
+        sqlx::query(
+            r#"
+            INSERT INTO notes (id, guid, mid, mod, usn, tags, flds, sfld, csum, flags, data)
+            VALUES (?, ?, ?, ?, -1, ?, ?, ?, ?, 0, '')
+            "#,
+        )
+        .bind(id)
+        .bind(note_guid(word))
+        .bind(NOTE_TYPE_ID)
+        .bind(now)
+        .bind(note_tags(word))
+        .bind(fields.join(FIELD_SEPARATOR))
+        .bind(&word.expression)
+        .bind(field_checksum(&word.expression))
+        .execute(&mut *transaction)
+        .await?;
+
+        // card type/queue: 0 = new (due is the position in the new queue), 2 = review (due is a day)
+        let (card_type, due, interval, factor) = if word.practice_time > 0 {
+            (2, word.due_day - created_day, word.interval_days.max(1), (word.ease * 1000.0).round() as i64)
+        } else {
+            (0, position as i64 + 1, 0, 0)
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO cards (id, nid, did, ord, mod, usn, type, queue, due, ivl, factor, reps, lapses, left, odue, odid, flags, data)
+            VALUES (?, ?, ?, 0, ?, -1, ?, ?, ?, ?, ?, ?, ?, 0, 0, 0, ?, '')
+            "#,
+        )
+        .bind(id)
+        .bind(id)
+        .bind(DECK_ID)
+        .bind(now)
+        .bind(card_type)
+        .bind(card_type)
+        .bind(due)
+        .bind(interval)
+        .bind(factor)
+        .bind(word.practice_time)
+        .bind(word.lapses)
+        .bind(if word.user_mark { MARK_FLAG } else { 0 })
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    transaction.commit().await?;
+    conn.close().await?;
+    Ok(())
+}
+
+/// A note type found in an Anki collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnkiNoteType {
+    pub id: i64,
+    pub name: String,
+    pub fields: Vec<String>,
+    pub note_count: i64,
+}
+
+impl AnkiNoteType {
+    /// Index of the field called `name`, ignoring case.
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field.eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Which note fields go into which word columns, fields are indexes into `AnkiNoteType::fields`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnkiFieldMap {
+    pub note_type_id: i64,
+    pub expression: usize,
+    /// Without a reading field the expression is used, fine for kana only decks.
+    pub reading: Option<usize>,
+    pub meaning: usize,
+    /// Without a jlpt field the level comes from a tag like `JLPT_3`, else `default_jlpt`.
+    pub jlpt: Option<usize>,
+    pub default_jlpt: String,
+}
+
+impl AnkiFieldMap {
+    /// Guesses the mapping from common field names, None if there is no expression or meaning field.
+    pub fn guess(note_type: &AnkiNoteType, default_jlpt: &str) -> Option<Self> {
+        let find = |names: &[&str]| names.iter().find_map(|name| note_type.field_index(name));
+        Some(AnkiFieldMap {
+            note_type_id: note_type.id,
+            expression: find(&["Expression", "Vocab", "Vocabulary", "Word", "Kanji", "Japanese", "Front"])?,
+            reading: find(&["Reading", "Kana", "Furigana", "Hiragana", "Vocab-Kana"]),
+            meaning: find(&["Meaning", "English", "Definition", "Glossary", "Vocab-Meaning", "Back"])?,
+            jlpt: find(&["JLPT", "Level", "JLPT Level"]),
+            default_jlpt: default_jlpt.to_string(),
+        })
+    }
+}
+
+/// Options for `import_anki_package`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnkiImportOptions {
+    /// Copy the card's review state (reps, interval, ease, due day, lapses, flag) into the progress columns.
+    pub carry_progress: bool,
+    /// Compute the report without writing anything.
+    pub dry_run: bool,
+}
+
+/// What an Anki import did (or would do in a dry run).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnkiImportReport {
+    pub inserted: Vec<String>,
+    /// Words already in the database or twice in the package, matched on (expression, reading).
+    pub duplicates: Vec<String>,
+    /// Notes with an empty expression or meaning.
+    pub skipped: usize,
+}
+
+impl fmt::Display for AnkiImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for word in &self.inserted {
+            writeln!(f, "+ {}", word)?;
+        }
+        for word in &self.duplicates {
+            writeln!(f, "= {}", word)?;
+        }
+        write!(
+            f,
+            "{} inserted, {} duplicates, {} skipped",
+            self.inserted.len(),
+            self.duplicates.len(),
+            self.skipped
+        )
+    }
+}
+
+/// An Anki package opened for reading. The collection is extracted to a temp file,
+/// call `close` to remove it.
+pub struct AnkiPackageReader {
+    collection_path: PathBuf,
+    pool: sqlx::SqlitePool,
+}
+
+impl AnkiPackageReader {
+    /// Opens an `.apkg` or legacy `.colpkg`.
+    pub async fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut archive = ZipArchive::new(fs::File::open(path)?)?;
+        // newer packages carry the real collection as anki21 and a dummy anki2 for old clients
+        let name = ["collection.anki21", "collection.anki2"]
+            .into_iter()
+            .find(|name| archive.index_for_name(name).is_some());
+        let Some(name) = name else {
+            if archive.index_for_name("collection.anki21b").is_some() {
+                return Err("This package uses the latest Anki format, export it again with \"Support older Anki versions\" checked.".into());
+            }
+            return Err("Not an Anki package: no collection found.".into());
+        };
+
+        let mut collection = Vec::new();
+        archive.by_name(name)?.read_to_end(&mut collection)?;
+        let collection_path = temp_file_path(name);
+        fs::write(&collection_path, collection)?;
+
+        let options = SqliteConnectOptions::new().filename(&collection_path).read_only(true);
+        let pool = match SqlitePoolOptions::new().max_connections(1).connect_with(options).await {
+            Ok(pool) => pool,
+            Err(e) => {
+                let _ = fs::remove_file(&collection_path);
+                return Err(e.into());
+            }
+        };
+        Ok(AnkiPackageReader { collection_path, pool })
+    }
+
+    /// Lists the note types of the collection with their fields, so the user can pick a mapping.
+    pub async fn note_types(&self) -> Result<Vec<AnkiNoteType>, Box<dyn Error>> {
+        let models: String = sqlx::query_scalar("SELECT models FROM col").fetch_one(&self.pool).await?;
+        let models: serde_json::Value = serde_json::from_str(&models)?;
+
+        let mut note_types = Vec::new();
+        for model in models.as_object().map(|m| m.values().collect::<Vec<_>>()).unwrap_or_default() {
+            let id = model["id"].as_i64().unwrap_or(0);
+            let mut fields: Vec<(i64, String)> = model["flds"]
+                .as_array()
+                .map(|flds| {
+                    flds.iter()
+                        .map(|fld| (fld["ord"].as_i64().unwrap_or(0), fld["name"].as_str().unwrap_or("").to_string()))
+                        .collect()
+                })
+                .unwrap_or_default();
+            fields.sort_by_key(|(ord, _)| *ord);
+
+            let note_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM notes WHERE mid = ?")
+                .bind(id)
+                .fetch_one(&self.pool)
+                .await?;
+            note_types.push(AnkiNoteType {
+                id,
+                name: model["name"].as_str().unwrap_or("").to_string(),
+                fields: fields.into_iter().map(|(_, name)| name).collect(),
+                note_count,
+            });
+        }
+        note_types.sort_by_key(|note_type| std::cmp::Reverse(note_type.note_count));
+        Ok(note_types)
+    }
+
+    /// Reads the notes of `map.note_type_id` as word records, in the package order.
+    /// Progress columns are left at their defaults unless `carry_progress` is set.
+    pub async fn read_words(&self, map: &AnkiFieldMap, carry_progress: bool) -> Result<Vec<WordRecord>, Box<dyn Error>> {
+        let created: i64 = sqlx::query_scalar("SELECT crt FROM col").fetch_one(&self.pool).await?;
+        let created_day = created / SECONDS_PER_DAY;
+
+        // one card per note is enough, take the first template's card
+        let rows = sqlx::query(
+            r#"
+            SELECT notes.flds, notes.tags,
+                   cards.type, cards.due, cards.ivl, cards.factor, cards.reps, cards.lapses, cards.flags
+            FROM notes
+            LEFT JOIN cards ON cards.id = (SELECT id FROM cards WHERE cards.nid = notes.id ORDER BY ord LIMIT 1)
+            WHERE notes.mid = ?
+            ORDER BY notes.id
+            "#,
+        )
+        .bind(map.note_type_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut words = Vec::new();
+        for row in rows {
+            let flds: String = row.get("flds");
+            let fields: Vec<String> = flds.split(FIELD_SEPARATOR).map(strip_html).collect();
+            let field = |index: usize| fields.get(index).cloned().unwrap_or_default();
+
+            let mut tags: Vec<String> = row.get::<String, _>("tags").split_whitespace().map(|t| t.to_string()).collect();
+            let familiar_tag = tags.iter().any(|tag| tag == FAMILIAR_TAG);
+            tags.retain(|tag| tag != FAMILIAR_TAG);
+
+            let expression = field(map.expression);
+            let reading = map.reading.map(field).filter(|r| !r.is_empty()).unwrap_or_else(|| expression.clone());
+            let jlpt = map
+                .jlpt
+                .and_then(|index| normalize_jlpt(&field(index)))
+                .or_else(|| tags.iter().find_map(|tag| jlpt_from_tag(tag)))
+                .unwrap_or_else(|| map.default_jlpt.clone());
+
+            let mut word = WordRecord {
+                expression,
+                reading,
+                meaning: field(map.meaning),
+                jlpt,
+                tags,
+                ..Default::default()
+            };
+
+            if carry_progress {
+                let card_type = row.get::<Option<i64>, _>("type").unwrap_or(0);
+                let due = row.get::<Option<i64>, _>("due").unwrap_or(0);
+                let interval = row.get::<Option<i64>, _>("ivl").unwrap_or(0);
+                let factor = row.get::<Option<i64>, _>("factor").unwrap_or(0);
+
+                word.practice_time = row.get::<Option<i64>, _>("reps").unwrap_or(0);
+                word.lapses = row.get::<Option<i64>, _>("lapses").unwrap_or(0);
+                // the low 3 bits are the colored flag
+                word.user_mark = row.get::<Option<i64>, _>("flags").unwrap_or(0) & 0b111 != 0;
+                if factor > 0 {
+                    word.ease = (factor as f64 / 1000.0).max(MIN_EASE);
+                }
+                // negative intervals are learning steps in seconds
+                word.interval_days = interval.max(0);
+                word.due_day = match card_type {
+                    2 => created_day + due,       // review: days since the collection was created
+                    1 | 3 => due / SECONDS_PER_DAY, // (re)learning: unix timestamp
+                    _ => 0,                         // new
+                };
+                // a mature card (21 days, Anki's own threshold) counts as familiar
+                word.familiar = familiar_tag || (card_type == 2 && interval >= 21);
+            } else {
+                word.familiar = familiar_tag;
+            }
+
+            words.push(word);
+        }
+        Ok(words)
+    }
+
+    /// Closes the collection and removes the extracted temp file.
+    pub async fn close(self) {
+        self.pool.close().await;
+        let _ = fs::remove_file(&self.collection_path);
+    }
+}
+
+/// Tags that break the text into lines or blocks, they become a space.
+/// Inline tags like <b> are dropped so "食<b>べ</b>る" stays one word.
+const BREAKING_TAGS: &[&str] = &[
+    "br", "div", "p", "li", "ul", "ol", "tr", "td", "th", "table", "hr", "h1", "h2", "h3", "h4", "h5", "h6",
+];
+
+/// Anki fields are html, keep the text only.
+fn strip_html(field: &str) -> String {
+    let mut text = String::new();
+    let mut tag: Option<String> = None;
+    for c in field.chars() {
+        match (c, tag.as_mut()) {
+            ('<', None) => tag = Some(String::new()),
+            ('>', Some(name)) => {
+                // the name without a closing slash or attributes, e.g. "br" of "<br/>" or "</div>"
+                let name = name.trim_start_matches('/').split([' ', '/']).next().unwrap_or("").to_lowercase();
+                if BREAKING_TAGS.contains(&name.as_str()) {
+                    text.push(' ');
+                }
+                tag = None;
+            }
+            (_, Some(name)) => name.push(c),
+            (_, None) => text.push(c),
+        }
+    }
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Turns "N3", "n3", "JLPT N3" or "3" into our "n3".
+fn normalize_jlpt(value: &str) -> Option<String> {
+    let value = value.trim().to_lowercase();
+    let digits: Vec<char> = value.chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.len() != 1 || !(value.contains('n') || value.contains("jlpt") || value.len() == 1) {
+        return None;
+    }
+    match digits[0] {
+        '1'..='5' => Some(format!("n{}", digits[0])),
+        _ => None,
+    }
+}
+
+/// Reads the level from tags like "JLPT_3" or "JLPT::N3", other tags (e.g. "Genki_Ln.3") are ignored.
+fn jlpt_from_tag(tag: &str) -> Option<String> {
+    if tag.to_lowercase().contains("jlpt") {
+        normalize_jlpt(tag)
+    } else {
+        None
+    }
+}
+
+/// Imports the notes of an Anki package into the word table through `insert_words`.
+/// Notes whose (expression, reading) is already in the database, or earlier in the package, are skipped.
+pub async fn import_anki_package(
+    pool: &sqlx::SqlitePool,
+    path: &Path,
+    map: &AnkiFieldMap,
+    options: AnkiImportOptions,
+) -> Result<AnkiImportReport, Box<dyn Error>> {
+    let reader = AnkiPackageReader::open(path).await?;
+    let words = reader.read_words(map, options.carry_progress).await;
+    reader.close().await;
+    let words = words?;
+
+    // soft deleted words count too, merge_import_words restores those
+    let rows = sqlx::query("SELECT expression, reading FROM words").fetch_all(pool).await?;
+    let mut seen: HashSet<(String, String)> = rows
+        .iter()
+        .map(|row| (row.get::<String, _>("expression"), row.get::<String, _>("reading")))
+        .collect();
+
+    let mut report = AnkiImportReport::default();
+    let mut new_words = Vec::new();
+    for word in words {
+        if word.expression.is_empty() || word.meaning.is_empty() {
+            report.skipped += 1;
+            continue;
+        }
+        let label = format!("{} [{}]", word.expression, word.reading);
+        if !seen.insert((word.expression.clone(), word.reading.clone())) {
+            report.duplicates.push(label);
+            continue;
+        }
+        report.inserted.push(label);
+        new_words.push(word);
+    }
+
+    if !options.dry_run {
+        insert_words(pool, new_words).await?;
+    }
+    Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+    use crate::scheduler::{grade_word, Grade};
+
+    #[tokio::test]
+    async fn test_export_anki_package() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            WordRecord { tags: vec!["JLPT_5".to_string()], ..test_word("青", "あお", "blue", "n5") },
+            test_word("会う", "あう", "to meet", "n5"),
+        ]).await.expect("Failed to insert words.");
+        grade_word(&pool, 1, Grade::Good).await.unwrap();
+        ProgressUpdate::new().set_user_mark(true).execute(&pool, 1).await.unwrap();
+
+        let apkg = temp_file_path("export.apkg");
+        let count = export_selection(&pool, ProgressSelect::new().select_jlpt(JLPTlv::N5), "JLPT N5", &apkg)
+            .await
+            .expect("Failed to export.");
+        assert_eq!(count, 2);
+
+        // unzip the collection and open it like Anki would
+        let mut archive = zip::ZipArchive::new(fs::File::open(&apkg).unwrap()).unwrap();
+        let mut media = String::new();
+        archive.by_name("media").unwrap().read_to_string(&mut media).unwrap();
+        assert_eq!(media, "{}");
+        let mut collection = Vec::new();
+        archive.by_name("collection.anki2").unwrap().read_to_end(&mut collection).unwrap();
+        let collection_path = temp_file_path("export.anki2");
+        fs::write(&collection_path, collection).unwrap();
+
+        let anki = SqlitePoolOptions::new()
+            .connect_with(SqliteConnectOptions::new().filename(&collection_path))
+            .await
+            .expect("Failed to open exported collection.");
+
+        let notes = sqlx::query("SELECT notes.flds, notes.tags, notes.sfld, cards.flags, cards.type, cards.ivl FROM notes JOIN cards ON cards.nid = notes.id ORDER BY notes.id")
+            .fetch_all(&anki)
+            .await
+            .unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].get::<String, _>("flds"), "青\x1fあお\x1fblue\x1fn5");
+        assert_eq!(notes[0].get::<String, _>("tags"), " JLPT_5 familiar ");
+        assert_eq!(notes[0].get::<i64, _>("flags"), MARK_FLAG);
+        assert_eq!(notes[0].get::<i64, _>("type"), 2);
+        assert_eq!(notes[0].get::<i64, _>("ivl"), 1);
+        assert_eq!(notes[1].get::<String, _>("sfld"), "会う");
+        assert_eq!(notes[1].get::<String, _>("tags"), "");
+        assert_eq!(notes[1].get::<i64, _>("flags"), 0);
+        assert_eq!(notes[1].get::<i64, _>("type"), 0);
+
+        let models: String = sqlx::query_scalar("SELECT models FROM col").fetch_one(&anki).await.unwrap();
+        let models: serde_json::Value = serde_json::from_str(&models).unwrap();
+        let fields = &models[NOTE_TYPE_ID.to_string()]["flds"];
+        assert_eq!(fields.as_array().unwrap().len(), NOTE_FIELDS.len());
+        assert_eq!(fields[2]["name"], "Meaning");
+
+        anki.close().await;
+        let _ = fs::remove_file(&apkg);
+        let _ = fs::remove_file(&collection_path);
+    }
+
+    #[tokio::test]
+    async fn test_import_anki_package() {
+        let source = setup_test_db().await;
+        bulk_insert_words(&source, vec![
+            WordRecord { tags: vec!["JLPT_5".to_string()], ..test_word("青", "あお", "blue", "n5") },
+            test_word("作法", "さほう", "manners", "n3"),
+            test_word("様々", "さまざま", "<b>varied</b>&nbsp;various", "n3"),
+        ]).await.expect("Failed to insert words.");
+        grade_word(&source, 2, Grade::Good).await.unwrap();
+        grade_word(&source, 2, Grade::Good).await.unwrap();
+        ProgressUpdate::new().set_user_mark(true).execute(&source, 2).await.unwrap();
+        ProgressUpdate::new().set_familiar(true).execute(&source, 3).await.unwrap();
+
+        let apkg = temp_file_path("import.apkg");
+        export_selection(&source, ProgressSelect::new(), "JLPT", &apkg).await.expect("Failed to export.");
+
+        // the target already has 青
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            test_word("青", "あお", "blue", "n5"),
+        ]).await.unwrap();
+
+        let reader = AnkiPackageReader::open(&apkg).await.expect("Failed to open package.");
+        let note_types = reader.note_types().await.unwrap();
+        reader.close().await;
+        assert_eq!(note_types.len(), 1);
+        assert_eq!(note_types[0].fields, NOTE_FIELDS.to_vec());
+        assert_eq!(note_types[0].note_count, 3);
+
+        let map = AnkiFieldMap::guess(&note_types[0], "n3").expect("Failed to guess mapping.");
+        assert_eq!((map.expression, map.reading, map.meaning, map.jlpt), (0, Some(1), 2, Some(3)));
+
+        let options = AnkiImportOptions { carry_progress: true, dry_run: true };
+        let dry = import_anki_package(&pool, &apkg, &map, options).await.unwrap();
+        assert_eq!(dry.inserted.len(), 2);
+        assert_eq!(count_words(&pool).await, 1);
+
+        let options = AnkiImportOptions { carry_progress: true, dry_run: false };
+        let report = import_anki_package(&pool, &apkg, &map, options).await.unwrap();
+        assert_eq!(report, dry);
+        assert_eq!(report.duplicates, vec!["青 [あお]".to_string()]);
+        assert_eq!(count_words(&pool).await, 3);
+
+        let imported = find_word_by_ids(&pool, vec![2, 3]).await.unwrap();
+        let original = find_word_by_ids(&source, vec![2]).await.unwrap();
+        assert_eq!(imported[0].expression, "作法");
+        assert_eq!(imported[0].practice_time, 2);
+        assert_eq!(imported[0].interval_days, original[0].interval_days);
+        assert_eq!(imported[0].due_day, original[0].due_day);
+        assert!(imported[0].user_mark);
+        assert_eq!(imported[1].meaning, "varied various");
+        assert!(imported[1].familiar);
+        assert!(imported[1].tags.is_empty());
+
+        // importing again only finds duplicates
+        let report = import_anki_package(&pool, &apkg, &map, options).await.unwrap();
+        assert!(report.inserted.is_empty());
+        assert_eq!(report.duplicates.len(), 3);
+
+        let _ = fs::remove_file(&apkg);
+    }
+
+    #[test]
+    fn test_normalize_jlpt() {
+        assert_eq!(normalize_jlpt("N3"), Some("n3".to_string()));
+        assert_eq!(normalize_jlpt("JLPT N1"), Some("n1".to_string()));
+        assert_eq!(normalize_jlpt("4"), Some("n4".to_string()));
+        assert_eq!(normalize_jlpt("N6"), None);
+        assert_eq!(jlpt_from_tag("JLPT::N2"), Some("n2".to_string()));
+        assert_eq!(jlpt_from_tag("Genki_Ln.3"), None);
+    }
+
+    #[test]
+    fn test_strip_html() {
+        // inline markup is dropped without splitting the word
+        assert_eq!(strip_html("食<b>べ</b>る"), "食べる");
+        assert_eq!(strip_html("<span style=\"color: red\">赤</span>い"), "赤い");
+        // line breaks and blocks separate the text
+        assert_eq!(strip_html("to eat<br>to live on"), "to eat to live on");
+        assert_eq!(strip_html("<div>one</div><div>two</div>"), "one two");
+        assert_eq!(strip_html(" a&nbsp;&amp;&nbsp;b <br/> "), "a & b");
+    }
+
+    async fn count_words(pool: &sqlx::SqlitePool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM words").fetch_one(pool).await.unwrap()
+    }
+}
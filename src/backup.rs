@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use crate::migrations::current_version;
+use crate::review_log::now_timestamp;
+
+/// Version of the backup document, bump it when the format changes in a way old readers can't handle.
+pub const BACKUP_FORMAT_VERSION: i64 = 1;
+
+/// One answer from the review history, without the word id since ids differ between databases.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewBackup {
+    pub reviewed_at: i64,
+    pub mode: String,
+    pub j_to_e: bool,
+    pub grade: i64,
+    pub response_ms: i64,
+}
+
+/// The progress of one word, keyed by (expression, reading, jlpt) so it can be restored
+/// into a freshly built database.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WordProgress {
+    pub expression: String,
+    pub reading: String,
+    pub jlpt: String,
+    pub practice_time: i64,
+    pub familiar: bool,
+    pub user_mark: bool,
+    pub ease: f64,
+    pub interval_days: i64,
+    pub due_day: i64,
+    pub lapses: i64,
+    #[serde(default)]
+    pub reviews: Vec<ReviewBackup>,
+}
+
+/// The portable progress backup document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgressBackup {
+    pub format_version: i64,
+    /// unix timestamp in seconds
+    pub created_at: i64,
+    /// schema version of the database the backup was taken from, informational only
+    pub schema_version: i64,
+    pub words: Vec<WordProgress>,
+}
+
+/// What a restore did (or would do in a dry run).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RestoreReport {
+    /// Words whose progress was restored.
+    pub restored: usize,
+    /// Reviews added to the history, reviews already in the database are not added twice.
+    pub reviews_added: usize,
+    /// Words in the backup that are not in the database, as "expression [reading] jlpt".
+    pub missing: Vec<String>,
+}
+
+impl fmt::Display for RestoreReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for word in &self.missing {
+            writeln!(f, "? {}", word)?;
+        }
+        write!(
+            f,
+            "{} words restored, {} reviews added, {} words not found",
+            self.restored,
+            self.reviews_added,
+            self.missing.len()
+        )
+    }
+}
+
+/// Collects the progress of every word that was practiced, marked or has review history.
+pub async fn export_progress(pool: &sqlx::SqlitePool) -> Result<ProgressBackup, sqlx::Error> {
+    let mut reviews: HashMap<i64, Vec<ReviewBackup>> = HashMap::new();
+    let rows = sqlx::query("SELECT * FROM reviews ORDER BY reviewed_at, id").fetch_all(pool).await?;
+    for row in rows {
+        reviews.entry(row.get("word_id")).or_default().push(ReviewBackup {
+            reviewed_at: row.get("reviewed_at"),
+            mode: row.get("mode"),
+            j_to_e: row.get("j_to_e"),
+            grade: row.get("grade"),
+            response_ms: row.get("response_ms"),
+        });
+    }
+
+    let rows = sqlx::query(
+        r#"
+        SELECT * FROM words
+        WHERE practice_time > 0 OR familiar = 1 OR user_mark = 1
+           OR EXISTS (SELECT 1 FROM reviews WHERE reviews.word_id = words.id)
+        ORDER BY id
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let words = rows
+        .iter()
+        .map(|row| WordProgress {
+            expression: row.get("expression"),
+            reading: row.get("reading"),
+            jlpt: row.get("jlpt"),
+            practice_time: row.get("practice_time"),
+            familiar: row.get("familiar"),
+            user_mark: row.get("user_mark"),
+            ease: row.get("ease"),
+            interval_days: row.get("interval_days"),
+            due_day: row.get("due_day"),
+            lapses: row.get("lapses"),
+            reviews: reviews.remove(&row.get::<i64, _>("id")).unwrap_or_default(),
+        })
+        .collect();
+
+    Ok(ProgressBackup {
+        format_version: BACKUP_FORMAT_VERSION,
+        created_at: now_timestamp(),
+        schema_version: current_version(pool).await?,
+        words,
+    })
+}
+
+/// Writes the progress backup to `path` as pretty printed JSON and returns the number of words in it.
+pub async fn save_progress_backup(pool: &sqlx::SqlitePool, path: &Path) -> Result<usize, Box<dyn Error>> {
+    let backup = export_progress(pool).await?;
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    fs::write(path, serde_json::to_string_pretty(&backup)?)?;
+    Ok(backup.words.len())
+}
+
+/// Reads a backup file, backups written by a newer version of the app are refused.
+pub fn load_progress_backup(path: &Path) -> Result<ProgressBackup, Box<dyn Error>> {
+    let backup: ProgressBackup = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if backup.format_version > BACKUP_FORMAT_VERSION {
+        return Err(format!(
+            "Backup format version {} is newer than this app supports ({}), please update the app.",
+            backup.format_version, BACKUP_FORMAT_VERSION
+        )
+        .into());
+    }
+    Ok(backup)
+}
+
+/// Restores a backup: words are matched on (expression, reading, jlpt), their progress columns
+/// are overwritten with the backed up values and missing reviews are added to the history.
+/// With `dry_run` nothing is written, use it to show the summary before applying.
+pub async fn restore_progress(
+    pool: &sqlx::SqlitePool,
+    backup: &ProgressBackup,
+    dry_run: bool,
+) -> Result<RestoreReport, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let mut report = RestoreReport::default();
+
+    let rows = sqlx::query("SELECT id, expression, reading, jlpt FROM words ORDER BY id")
+        .fetch_all(&mut *transaction)
+        .await?;
+    let mut ids: HashMap<(String, String, String), i64> = HashMap::new();
+    for row in rows {
+        // keep the oldest row if the same word was added twice
+        ids.entry((row.get("expression"), row.get("reading"), row.get("jlpt"))).or_insert(row.get("id"));
+    }
+
+    for word in &backup.words {
+        let key = (word.expression.clone(), word.reading.clone(), word.jlpt.clone());
+        let Some(&word_id) = ids.get(&key) else {
+            report.missing.push(format!("{} [{}] {}", word.expression, word.reading, word.jlpt));
+            continue;
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE words
+            SET practice_time = ?, familiar = ?, user_mark = ?, ease = ?, interval_days = ?, due_day = ?, lapses = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(word.practice_time)
+        .bind(word.familiar)
+        .bind(word.user_mark)
+        .bind(word.ease)
+        .bind(word.interval_days)
+        .bind(word.due_day)
+        .bind(word.lapses)
+        .bind(word_id)
+        .execute(&mut *transaction)
+        .await?;
+        report.restored += 1;
+
+        for review in &word.reviews {
+            let result = sqlx::query(
+                r#"
+                INSERT INTO reviews (word_id, reviewed_at, mode, j_to_e, grade, response_ms)
+                SELECT ?, ?, ?, ?, ?, ?
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM reviews
+                    WHERE word_id = ? AND reviewed_at = ? AND mode = ? AND j_to_e = ? AND grade = ?
+                )
+                "#,
+            )
+            .bind(word_id)
+            .bind(review.reviewed_at)
+            .bind(&review.mode)
+            .bind(review.j_to_e)
+            .bind(review.grade)
+            .bind(review.response_ms)
+            .bind(word_id)
+            .bind(review.reviewed_at)
+            .bind(&review.mode)
+            .bind(review.j_to_e)
+            .bind(review.grade)
+            .execute(&mut *transaction)
+            .await?;
+            report.reviews_added += result.rows_affected() as usize;
+        }
+    }
+
+    if dry_run {
+        transaction.rollback().await?;
+    } else {
+        transaction.commit().await?;
+    }
+
+    Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+    use crate::review_log::{log_review, reviews_for_word, ReviewEntry, ReviewMode};
+    use crate::scheduler::{grade_word, Grade};
+
+    async fn setup_db() -> sqlx::SqlitePool {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            test_word("青", "あお", "blue", "n5"),
+            test_word("作法", "さほう", "manners", "n3"),
+        ]).await.expect("Failed to insert words.");
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let pool = setup_db().await;
+        grade_word(&pool, 2, Grade::Good).await.unwrap();
+        log_review(&pool, &ReviewEntry::new(2, ReviewMode::Test, true, Grade::Good, 900)).await.unwrap();
+        ProgressUpdate::new().set_user_mark(true).execute(&pool, 1).await.unwrap();
+
+        let backup = export_progress(&pool).await.unwrap();
+        assert_eq!(backup.format_version, BACKUP_FORMAT_VERSION);
+        assert_eq!(backup.words.len(), 2);
+
+        // round trip through JSON like a backup file
+        let json = serde_json::to_string_pretty(&backup).unwrap();
+        let mut backup: ProgressBackup = serde_json::from_str(&json).unwrap();
+        backup.words.push(WordProgress {
+            expression: "消えた".to_string(),
+            reading: "きえた".to_string(),
+            jlpt: "n1".to_string(),
+            practice_time: 1,
+            familiar: false,
+            user_mark: false,
+            ease: 2.5,
+            interval_days: 0,
+            due_day: 0,
+            lapses: 0,
+            reviews: Vec::new(),
+        });
+
+        // the words are added in a different order in the new database, ids don't matter
+        let fresh = setup_test_db().await;
+        bulk_insert_words(&fresh, vec![
+            test_word("作法", "さほう", "manners", "n3"),
+            test_word("青", "あお", "blue", "n5"),
+        ]).await.unwrap();
+
+        let dry = restore_progress(&fresh, &backup, true).await.unwrap();
+        assert_eq!(dry.restored, 2);
+        assert_eq!(dry.reviews_added, 1);
+        assert_eq!(dry.missing, vec!["消えた [きえた] n1".to_string()]);
+        assert_eq!(count_total_practiced_words(&fresh).await.unwrap(), 0);
+
+        let report = restore_progress(&fresh, &backup, false).await.unwrap();
+        assert_eq!(report, dry);
+
+        let original = find_word_by_ids(&pool, vec![2]).await.unwrap();
+        let restored = find_word_by_ids(&fresh, vec![1]).await.unwrap();
+        assert_eq!(restored[0].practice_time, original[0].practice_time);
+        assert_eq!(restored[0].due_day, original[0].due_day);
+        assert_eq!(restored[0].interval_days, original[0].interval_days);
+        assert!(find_word_by_ids(&fresh, vec![2]).await.unwrap()[0].user_mark);
+        assert_eq!(reviews_for_word(&fresh, 1).await.unwrap()[0].response_ms, 900);
+
+        // restoring twice doesn't duplicate the history
+        let again = restore_progress(&fresh, &backup, false).await.unwrap();
+        assert_eq!(again.reviews_added, 0);
+        assert_eq!(reviews_for_word(&fresh, 1).await.unwrap().len(), 1);
+    }
+}
@@ -1,31 +1,49 @@
-
-
 use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{migrate::MigrateDatabase, Sqlite};
 use sqlx::Row;
 use dxgui::db::*;
+use dxgui::migrations::{current_version, run_migrations};
+use dxgui::word_import::{merge_import_words, ImportOptions};
 
+/// Builds or upgrades the word database.
+/// By default an existing database is upgraded in place and the csv files are merged into it,
+/// keeping user progress. Flags:
+/// --reset   drop everything and rebuild from the csv files
+/// --prune   soft delete words no longer in the csv files
+/// --dry-run only print the merge report
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let db_url = "sqlite:data/words_database.db";
-    let csv_file_path5 = "data//n5.csv"; 
+    let csv_file_path5 = "data//n5.csv";
     let csv_file_path4 = "data//n4.csv";
     let csv_file_path3 = "data//n3.csv";
     let csv_file_path2 = "data//n2.csv";
     let csv_file_path1 = "data//n1.csv";
     let mut records: Vec<WordRecord> = Vec::new();
 
+    let reset = std::env::args().any(|arg| arg == "--reset");
+    let options = ImportOptions {
+        soft_delete_missing: std::env::args().any(|arg| arg == "--prune"),
+        dry_run: std::env::args().any(|arg| arg == "--dry-run"),
+    };
+
     // load csv file to word records
     load_csv_to_word_records(csv_file_path5, &mut records,"n5")?;
     load_csv_to_word_records(csv_file_path4, &mut records,"n4")?;
-    load_csv_to_word_records(csv_file_path3, &mut records,"n3")?;    
+    load_csv_to_word_records(csv_file_path3, &mut records,"n3")?;
     load_csv_to_word_records(csv_file_path2, &mut records,"n2")?;
     load_csv_to_word_records(csv_file_path1, &mut records,"n1")?;
 
-    // reset the database
-    match reset_database(db_url).await {
-        Ok(_) => println!("Database reset successfully."),
-        Err(error) => panic!("Error resetting database: {}", error),
+    if reset {
+        // reset the database
+        match reset_database(db_url).await {
+            Ok(_) => println!("Database reset successfully."),
+            Err(error) => panic!("Error resetting database: {}", error),
+        }
+    } else if !Sqlite::database_exists(db_url).await? {
+        Sqlite::create_database(db_url).await?;
+        println!("Database created.");
     }
 
     // connect to the database
@@ -36,16 +54,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("Connected to database: {}", db_url);
 
-    // create table if not exists
-    match create_table(&pool).await {
-        Ok(_) => println!("Table created successfully."),
-        Err(error) => panic!("Error creating table: {}", error),
+    // create or upgrade the tables
+    match run_migrations(&pool).await {
+        Ok(applied) if applied.is_empty() => println!("Schema is up to date."),
+        Ok(applied) => println!("Applied migrations: {:?}", applied),
+        Err(error) => panic!("Error migrating database: {}", error),
     }
+    println!("Schema version: {}", current_version(&pool).await?);
+
+    // fill an empty database, merge into an existing one so it keeps its progress
+    let existing = sqlx::query("SELECT COUNT(*) FROM words")
+        .fetch_one(&pool)
+        .await?
+        .get::<i64, _>(0);
 
-    // insert records to the database
-    match bulk_insert_words(&pool, records).await {
-        Ok(_) => println!("Records inserted successfully."),
-        Err(error) => panic!("Error inserting records: {}", error),
+    if existing == 0 {
+        // insert records to the database
+        match bulk_insert_words(&pool, records).await {
+            Ok(_) => println!("Records inserted successfully."),
+            Err(error) => panic!("Error inserting records: {}", error),
+        }
+    } else {
+        println!("Database already has {} words, merging the csv files, user progress is kept.", existing);
+        let report = merge_import_words(&pool, records, options).await?;
+        println!("{}", report);
+        if options.dry_run {
+            println!("Dry run, nothing was written.");
+        }
     }
 
     // check the number of records
@@ -57,6 +92,3 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
-
-
-
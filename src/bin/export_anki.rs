@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+use sqlx::sqlite::SqlitePoolOptions;
+use dxgui::db::*;
+use dxgui::anki::export_selection;
+use dxgui::migrations::run_migrations;
+
+/// Exports words to an Anki package.
+/// Usage: export_anki <output.apkg|output.colpkg> [n1..n5] [--marked] [--practiced]
+/// A `.colpkg` output is written as a whole collection, anything else as a deck.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(output) = args.iter().find(|arg| !arg.starts_with("--") && JLPTlv::from_string(arg).is_none()) else {
+        eprintln!("Usage: export_anki <output.apkg|output.colpkg> [n1..n5] [--marked] [--practiced]");
+        std::process::exit(1);
+    };
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(DB_URL)
+        .await?;
+    run_migrations(&pool).await?;
+
+    let mut select = ProgressSelect::new();
+    let mut deck_name = "JLPT".to_string();
+    if let Some(jlpt) = args.iter().find_map(|arg| JLPTlv::from_string(arg)) {
+        select = select.select_jlpt(jlpt);
+        deck_name = format!("JLPT {}", jlpt.to_string().to_uppercase());
+    }
+    if args.iter().any(|arg| arg == "--marked") {
+        select = select.select_user_mark(true);
+    }
+    if args.iter().any(|arg| arg == "--practiced") {
+        select = select.select_practice_time(1);
+    }
+
+    let path = PathBuf::from(output);
+    let count = export_selection(&pool, select, &deck_name, &path).await?;
+    println!("Exported {} words to {}", count, path.display());
+
+    Ok(())
+}
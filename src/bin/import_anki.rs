@@ -0,0 +1,112 @@
+use std::path::PathBuf;
+use sqlx::sqlite::SqlitePoolOptions;
+use dxgui::db::*;
+use dxgui::anki::{import_anki_package, AnkiFieldMap, AnkiImportOptions, AnkiPackageReader};
+use dxgui::migrations::run_migrations;
+
+/// Imports the notes of an Anki package into the word database.
+/// Usage: import_anki <deck.apkg> [options]
+/// --note-type <name>     note type to import, defaults to the one with the most notes
+/// --expression <field>   note field for the expression, the other fields likewise:
+/// --reading <field>      --meaning <field>  --jlpt-field <field>
+/// --jlpt <n1..n5>        level for notes without a jlpt field or JLPT tag, default n5
+/// --progress             carry the Anki review state into the progress columns
+/// --dry-run              only print the report
+/// Fields that are not given are guessed from their names.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flag_value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+    };
+    let Some(package) = args.first().filter(|arg| !arg.starts_with("--")) else {
+        eprintln!("Usage: import_anki <deck.apkg> [--note-type <name>] [--expression <field>] [--reading <field>] [--meaning <field>] [--jlpt-field <field>] [--jlpt <n1..n5>] [--progress] [--dry-run]");
+        std::process::exit(1);
+    };
+    let path = PathBuf::from(package);
+
+    // show what is in the package so the user can pick the fields
+    let reader = AnkiPackageReader::open(&path).await?;
+    let note_types = reader.note_types().await;
+    reader.close().await;
+    let note_types = note_types?;
+    for note_type in &note_types {
+        println!("Note type \"{}\" ({} notes): {}", note_type.name, note_type.note_count, note_type.fields.join(", "));
+    }
+
+    let note_type = match flag_value("--note-type") {
+        Some(name) => note_types.iter().find(|note_type| note_type.name == name),
+        None => note_types.first(),
+    };
+    let Some(note_type) = note_type else {
+        eprintln!("Note type not found.");
+        std::process::exit(1);
+    };
+
+    let default_jlpt = flag_value("--jlpt").unwrap_or_else(|| "n5".to_string());
+    if JLPTlv::from_string(&default_jlpt).is_none() {
+        eprintln!("Invalid JLPT level: {}", default_jlpt);
+        std::process::exit(1);
+    }
+
+    // start from the guessed mapping and apply the fields given on the command line
+    let mut map = AnkiFieldMap::guess(note_type, &default_jlpt).unwrap_or(AnkiFieldMap {
+        note_type_id: note_type.id,
+        expression: 0,
+        reading: None,
+        meaning: 1,
+        jlpt: None,
+        default_jlpt: default_jlpt.clone(),
+    });
+    let field_index = |name: String| match note_type.field_index(&name) {
+        Some(index) => index,
+        None => {
+            eprintln!("Field \"{}\" not found in note type \"{}\".", name, note_type.name);
+            std::process::exit(1);
+        }
+    };
+    if let Some(name) = flag_value("--expression") {
+        map.expression = field_index(name);
+    }
+    if let Some(name) = flag_value("--reading") {
+        map.reading = Some(field_index(name));
+    }
+    if let Some(name) = flag_value("--meaning") {
+        map.meaning = field_index(name);
+    }
+    if let Some(name) = flag_value("--jlpt-field") {
+        map.jlpt = Some(field_index(name));
+    }
+
+    let field_name = |index: usize| note_type.fields.get(index).cloned().unwrap_or_default();
+    println!(
+        "Mapping: expression = {}, reading = {}, meaning = {}, jlpt = {}",
+        field_name(map.expression),
+        map.reading.map(field_name).unwrap_or_else(|| "(expression)".to_string()),
+        field_name(map.meaning),
+        map.jlpt.map(field_name).unwrap_or_else(|| format!("(JLPT tag or {})", map.default_jlpt)),
+    );
+
+    let options = AnkiImportOptions {
+        carry_progress: args.iter().any(|arg| arg == "--progress"),
+        dry_run: args.iter().any(|arg| arg == "--dry-run"),
+    };
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(DB_URL)
+        .await?;
+    run_migrations(&pool).await?;
+
+    let report = import_anki_package(&pool, &path, &map, options).await?;
+    println!("{}", report);
+    if options.dry_run {
+        println!("Dry run, nothing was written.");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use std::fs::File;
+use std::io::BufReader;
+use sqlx::sqlite::SqlitePoolOptions;
+use dxgui::db::DB_URL;
+use dxgui::jmdict::import_jmdict;
+use dxgui::migrations::run_migrations;
+
+/// Enriches the word database with JMdict senses, part of speech, alternate forms and common flags.
+/// Usage: import_jmdict <JMdict_e.xml>
+/// Download and unzip JMdict_e.gz from https://www.edrdg.org/jmdict/edict_doc.html first.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: import_jmdict <JMdict_e.xml>");
+        std::process::exit(1);
+    };
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(DB_URL)
+        .await?;
+    run_migrations(&pool).await?;
+
+    println!("Reading {}, this takes a while...", path);
+    let report = import_jmdict(&pool, BufReader::new(File::open(&path)?)).await?;
+    println!("{}", report);
+
+    Ok(())
+}
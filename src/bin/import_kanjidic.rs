@@ -0,0 +1,29 @@
+use std::fs::File;
+use std::io::BufReader;
+use sqlx::sqlite::SqlitePoolOptions;
+use dxgui::db::DB_URL;
+use dxgui::kanji::import_kanjidic;
+use dxgui::migrations::run_migrations;
+
+/// Imports the kanji table from KANJIDIC2, kanji drill progress is kept when run again.
+/// Usage: import_kanjidic <kanjidic2.xml>
+/// Download and unzip kanjidic2.xml.gz from https://www.edrdg.org/wiki/index.php/KANJIDIC_Project first.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: import_kanjidic <kanjidic2.xml>");
+        std::process::exit(1);
+    };
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(DB_URL)
+        .await?;
+    run_migrations(&pool).await?;
+
+    let count = import_kanjidic(&pool, BufReader::new(File::open(&path)?)).await?;
+    println!("Imported {} kanji.", count);
+
+    Ok(())
+}
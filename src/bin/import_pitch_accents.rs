@@ -0,0 +1,29 @@
+use std::fs::File;
+use std::io::BufReader;
+use sqlx::sqlite::SqlitePoolOptions;
+use dxgui::db::DB_URL;
+use dxgui::pitch::import_pitch_accents;
+use dxgui::migrations::run_migrations;
+
+/// Imports pitch accents from a Kanjium style accents file, words already imported are replaced.
+/// Usage: import_pitch_accents <accents.txt>
+/// Each line is `expression<TAB>reading<TAB>accents`, e.g. accents.txt from https://github.com/mifunetoshiro/kanjium.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: import_pitch_accents <accents.txt>");
+        std::process::exit(1);
+    };
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(DB_URL)
+        .await?;
+    run_migrations(&pool).await?;
+
+    let count = import_pitch_accents(&pool, BufReader::new(File::open(&path)?)).await?;
+    println!("Imported the pitch accents of {} words.", count);
+
+    Ok(())
+}
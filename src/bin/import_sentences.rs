@@ -0,0 +1,30 @@
+use std::fs::File;
+use std::io::BufReader;
+use sqlx::sqlite::SqlitePoolOptions;
+use dxgui::db::DB_URL;
+use dxgui::cloze::import_sentences;
+use dxgui::migrations::run_migrations;
+
+/// Imports example sentences for the sentence blank tests, sentences already imported are skipped.
+/// Usage: import_sentences <sentences.tsv>
+/// Each line is `sentence<TAB>translation`, or a Tatoeba sentence pairs export
+/// `id<TAB>sentence<TAB>id<TAB>translation` from https://tatoeba.org/en/downloads.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: import_sentences <sentences.tsv>");
+        std::process::exit(1);
+    };
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(DB_URL)
+        .await?;
+    run_migrations(&pool).await?;
+
+    let count = import_sentences(&pool, BufReader::new(File::open(&path)?)).await?;
+    println!("Imported {} new sentences.", count);
+
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use dxgui::llm_json;
 use dxgui::utils;
 
 fn main() {
@@ -20,7 +21,7 @@ fn main() {
         }
         "#;
 
-    if let Some(json_str) = utils::extract_json_from_llm_output(messy_output) {
+    if let Some(json_str) = llm_json::repair_json(messy_output) {
         println!("--- Clean JSON Extracted ---");
         println!("{}", json_str);
 
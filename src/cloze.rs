@@ -0,0 +1,610 @@
+use std::error::Error;
+use std::io::BufRead;
+use std::time::Instant;
+
+use dioxus::prelude::*;
+use rand::seq::IndexedRandom;
+use rand::{rng, Rng};
+use sqlx::Row;
+use wana_kana::ConvertJapanese;
+
+use crate::db::*;
+use crate::distractor::pick_distractors;
+use crate::footer::{StatusMessage, StatusLevel};
+use crate::recall::{grade_reading, kana_answer, kana_as_typed, RecallResult};
+use crate::review_log::{log_review, ReviewEntry, ReviewMode};
+use crate::scheduler::{grade_word, Grade};
+use crate::tokenizer::{tokenize, Token};
+use crate::wordexplain_gemma::{cached_explanation, Example};
+use crate::Route;
+
+/// Corpus sentences looked at for one word, most of them are dropped when the word isn't a token of them.
+const CORPUS_CANDIDATES_PER_WORD: i64 = 200;
+/// Corpus sentences kept for one word.
+const CORPUS_SENTENCES_PER_WORD: usize = 20;
+
+/// An example sentence with the word blanked out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClozeSentence {
+    pub sentence: String,
+    pub translation: String,
+    /// The sentence before and after the blank.
+    pub before: String,
+    pub after: String,
+    /// The word as written in the sentence, e.g. "食べ" of "食べました".
+    pub blank: String,
+    /// The reading of the blank in hiragana, e.g. "たべ".
+    pub blank_reading: String,
+    /// The dictionary form of the blank, e.g. "食べる".
+    pub lemma: String,
+}
+
+/// A selected word and the sentence it is asked in.
+#[derive(Debug, Clone)]
+pub struct ClozeCard {
+    pub word: WordRecord,
+    pub cloze: ClozeSentence,
+}
+
+/// Parses one line of a sentence file, either `sentence<TAB>translation` or a Tatoeba
+/// sentence pairs export `id<TAB>sentence<TAB>id<TAB>translation`. The translation may be missing.
+pub fn parse_sentence_line(line: &str) -> Option<Example> {
+    let fields: Vec<&str> = line.trim_end_matches(['\r', '\n']).split('\t').map(str::trim).collect();
+    let (sentence, translation) = match fields.as_slice() {
+        [_, sentence, _, translation, ..] => (*sentence, *translation),
+        [sentence, translation, ..] => (*sentence, *translation),
+        [sentence] => (*sentence, ""),
+        _ => return None,
+    };
+    if sentence.is_empty() || sentence.starts_with('#') {
+        return None;
+    }
+    Some(Example { sentence: sentence.to_string(), translation: translation.to_string() })
+}
+
+/// Imports example sentences and returns the number of new ones, sentences already imported are skipped.
+pub async fn import_sentences<R: BufRead>(pool: &sqlx::SqlitePool, source: R) -> Result<usize, Box<dyn Error>> {
+    let mut transaction = pool.begin().await?;
+    let mut count = 0;
+    for line in source.lines() {
+        let Some(example) = parse_sentence_line(&line?) else {
+            continue;
+        };
+        let result = sqlx::query("INSERT OR IGNORE INTO example_sentences (sentence, translation) VALUES (?, ?)")
+            .bind(&example.sentence)
+            .bind(&example.translation)
+            .execute(&mut *transaction)
+            .await?;
+        count += result.rows_affected() as usize;
+    }
+    transaction.commit().await?;
+    Ok(count)
+}
+
+/// The part of the expression a conjugated form still contains, e.g. "食" of "食べる".
+fn search_stem(expression: &str) -> &str {
+    let stem = expression.trim_end_matches(|c: char| ('\u{3041}'..='\u{309F}').contains(&c));
+    if stem.is_empty() { expression } else { stem }
+}
+
+/// The kana every form of the word starts with, to find the sentences writing it in kana:
+/// "たべ" of "食べる", "べんきょう" of "勉強する", "ねこ" of "猫". None when that is a single kana,
+/// the "の" of "飲む" would match nearly every sentence.
+fn reading_stem(word: &WordRecord) -> Option<String> {
+    let reading = if word.reading.is_empty() { word.expression.to_hiragana() } else { word.reading.to_hiragana() };
+    let stem = if search_stem(&word.expression) == word.expression {
+        // no okurigana, nothing conjugates
+        reading.as_str()
+    } else if let Some(stem) = reading.strip_suffix("する") {
+        stem
+    } else {
+        // up to the kana that changes, the る of 食べる is the ま of 食べます
+        let mut chars = reading.chars();
+        chars.next_back();
+        chars.as_str()
+    };
+    (stem.chars().count() > 1).then(|| stem.to_string())
+}
+
+/// The sentences the word can be blanked out of: the examples of its cached explanation and
+/// some imported sentences containing its stem or the stem of its reading, see `reading_stem`.
+pub async fn cloze_sentences(pool: &sqlx::SqlitePool, word: &WordRecord) -> Result<Vec<ClozeSentence>, Box<dyn Error>> {
+    let mut clozes = Vec::new();
+    let examples = cached_explanation(pool, &word.expression)
+        .await?
+        .map(|cached| cached.examples)
+        .unwrap_or_default();
+    for example in examples {
+        clozes.extend(make_cloze(&example.sentence, &example.translation, word)?);
+    }
+
+    let rows = sqlx::query(
+        "SELECT sentence, translation FROM example_sentences WHERE instr(sentence, ?) > 0 OR instr(sentence, ?) > 0 \
+         ORDER BY RANDOM() LIMIT ?",
+    )
+    .bind(search_stem(&word.expression))
+    .bind(reading_stem(word))
+    .bind(CORPUS_CANDIDATES_PER_WORD)
+    .fetch_all(pool)
+    .await?;
+    let mut found = 0;
+    for row in rows {
+        if found >= CORPUS_SENTENCES_PER_WORD {
+            break;
+        }
+        if let Some(cloze) = make_cloze(row.get("sentence"), row.get("translation"), word)? {
+            clozes.push(cloze);
+            found += 1;
+        }
+    }
+    Ok(clozes)
+}
+
+/// The first and last token of the word in the sentence and its dictionary form.
+/// The last token may be conjugated, "食べ" + "まし" + "た" gives the tokens of "食べ" and "食べる".
+/// Sentences written in kana match the reading of the word.
+fn find_word_tokens(tokens: &[Token], word: &WordRecord) -> Option<(usize, usize, String)> {
+    if word.expression.is_empty() {
+        return None;
+    }
+    let reading = word.reading.to_hiragana();
+    let is_word = |text: &str| text == word.expression || (!reading.is_empty() && text.to_hiragana() == reading);
+    let longest = word.expression.chars().count().max(reading.chars().count());
+
+    for first in 0..tokens.len() {
+        let mut surfaces = String::new();
+        for (last, token) in tokens.iter().enumerate().skip(first) {
+            let lemma = format!("{}{}", surfaces, token.base_form);
+            surfaces.push_str(&token.surface);
+            if is_word(&surfaces) || is_word(&lemma) {
+                return Some((first, last, lemma));
+            }
+            if surfaces.chars().count() >= longest {
+                break;
+            }
+        }
+    }
+    None
+}
+
+/// Blanks the word out of a tokenized sentence. None when no tokens make up the word,
+/// 日 is not blanked out of 日本語.
+fn cloze_from_tokens(sentence: &str, translation: &str, tokens: &[Token], word: &WordRecord) -> Option<ClozeSentence> {
+    // where each token is in the sentence, the tokenizer may drop spaces
+    let mut spans = Vec::new();
+    let mut cursor = 0;
+    for token in tokens {
+        let Some(start) = sentence[cursor..].find(&token.surface).map(|offset| cursor + offset) else {
+            break;
+        };
+        cursor = start + token.surface.len();
+        spans.push((start, cursor));
+    }
+
+    let (first, last, lemma) = find_word_tokens(tokens, word).filter(|(_, last, _)| *last < spans.len())?;
+    let (start, end) = (spans[first].0, spans[last].1);
+    let blank_reading: String = tokens[first..=last]
+        .iter()
+        .map(|token| if token.reading.is_empty() { token.surface.to_hiragana() } else { token.reading_hiragana() })
+        .collect();
+
+    Some(ClozeSentence {
+        sentence: sentence.to_string(),
+        translation: translation.to_string(),
+        before: sentence[..start].to_string(),
+        after: sentence[end..].to_string(),
+        blank: sentence[start..end].to_string(),
+        blank_reading,
+        lemma,
+    })
+}
+
+/// Blanks `word` out of a sentence along the token boundaries of the shared tokenizer.
+pub fn make_cloze(sentence: &str, translation: &str, word: &WordRecord) -> Result<Option<ClozeSentence>, Box<dyn Error>> {
+    let tokens = tokenize(sentence)?;
+    Ok(cloze_from_tokens(sentence, translation, &tokens, word))
+}
+
+/// Whether the answer fills the blank: the form in the sentence, the dictionary form,
+/// or the reading of either, typed in kana or romaji.
+pub fn grade_cloze(input: &str, cloze: &ClozeSentence, word: &WordRecord) -> bool {
+    let typed = input.trim();
+    if typed.is_empty() {
+        return false;
+    }
+    typed == cloze.blank
+        || typed == cloze.lemma
+        || typed == word.expression
+        || kana_answer(typed) == cloze.blank_reading
+        || grade_reading(typed, &word.expression, &word.reading) == RecallResult::Correct
+}
+
+/// One card per word, in a random sentence of the word. Words without a sentence are left out.
+pub async fn build_cloze_cards(pool: &sqlx::SqlitePool, words: &[WordRecord]) -> Result<Vec<ClozeCard>, Box<dyn Error>> {
+    let mut cards = Vec::new();
+    for word in words {
+        if let Some(cloze) = cloze_sentences(pool, word).await?.choose(&mut rng()) {
+            cards.push(ClozeCard { word: word.clone(), cloze: cloze.clone() });
+        }
+    }
+    Ok(cards)
+}
+
+/// Updates the schedule of the word and logs the answer as a cloze review.
+pub async fn record_cloze(pool: &sqlx::SqlitePool, word_id: i64, correct: bool, response_ms: i64) -> Result<(), sqlx::Error> {
+    let grade = if correct { Grade::Good } else { Grade::Again };
+    grade_word(pool, word_id, grade).await?;
+    log_review(pool, &ReviewEntry::new(word_id, ReviewMode::Cloze, true, grade, response_ms)).await?;
+    Ok(())
+}
+
+/// Example sentences of the selected words with the word blanked out, the word is picked
+/// from its dictionary form and a few others or typed. Enter goes to the next card.
+#[component]
+pub fn ClozeTest(typing: bool) -> Element {
+    let navigator = use_navigator();
+    let select_words = use_context::<Signal<Vec<WordRecord>>>();
+    let mut status_message = use_context::<Signal<StatusMessage>>();
+    let db_pool = use_context::<sqlx::SqlitePool>();
+    let pool_cards = db_pool.clone();
+    let pool_choices = db_pool.clone();
+
+    let mut index = use_signal(|| 0_usize);
+    let mut input = use_signal(String::new);
+    let mut result = use_signal(|| None::<bool>); // whether the answer was right, once checked
+    let mut picked = use_signal(|| None::<usize>);
+    let mut score = use_signal(|| 0);
+    let mut card_shown_at = use_signal(Instant::now); // used to measure the response time
+
+    let cards = use_resource(move || {
+        let pool = pool_cards.clone();
+        async move {
+            match build_cloze_cards(&pool, &select_words()).await {
+                Ok(cards) => cards,
+                Err(e) => {
+                    eprintln!("Failed to build the cloze cards: {}", e);
+                    status_message.set(StatusMessage {
+                        message: "Could not read the example sentences.".to_string(),
+                        level: StatusLevel::Error,
+                    });
+                    Vec::new()
+                }
+            }
+        }
+    });
+    let current_card = move || cards.read().as_ref().and_then(|cards| cards.get(index()).cloned());
+
+    // the dictionary form of the word and up to 3 other words, shuffled
+    let choices = use_resource(move || {
+        let pool = pool_choices.clone();
+        async move {
+            let Some(card) = current_card() else {
+                return Vec::new();
+            };
+            if typing {
+                return Vec::new();
+            }
+            let mut answers: Vec<String> = match pick_distractors(&pool, &card.word, 3).await {
+                Ok(words) => words.into_iter().map(|word| word.expression).collect(),
+                Err(e) => {
+                    eprintln!("Failed to pick the other answers: {}", e);
+                    Vec::new()
+                }
+            };
+            let idx = rng().random_range(0..=answers.len());
+            answers.insert(idx, card.word.expression.clone());
+            answers
+        }
+    });
+
+    let check_answer = use_callback(move |given: String| {
+        if result().is_some() {
+            return;
+        }
+        let Some(card) = current_card() else {
+            return;
+        };
+        let correct = grade_cloze(&given, &card.cloze, &card.word);
+        if correct {
+            score += 1;
+        }
+        result.set(Some(correct));
+
+        let pool = db_pool.clone();
+        let response_ms = card_shown_at().elapsed().as_millis() as i64;
+        spawn(async move {
+            if let Err(e) = record_cloze(&pool, card.word.id, correct, response_ms).await {
+                eprintln!("Failed to record the answer for word {}: {}", card.word.id, e);
+                status_message.set(StatusMessage {
+                    message: "A database error occurred.".to_string(),
+                    level: StatusLevel::Error,
+                });
+            }
+        });
+    });
+
+    let next_card = use_callback(move |()| {
+        if result().is_none() {
+            return;
+        }
+        let total_cards = cards.read().as_ref().map(|cards| cards.len()).unwrap_or(0);
+        if index() + 1 < total_cards {
+            index += 1;
+        } else {
+            status_message.set(StatusMessage {
+                message: format!("Sentences finished! Your score: {}/{}", score(), total_cards),
+                level: StatusLevel::Info,
+            });
+            index.set(0);
+            score.set(0);
+        }
+        input.set(String::new());
+        result.set(None);
+        picked.set(None);
+        card_shown_at.set(Instant::now());
+    });
+
+    let go_back = move |_: MouseEvent| {
+        navigator.push(Route::GnerateTestCard {});
+    };
+
+    let Some(total_cards) = cards.read().as_ref().map(|cards| cards.len()) else {
+        return rsx! {
+            div { class: "container p-4", p { class: "lead", "Looking for example sentences..." } }
+        };
+    };
+    let Some(card) = current_card() else {
+        return rsx! {
+            div { class: "container p-4",
+                div { class: "alert alert-info",
+                    "None of the selected words has an example sentence yet. Explain the words first, "
+                    "or import sentences with "
+                    code { "cargo run --bin import_sentences" }
+                    "."
+                }
+                button { class: "btn btn-secondary", onclick: go_back, "Go Back" }
+            }
+        };
+    };
+    let answers = choices().unwrap_or_default();
+
+    let choice_class = |i: usize, choice: &str| match (result(), picked()) {
+        (Some(_), _) if choice == card.word.expression => "card mb-2 bg-success text-light",
+        (Some(_), Some(p)) if p == i => "card mb-2 bg-danger text-light",
+        _ => "card clickable mb-2 bg-dark text-light",
+    };
+
+    rsx! {
+        div { class: "container h-75 d-flex flex-column",
+            tabindex: "0",
+            onmounted: move |evt| {
+                // Focus the div when mounted to capture key events
+                if !typing {
+                    let element = evt.data();
+                    spawn(async move {
+                        let _ = element.set_focus(true).await;
+                    });
+                }
+            },
+            onkeydown: move |event: KeyboardEvent| {
+                // typing mode handles its keys in the input
+                if typing {
+                    return;
+                }
+                match event.key() {
+                    Key::Enter => next_card.call(()),
+                    Key::Character(s) => {
+                        let answers = choices().unwrap_or_default();
+                        if let Some(i) = s.parse::<usize>().ok().filter(|i| (1..=answers.len()).contains(i)) {
+                            picked.set(Some(i - 1));
+                            check_answer.call(answers[i - 1].clone());
+                        }
+                    }
+                    _ => {}
+                }
+            },
+
+            // --- Top Controls ---
+            div { class: "row my-3",
+                div { class: "col-auto",
+                    button { class: "btn btn-secondary", onclick: go_back, "Go Back" }
+                }
+                div { class: "col d-flex align-items-center",
+                    h4 { class: "mb-0", "Fill in the missing word" }
+                }
+            }
+
+            // --- Main Content ---
+            div { class: "row flex-grow-1 d-flex flex-column justify-content-center",
+                p { class: "display-6 my-3",
+                    "{card.cloze.before}"
+                    if result().is_some() {
+                        span { class: "text-warning border-bottom border-2 border-warning", "{card.cloze.blank}" }
+                    } else {
+                        span { class: "border-bottom border-2 px-5 mx-1" }
+                    }
+                    "{card.cloze.after}"
+                }
+                if !card.cloze.translation.is_empty() {
+                    p { class: "text-secondary", "{card.cloze.translation}" }
+                }
+
+                if typing {
+                    input {
+                        class: "form-control form-control-lg",
+                        r#type: "text",
+                        placeholder: "the word, e.g. taberu or 食べる",
+                        value: "{input}",
+                        readonly: result().is_some(),
+                        onmounted: move |evt| {
+                            let element = evt.data();
+                            spawn(async move {
+                                let _ = element.set_focus(true).await;
+                            });
+                        },
+                        oninput: move |evt| input.set(kana_as_typed(&evt.value())),
+                        onkeydown: move |evt| {
+                            if evt.key() == Key::Enter {
+                                if result().is_none() {
+                                    check_answer.call(input());
+                                } else {
+                                    next_card.call(());
+                                }
+                            }
+                        },
+                    }
+                } else {
+                    div { class: "d-flex flex-column gap-3",
+                        for (i, choice) in answers.iter().cloned().enumerate() {
+                            div {
+                                class: choice_class(i, &choice),
+                                style: "cursor: pointer;",
+                                onclick: move |_| {
+                                    picked.set(Some(i));
+                                    check_answer.call(choice.clone());
+                                },
+                                div { class: "card-body d-flex align-items-center",
+                                    span { class: "badge bg-secondary me-3", style: "width: 2rem;", u { "{i + 1}" } }
+                                    span { "{choice}" }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                match result() {
+                    Some(true) => rsx! { div { class: "alert alert-success mt-3", "Correct!" } },
+                    Some(false) => rsx! { div { class: "alert alert-danger mt-3", "Not quite." } },
+                    None => rsx! {},
+                }
+                if result().is_some() {
+                    div { class: "mt-1",
+                        p { class: "lead mb-1", "{card.word.expression} 【{card.word.reading}】" }
+                        if card.cloze.blank != card.cloze.lemma {
+                            p { class: "mb-1", "Written here as {card.cloze.blank}, from {card.cloze.lemma}." }
+                        }
+                        p { class: "text-secondary", "{card.word.meaning}" }
+                    }
+                }
+            }
+
+            // --- Progress Bar ---
+            div { class: "row my-3 align-items-center",
+                div { class: "col",
+                    div { class: "progress",
+                        div {
+                            class: "progress-bar",
+                            role: "progressbar",
+                            style: "width: {((index() + 1) as f32 / total_cards as f32) * 100.0}%",
+                        }
+                    }
+                }
+                div { class: "col-auto", span { "{index() + 1} / {total_cards}" } }
+                div { class: "col-auto", span { class: "text-success", "Score: {score}" } }
+            }
+
+            // --- Bottom Controls ---
+            div { class: "row my-3",
+                div { class: "col",
+                    button {
+                        class: "btn btn-outline-danger w-100",
+                        disabled: result().is_some(),
+                        onclick: move |_| check_answer.call(String::new()),
+                        "I don't know"
+                    }
+                }
+                div { class: "col",
+                    if result().is_none() && typing {
+                        button { class: "btn btn-primary w-100", onclick: move |_| check_answer.call(input()), "Check (Enter)" }
+                    } else {
+                        button { class: "btn btn-primary w-100", disabled: result().is_none(), onclick: move |_| next_card.call(()), "Next (Enter)" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::review_log::reviews_for_word;
+    use crate::wordexplain_gemma::{save_explanation, WordExplanation};
+
+    #[test]
+    fn test_make_cloze() {
+        let taberu = test_word("食べる", "たべる", "", "n5");
+        let cloze = make_cloze("昨日、寿司を食べました。", "I ate sushi yesterday.", &taberu).unwrap().unwrap();
+        assert_eq!(
+            (cloze.before.as_str(), cloze.blank.as_str(), cloze.after.as_str()),
+            ("昨日、寿司を", "食べ", "ました。")
+        );
+        assert_eq!((cloze.lemma.as_str(), cloze.blank_reading.as_str()), ("食べる", "たべ"));
+
+        // the dictionary form, the form in the sentence and their readings all fill the blank
+        for answer in ["食べる", "食べ", "taberu", "たべ", " tabe "] {
+            assert!(grade_cloze(answer, &cloze, &taberu), "{} should be accepted", answer);
+        }
+        assert!(!grade_cloze("飲む", &cloze, &taberu));
+        assert!(!grade_cloze("", &cloze, &taberu));
+
+        // a suru verb spans two tokens
+        let cloze = make_cloze("毎日日本語を勉強します。", "", &test_word("勉強する", "べんきょうする", "", "n5")).unwrap().unwrap();
+        assert_eq!((cloze.blank.as_str(), cloze.lemma.as_str(), cloze.after.as_str()), ("勉強し", "勉強する", "ます。"));
+
+        assert_eq!(make_cloze("猫が好きです。", "", &taberu).unwrap(), None);
+
+        // a kanji inside a longer word is not the word
+        assert_eq!(make_cloze("毎日日本語を勉強します。", "", &test_word("日", "ひ", "", "n5")).unwrap(), None);
+        assert_eq!(make_cloze("人間は考える葦である。", "", &test_word("人", "ひと", "", "n5")).unwrap(), None);
+
+        // kana sentences are found by the reading up to the kana that conjugates
+        assert_eq!(reading_stem(&taberu).as_deref(), Some("たべ"));
+        assert_eq!(reading_stem(&test_word("勉強する", "べんきょうする", "", "n5")).as_deref(), Some("べんきょう"));
+        assert_eq!(reading_stem(&test_word("猫", "ねこ", "", "n5")).as_deref(), Some("ねこ"));
+        assert_eq!(reading_stem(&test_word("飲む", "のむ", "", "n5")), None);
+    }
+
+    #[tokio::test]
+    async fn test_cloze_cards() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![test_word("食べる", "たべる", "", "n5"), test_word("猫", "ねこ", "", "n5")]).await.unwrap();
+
+        assert_eq!(
+            parse_sentence_line("1\t魚を食べた。\t2\tI ate fish.\n"),
+            Some(Example { sentence: "魚を食べた。".to_string(), translation: "I ate fish.".to_string() })
+        );
+        assert_eq!(parse_sentence_line("食事です。").unwrap().translation, "");
+        assert_eq!(parse_sentence_line("# comment"), None);
+
+        let source = "1\t魚を食べた。\t2\tI ate fish.\n食事です。\tIt's a meal.\n魚を食べた。\tduplicate\nうちのねこだった。\tIt was our cat.\nりんごをたべた。\tI ate an apple.\n";
+        assert_eq!(import_sentences(&pool, source.as_bytes()).await.unwrap(), 4);
+
+        let example = |sentence: &str| Example { sentence: sentence.to_string(), translation: String::new() };
+        let explanation = WordExplanation {
+            explain: "to eat".to_string(),
+            example1: example("パンを食べます。"),
+            example2: example("何を食べたい？"),
+            example3: example("よく食べる。"),
+        };
+        save_explanation(&pool, "食べる", &explanation, "gemma3:4b").await.unwrap();
+
+        let words = find_word_by_ids(&pool, vec![1, 2]).await.unwrap();
+        // the cached examples and the imported sentences with the word in kanji or kana, 食事 isn't it
+        let clozes = cloze_sentences(&pool, &words[0]).await.unwrap();
+        assert_eq!(clozes.len(), 5);
+        assert!(clozes.iter().any(|cloze| cloze.blank == "たべ"));
+        assert!(clozes.iter().all(|cloze| cloze.lemma == "食べる"));
+
+        // 猫 is only written in kana
+        let cards = build_cloze_cards(&pool, &words).await.unwrap();
+        assert_eq!(cards.len(), 2);
+        assert_eq!(cards[0].cloze.lemma, "食べる");
+        assert_eq!((cards[1].cloze.sentence.as_str(), cards[1].cloze.blank.as_str()), ("うちのねこだった。", "ねこ"));
+
+        record_cloze(&pool, 1, true, 1500).await.unwrap();
+        assert_eq!(find_word_by_ids(&pool, vec![1]).await.unwrap()[0].practice_time, 1);
+        assert_eq!(reviews_for_word(&pool, 1).await.unwrap()[0].mode, ReviewMode::Cloze);
+    }
+}
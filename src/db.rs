@@ -1,11 +1,16 @@
 use sqlx::{migrate::MigrateDatabase, Sqlite};
 use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
 use std::error::Error;
 use std::fs;
 use csv::{ReaderBuilder};
+use crate::scheduler::ReviewState;
 
 pub const DB_URL: &str = "sqlite:data/words_database.db";
 
+/// Columns used by every WordRecord query, the word's tags are joined into a space separated `tags` column.
+pub const WORD_COLUMNS: &str = "words.*, (SELECT GROUP_CONCAT(tags.name, ' ') FROM word_tags JOIN tags ON tags.id = word_tags.tag_id WHERE word_tags.word_id = words.id) AS tags";
+
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -84,6 +89,66 @@ pub struct WordRecord {
     pub familiar: bool,
     pub user_mark: bool,
 
+    // spaced repetition state, see scheduler.rs
+    pub ease: f64,
+    pub interval_days: i64,
+    pub due_day: i64,
+    pub lapses: i64,
+
+    // tags from the csv, e.g. "Genki_Ln.9", "JLPT_3"
+    pub tags: Vec<String>,
+}
+
+impl Default for WordRecord {
+    fn default() -> Self {
+        WordRecord {
+            id: 0,
+            expression: String::new(),
+            reading: String::new(),
+            meaning: String::new(),
+            jlpt: String::new(),
+            practice_time: 0,
+            familiar: false,
+            user_mark: false,
+            ease: crate::scheduler::DEFAULT_EASE,
+            interval_days: 0,
+            due_day: 0,
+            lapses: 0,
+            tags: Vec::new(),
+        }
+    }
+}
+
+impl WordRecord {
+    /// The distinct kanji of the expression, in order, e.g. "食べ物" -> ['食', '物'].
+    pub fn kanji(&self) -> Vec<char> {
+        crate::kanji::kanji_in(&self.expression)
+    }
+
+    /// Builds a WordRecord from a `SELECT {WORD_COLUMNS} FROM words` row.
+    /// Rows without the `tags` column get an empty tag list.
+    pub fn from_row(row: &SqliteRow) -> Self {
+        WordRecord {
+            id: row.get::<i64, _>("id"),
+            expression: row.get::<String, _>("expression"),
+            reading: row.get::<String, _>("reading"),
+            meaning: row.get::<String, _>("meaning"),
+            jlpt: row.get::<String, _>("jlpt"),
+            practice_time: row.get::<i64, _>("practice_time"),
+            familiar: row.get::<bool, _>("familiar"),
+            user_mark: row.get::<bool, _>("user_mark"),
+            ease: row.get::<f64, _>("ease"),
+            interval_days: row.get::<i64, _>("interval_days"),
+            due_day: row.get::<i64, _>("due_day"),
+            lapses: row.get::<i64, _>("lapses"),
+            tags: row
+                .try_get::<Option<String>, _>("tags")
+                .ok()
+                .flatten()
+                .map(|tags| tags.split_whitespace().map(|t| t.to_string()).collect())
+                .unwrap_or_default(),
+        }
+    }
 }
 
 pub fn load_csv_to_word_records(
@@ -107,6 +172,8 @@ pub fn load_csv_to_word_records(
             practice_time: 0, // DB auto assign 0 when initiate
             familiar: false, // DB  auto assign false when initiate
             user_mark: false, // DB auto assign false when initiate
+            tags: record[3].split_whitespace().map(|t| t.to_string()).collect(),
+            ..Default::default() // scheduler fields also use DB defaults
         });
     }
     Ok(())
@@ -136,27 +203,10 @@ pub async fn reset_database(db_url: &str) -> Result<(), sqlx::Error> {
     Ok(())
 }
 
+/// Creates or upgrades all tables by running the pending schema migrations, see migrations.rs.
+/// This never drops data, so it is safe to call on an existing database.
 pub async fn create_table(pool: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS words (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            expression TEXT NOT NULL,
-            reading TEXT NOT NULL,
-            meaning TEXT NOT NULL,
-            jlpt TEXT NOT NULL, 
-
-            -- User Progress Fields --
-            practice_time INTEGER NOT NULL DEFAULT 0,
-            familiar      BOOLEAN NOT NULL DEFAULT 0,
-            user_mark     BOOLEAN NOT NULL DEFAULT 0
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
-   
-
+    crate::migrations::run_migrations(pool).await?;
     Ok(())
 }
 
@@ -164,7 +214,7 @@ pub async fn bulk_insert_words(pool: &sqlx::SqlitePool, records: Vec<WordRecord>
     let mut transaction = pool.begin().await?;
 
     for record in records {
-        sqlx::query(
+        let result = sqlx::query(
             r#"
             INSERT INTO words (expression, reading, meaning, jlpt)
             VALUES (?, ?, ?, ?)
@@ -175,11 +225,62 @@ pub async fn bulk_insert_words(pool: &sqlx::SqlitePool, records: Vec<WordRecord>
             .bind(&record.jlpt)
             .execute(&mut *transaction)
             .await?;
+        add_word_tags(&mut transaction, result.last_insert_rowid(), &record.tags).await?;
     }
 
     transaction.commit().await?;
     Ok(())
 }
+
+/// Attaches tags to a word, creating missing tags. Tags the word already has are ignored.
+pub(crate) async fn add_word_tags(conn: &mut sqlx::SqliteConnection, word_id: i64, tags: &[String]) -> Result<(), sqlx::Error> {
+    for tag in tags {
+        sqlx::query("INSERT OR IGNORE INTO tags (name) VALUES (?)")
+            .bind(tag)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO word_tags (word_id, tag_id)
+            SELECT ?, id FROM tags WHERE name = ?
+            "#,)
+            .bind(word_id)
+            .bind(tag)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Replaces all tags of a word.
+pub async fn set_word_tags(pool: &sqlx::SqlitePool, word_id: i64, tags: &[String]) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query("DELETE FROM word_tags WHERE word_id = ?")
+        .bind(word_id)
+        .execute(&mut *transaction)
+        .await?;
+    add_word_tags(&mut transaction, word_id, tags).await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Returns every tag with the number of words using it, sorted by name.
+pub async fn list_tags(pool: &sqlx::SqlitePool) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT tags.name AS name, COUNT(word_tags.word_id) AS count
+        FROM tags
+        JOIN word_tags ON word_tags.tag_id = tags.id
+        JOIN words ON words.id = word_tags.word_id
+        WHERE words.deleted = 0
+        GROUP BY tags.id
+        ORDER BY tags.name
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(|row| (row.get("name"), row.get("count"))).collect())
+}
 /// Finds a word by its field and value, returning a vector of IDs.
 pub async fn find_word_ids(pool: &sqlx::SqlitePool, field: WordField, value: &str) -> Result<Vec<i64>, sqlx::Error> {
     
@@ -205,21 +306,12 @@ pub async fn find_word_ids(pool: &sqlx::SqlitePool, field: WordField, value: &st
 pub async fn find_word_by_ids(pool: &sqlx::SqlitePool, ids: Vec<i64>) -> Result<Vec<WordRecord>, sqlx::Error> {
     let mut records = Vec::new();
     for id in ids {
-        let rows = sqlx::query("SELECT * FROM words WHERE id = ?")
+        let rows = sqlx::query(&format!("SELECT {} FROM words WHERE id = ?", WORD_COLUMNS))
             .bind(id)
             .fetch_all(pool)
             .await?;
         for row in rows {
-            records.push(WordRecord {
-                id: row.get::<i64, _>("id"),
-                expression: row.get::<String, _>("expression"),
-                reading: row.get::<String, _>("reading"),
-                meaning: row.get::<String, _>("meaning"),
-                jlpt: row.get::<String, _>("jlpt"),
-                practice_time: row.get::<i64, _>("practice_time"),
-                familiar: row.get::<bool, _>("familiar"),
-                user_mark: row.get::<bool, _>("user_mark"),
-            });
+            records.push(WordRecord::from_row(&row));
         }
     }
     Ok(records)
@@ -242,23 +334,23 @@ pub async fn return_words_by_user_progress(
     let query = if random {
         format!(
             r#"
-            SELECT *
+            SELECT {}
             FROM words
-            WHERE jlpt = ? AND practice_time >= ? AND familiar = ? AND user_mark = ?
+            WHERE deleted = 0 AND jlpt = ? AND practice_time >= ? AND familiar = ? AND user_mark = ?
             ORDER BY RANDOM()
             LIMIT {}
             "#,
-            num
+            WORD_COLUMNS, num
         )
     }  else {
         format!(
             r#"
-            SELECT *
+            SELECT {}
             FROM words
-            WHERE jlpt = ? AND practice_time >= ? AND familiar = ? AND user_mark = ?
+            WHERE deleted = 0 AND jlpt = ? AND practice_time >= ? AND familiar = ? AND user_mark = ?
             LIMIT {}
             "#,
-            num
+            WORD_COLUMNS, num
         )
     };
 
@@ -271,17 +363,8 @@ pub async fn return_words_by_user_progress(
         .await?;
 
     let records = rows
-        .into_iter()
-        .map(|row| WordRecord {
-            id: row.get::<i64, _>("id"),
-            expression: row.get::<String, _>("expression"),
-            reading: row.get::<String, _>("reading"),
-            meaning: row.get::<String, _>("meaning"),
-            jlpt: row.get::<String, _>("jlpt"),
-            practice_time: row.get::<i64, _>("practice_time"),
-            familiar: row.get::<bool, _>("familiar"),
-            user_mark: row.get::<bool, _>("user_mark"),
-        })
+        .iter()
+        .map(WordRecord::from_row)
         .collect();
     eprintln!("return_words_by_user_progress called");
     Ok(records)
@@ -306,11 +389,11 @@ pub async fn update_user_progress(pool: &sqlx::SqlitePool, word_id: i64, familia
 
 
 /// Resets all user progress fields for all words in the database.
-/// This function sets `practice_time`, `familiar`, and `user_mark` back to their default values (0 or false).
+/// This function sets `practice_time`, `familiar`, `user_mark` and the scheduler fields back to their default values.
 pub async fn reset_all_user_progress(pool: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
     // This query resets all progress fields for all words back to their default state.
     sqlx::query(
-        "UPDATE words SET practice_time = 0, familiar = 0, user_mark = 0"
+        "UPDATE words SET practice_time = 0, familiar = 0, user_mark = 0, ease = 2.5, interval_days = 0, due_day = 0, lapses = 0"
     )
     .execute(pool)
     .await?;
@@ -325,7 +408,7 @@ pub async fn count_unique_practiced_words(pool: &sqlx::SqlitePool) -> Result<i64
         r#"
         SELECT COUNT(id)
         FROM words
-        WHERE practice_time > 0
+        WHERE deleted = 0 AND practice_time > 0
         "#,
     )
     .fetch_one(pool)
@@ -337,7 +420,7 @@ pub async fn count_unique_practiced_words(pool: &sqlx::SqlitePool) -> Result<i64
 /// counts total number of words practiced by user
 pub async fn count_total_practiced_words(pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
     let count: i64 = sqlx::query_scalar(
-        "SELECT SUM(practice_time) as total FROM words"
+        "SELECT SUM(practice_time) as total FROM words WHERE deleted = 0"
     )
     .fetch_one(pool)
     .await?;
@@ -350,7 +433,7 @@ pub async fn count_total_familiar_words(pool: &sqlx::SqlitePool) -> Result<i64,
         r#"
         SELECT COUNT(id)
         FROM words
-        WHERE familiar = 1
+        WHERE deleted = 0 AND familiar = 1
         "#,
     )
     .fetch_one(pool)
@@ -364,7 +447,7 @@ pub async fn count_total_user_marked_words(pool: &sqlx::SqlitePool) -> Result<i6
         r#"
         SELECT COUNT(id)
         FROM words
-        WHERE user_mark = 1
+        WHERE deleted = 0 AND user_mark = 1
         "#,
     )
     .fetch_one(pool)
@@ -372,13 +455,28 @@ pub async fn count_total_user_marked_words(pool: &sqlx::SqlitePool) -> Result<i6
     Ok(count)
 }
 
+/// Counts practiced words that are due for review on or before `day` (see scheduler::today).
+pub async fn count_due_words(pool: &sqlx::SqlitePool, day: i64) -> Result<i64, sqlx::Error> {
+    let count: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(id)
+        FROM words
+        WHERE deleted = 0 AND practice_time > 0 AND due_day <= ?
+        "#,
+    )
+    .bind(day)
+    .fetch_one(pool)
+    .await?;
+    Ok(count)
+}
+
 /// Counts unfamiliar words that have been practiced (i.e., practice_time > 0 and familiar = 0).
 pub async fn count_unfamiliar_practiced_words(pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
     let count: i64 = sqlx::query_scalar(
         r#"
         SELECT COUNT(id)
         FROM words
-        WHERE practice_time > 0 AND familiar = 0
+        WHERE deleted = 0 AND practice_time > 0 AND familiar = 0
         "#,
     )
     .fetch_one(pool)
@@ -419,19 +517,30 @@ pub async fn delete_words(pool: &sqlx::SqlitePool, ids: Vec<i64>) -> Result<(),
     Ok(())
 }
 
+/// Inserts words together with their progress columns (practice_time, familiar, user_mark and the schedule),
+/// e.g. words imported from Anki. Use `WordRecord::default()` fields for brand new words.
 pub async fn insert_words(pool: &sqlx::SqlitePool, records: Vec<WordRecord>) -> Result<(), sqlx::Error> {
+    let mut conn = pool.acquire().await?;
     for record in records {
-        sqlx::query(
+        let result = sqlx::query(
             r#"
-            INSERT INTO words (expression, reading, meaning, jlpt)
-            VALUES (?, ?, ?, ?)
+            INSERT INTO words (expression, reading, meaning, jlpt, practice_time, familiar, user_mark, ease, interval_days, due_day, lapses)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,)
             .bind(&record.expression)
             .bind(&record.reading)
             .bind(&record.meaning)
             .bind(&record.jlpt)
-            .execute(pool)
+            .bind(record.practice_time)
+            .bind(record.familiar)
+            .bind(record.user_mark)
+            .bind(record.ease)
+            .bind(record.interval_days)
+            .bind(record.due_day)
+            .bind(record.lapses)
+            .execute(&mut *conn)
             .await?;
+        add_word_tags(&mut conn, result.last_insert_rowid(), &record.tags).await?;
     }
     Ok(())
 }
@@ -446,6 +555,7 @@ pub struct ProgressUpdate {
     increment_practice: bool,
     familiar: Option<bool>,
     user_mark: Option<bool>,
+    schedule: Option<ReviewState>,
 }
 
 impl ProgressUpdate {
@@ -472,10 +582,16 @@ impl ProgressUpdate {
         self
     }
 
+    /// Chainable method to store a new spaced repetition state (ease, interval, due day, lapses).
+    pub fn set_schedule(mut self, state: ReviewState) -> Self {
+        self.schedule = Some(state);
+        self
+    }
+
     /// Executes the update operation against the database.
     pub async fn execute(self, pool: &sqlx::SqlitePool, word_id: i64) -> Result<(), sqlx::Error> {
         // 1. Check for changes at the very top. It's more efficient.
-        if !self.increment_practice && self.familiar.is_none() && self.user_mark.is_none() {
+        if !self.increment_practice && self.familiar.is_none() && self.user_mark.is_none() && self.schedule.is_none() {
             eprintln!("Update called with no changes, doing nothing.");
             return Ok(());
         }
@@ -495,6 +611,12 @@ impl ProgressUpdate {
             // 3. THE FIX: Do the same for user_mark.
             separated.push("user_mark = ").push_bind_unseparated(val);
         }
+        if let Some(state) = self.schedule {
+            separated.push("ease = ").push_bind_unseparated(state.ease);
+            separated.push("interval_days = ").push_bind_unseparated(state.interval_days);
+            separated.push("due_day = ").push_bind_unseparated(state.due_day);
+            separated.push("lapses = ").push_bind_unseparated(state.lapses);
+        }
 
         // Add the final WHERE clause.
         builder.push(" WHERE id = ");
@@ -516,6 +638,10 @@ pub struct ProgressSelect {
     practice_time: Option<i64>,
     familiar: Option<bool>,
     user_mark: Option<bool>,
+    due_by: Option<i64>,
+    tags: Vec<String>,
+    limit: Option<usize>,
+    random: bool,
 }
 
 impl ProgressSelect {
@@ -543,9 +669,32 @@ impl ProgressSelect {
         self.user_mark = Some(value);
         self
     }
+    /// Chainable method to select practiced words due for review on or before `day`.
+    /// Use `scheduler::today()` for the "due today" queue, results are ordered by due day.
+    pub fn select_due(mut self, day: i64) -> Self {
+        self.due_by = Some(day);
+        self
+    }
+    /// Chainable method to select words having a tag, e.g. "Genki_Ln.12".
+    /// Calling it several times selects words having all the given tags.
+    pub fn select_tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+    /// Chainable method to limit the number of returned words.
+    pub fn limit(mut self, num: usize) -> Self {
+        self.limit = Some(num);
+        self
+    }
+    /// Chainable method to shuffle the returned words.
+    pub fn random(mut self, value: bool) -> Self {
+        self.random = value;
+        self
+    }
     /// Executes the selection operation against the database.
     pub async fn execute(self, pool: &sqlx::SqlitePool) -> Result<Vec<WordRecord>, sqlx::Error> {
-        let mut query = String::from("SELECT * FROM words WHERE 1=1");
+        // soft deleted words (removed from the word lists, see word_import.rs) are never selected
+        let mut query = format!("SELECT {} FROM words WHERE deleted = 0", WORD_COLUMNS);
         
         if let Some(_jlpt) = self.jlpt {
             query.push_str(" AND jlpt = ?");
@@ -559,6 +708,21 @@ impl ProgressSelect {
         if let Some(_user_mark) = self.user_mark {
             query.push_str(" AND user_mark = ?");
         }
+        if let Some(_due_by) = self.due_by {
+            query.push_str(" AND practice_time > 0 AND due_day <= ?");
+        }
+        for _tag in &self.tags {
+            query.push_str(" AND EXISTS (SELECT 1 FROM word_tags JOIN tags ON tags.id = word_tags.tag_id WHERE word_tags.word_id = words.id AND tags.name = ?)");
+        }
+
+        if self.random {
+            query.push_str(" ORDER BY RANDOM()");
+        } else if self.due_by.is_some() {
+            query.push_str(" ORDER BY due_day");
+        }
+        if let Some(num) = self.limit {
+            query.push_str(&format!(" LIMIT {}", num));
+        }
 
         let mut sql_query = sqlx::query(&query);
         
@@ -574,20 +738,17 @@ impl ProgressSelect {
         if let Some(user_mark) = self.user_mark {
             sql_query = sql_query.bind(user_mark);
         }
+        if let Some(due_by) = self.due_by {
+            sql_query = sql_query.bind(due_by);
+        }
+        for tag in &self.tags {
+            sql_query = sql_query.bind(tag);
+        }
 
         let rows = sql_query.fetch_all(pool).await?;
 
         // Map the rows to WordRecord
-        let records: Vec<WordRecord> = rows.into_iter().map(|row| WordRecord {
-            id: row.get("id"),
-            expression: row.get("expression"),
-            reading: row.get("reading"),
-            meaning: row.get("meaning"),
-            jlpt: row.get("jlpt"),
-            practice_time: row.get("practice_time"),
-            familiar: row.get("familiar"),
-            user_mark: row.get("user_mark"),
-        }).collect();
+        let records: Vec<WordRecord> = rows.iter().map(WordRecord::from_row).collect();
 
         Ok(records)
     }
@@ -598,36 +759,47 @@ impl ProgressSelect {
 
 
 
+/// Helper function to set up an in-memory database for testing, shared by the test modules.
 #[cfg(test)]
-mod tests {
-    use super::*;
-    use sqlx::sqlite::SqlitePool;
-    use sqlx::sqlite::SqlitePoolOptions;
-
-    /// Helper function to set up an in-memory database for testing.
-    async fn setup_test_db() -> SqlitePool {
-        // Use an in-memory SQLite database for fast, isolated tests.
-        let pool = SqlitePoolOptions::new()
-            .connect("sqlite::memory:")
-            .await
-            .expect("Failed to create in-memory db pool.");
-
-        // 3.1 & 3.2: Reset and create tables
-        create_table(&pool)
-            .await
-            .expect("Failed to create tables.");
+pub(crate) async fn setup_test_db() -> sqlx::SqlitePool {
+    // Use an in-memory SQLite database for fast, isolated tests.
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to create in-memory db pool.");
+
+    // 3.1 & 3.2: Reset and create tables
+    create_table(&pool)
+        .await
+        .expect("Failed to create tables.");
+
+    pool
+}
 
-        pool
+/// Builds a word for tests, other fields are left at their defaults.
+#[cfg(test)]
+pub(crate) fn test_word(expression: &str, reading: &str, meaning: &str, jlpt: &str) -> WordRecord {
+    WordRecord {
+        expression: expression.to_string(),
+        reading: reading.to_string(),
+        meaning: meaning.to_string(),
+        jlpt: jlpt.to_string(),
+        ..Default::default()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     /// Helper function to create fake word data.
     fn create_fake_data() -> Vec<WordRecord> {
         vec![
-            WordRecord { id: 0, expression: "一".to_string(), reading: "いち".to_string(), meaning: "one".to_string(), jlpt: "n5".to_string(), practice_time: 0, familiar: false, user_mark: false },
-            WordRecord { id: 0, expression: "二".to_string(), reading: "に".to_string(), meaning: "two".to_string(), jlpt: "n5".to_string() , practice_time: 0, familiar: false, user_mark: false },
-            WordRecord { id: 0, expression: "時間".to_string(), reading: "じかん".to_string(), meaning: "time".to_string(), jlpt: "n4".to_string() , practice_time: 0, familiar: false, user_mark: false },
-            WordRecord { id: 0, expression: "経済".to_string(), reading: "けいざい".to_string(), meaning: "economy".to_string(), jlpt: "n1".to_string() , practice_time: 0, familiar: false, user_mark: false },
-            WordRecord { id: 0, expression: "政治".to_string(), reading: "せいじ".to_string(), meaning: "politics".to_string(), jlpt: "n1".to_string() , practice_time: 0, familiar: false, user_mark: false },
+            WordRecord { id: 0, expression: "一".to_string(), reading: "いち".to_string(), meaning: "one".to_string(), jlpt: "n5".to_string(), practice_time: 0, familiar: false, user_mark: false, ..Default::default() },
+            WordRecord { id: 0, expression: "二".to_string(), reading: "に".to_string(), meaning: "two".to_string(), jlpt: "n5".to_string() , practice_time: 0, familiar: false, user_mark: false, ..Default::default() },
+            WordRecord { id: 0, expression: "時間".to_string(), reading: "じかん".to_string(), meaning: "time".to_string(), jlpt: "n4".to_string() , practice_time: 0, familiar: false, user_mark: false, ..Default::default() },
+            WordRecord { id: 0, expression: "経済".to_string(), reading: "けいざい".to_string(), meaning: "economy".to_string(), jlpt: "n1".to_string() , practice_time: 0, familiar: false, user_mark: false, ..Default::default() },
+            WordRecord { id: 0, expression: "政治".to_string(), reading: "せいじ".to_string(), meaning: "politics".to_string(), jlpt: "n1".to_string() , practice_time: 0, familiar: false, user_mark: false, ..Default::default() },
         ]
     }
 
@@ -748,5 +920,42 @@ mod tests {
 
 
 
+    }
+
+    #[tokio::test]
+    async fn test_word_tags() {
+        let pool = setup_test_db().await;
+        let tagged = |expression: &str, jlpt: &str, tags: &[&str]| WordRecord {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..test_word(expression, expression, "meaning", jlpt)
+        };
+        bulk_insert_words(&pool, vec![
+            tagged("青い", "n5", &["Genki_Ln.9", "JLPT_5"]),
+            tagged("作法", "n3", &["JLPT_2", "JLPT_3"]),
+            tagged("様々", "n3", &["JLPT_3"]),
+        ]).await.expect("Failed to insert tagged words.");
+
+        // GROUP_CONCAT doesn't keep any order
+        let mut tags = find_word_by_ids(&pool, vec![1]).await.unwrap().remove(0).tags;
+        tags.sort();
+        assert_eq!(tags, vec!["Genki_Ln.9".to_string(), "JLPT_5".to_string()]);
+
+        // words appearing in both N3 and N2
+        let both = ProgressSelect::new()
+            .select_tag("JLPT_3")
+            .select_tag("JLPT_2")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(both.len(), 1);
+        assert_eq!(both[0].expression, "作法");
+
+        let tags = list_tags(&pool).await.unwrap();
+        assert_eq!(tags.iter().find(|(name, _)| name == "JLPT_3").map(|(_, count)| *count), Some(2));
+
+        set_word_tags(&pool, 3, &["Genki_Ln.12".to_string()]).await.unwrap();
+        let genki = ProgressSelect::new().select_tag("Genki_Ln.12").execute(&pool).await.unwrap();
+        assert_eq!(genki.len(), 1);
+        assert_eq!(genki[0].tags, vec!["Genki_Ln.12".to_string()]);
     }
 }
@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+
+use rand::{rng, Rng};
+use sqlx::Row;
+use wana_kana::ConvertJapanese;
+
+use crate::db::{WordRecord, WORD_COLUMNS};
+use crate::recall::{levenshtein, normalize_meaning};
+
+/// Words of other levels looked at when the level of the word has too few candidates.
+const OTHER_LEVEL_CANDIDATES: i64 = 300;
+
+/// A word that may be offered as a wrong answer.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub word: WordRecord,
+    /// JMdict part-of-speech codes of the first sense, e.g. "v5r vt", empty without JMdict data.
+    pub pos: String,
+}
+
+/// A coarse word class to compare words by, e.g. "verb" for "v5r vt".
+/// Without JMdict data a meaning starting with "to " is taken as a verb.
+fn word_class(pos: &str, meaning: &str) -> Option<String> {
+    let Some(code) = pos.split_whitespace().next() else {
+        return meaning.trim_start().to_lowercase().starts_with("to ").then(|| "verb".to_string());
+    };
+    let class = if code.starts_with('v') {
+        "verb"
+    } else if code.starts_with("adj") {
+        "adjective"
+    } else if code.starts_with("adv") {
+        "adverb"
+    } else if code.starts_with('n') || code == "pn" {
+        "noun"
+    } else {
+        code
+    };
+    Some(class.to_string())
+}
+
+/// The normalized meanings of a word, see `recall::normalize_meaning`.
+fn meanings(meaning: &str) -> HashSet<String> {
+    meaning
+        .split([',', ';'])
+        .map(normalize_meaning)
+        .filter(|m| !m.is_empty())
+        .collect()
+}
+
+/// Whether two words share a meaning, e.g. "to eat" and "to eat; to live on".
+fn synonymous(a: &WordRecord, b: &WordRecord) -> bool {
+    !meanings(&a.meaning).is_disjoint(&meanings(&b.meaning))
+}
+
+/// The reading in hiragana, kana words without a reading are read as written.
+fn hiragana_reading(word: &WordRecord) -> String {
+    if word.reading.is_empty() { word.expression.to_hiragana() } else { word.reading.to_hiragana() }
+}
+
+/// How close a candidate is to the word, higher is a more tempting wrong answer:
+/// the same word class, a similar reading and shared kanji all add to it.
+fn similarity(word: &WordRecord, class: &Option<String>, candidate: &Candidate) -> f64 {
+    let mut score = 0.0;
+    if class.is_some() && *class == word_class(&candidate.pos, &candidate.word.meaning) {
+        score += 3.0;
+    }
+
+    let (reading, other) = (hiragana_reading(word), hiragana_reading(&candidate.word));
+    let length = reading.chars().count().max(other.chars().count());
+    if levenshtein(&reading, &other) <= (length / 3).max(1) {
+        score += 2.0;
+    } else if reading.chars().next() == other.chars().next() {
+        score += 1.0;
+    }
+
+    let kanji = word.kanji();
+    let shared = candidate.word.kanji().iter().filter(|k| kanji.contains(k)).count();
+    score += 2.0 * shared.min(2) as f64;
+    score
+}
+
+/// Adds the most similar candidates to `chosen` until it holds `count` words.
+/// Candidates with the expression or a meaning of the word or of a chosen word are skipped,
+/// so every choice can be told apart. Ties are broken at random.
+pub fn choose_distractors(word: &WordRecord, pos: &str, candidates: &[Candidate], chosen: &mut Vec<WordRecord>, count: usize) {
+    let class = word_class(pos, &word.meaning);
+    let mut rngen = rng();
+    let mut ranked: Vec<(f64, &Candidate)> = candidates
+        .iter()
+        .map(|candidate| (similarity(word, &class, candidate) + rngen.random::<f64>(), candidate))
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    for (_, candidate) in ranked {
+        if chosen.len() >= count {
+            break;
+        }
+        let other = &candidate.word;
+        let distinct = |taken: &WordRecord| {
+            taken.id != other.id && taken.expression != other.expression && !synonymous(taken, other)
+        };
+        if other.expression.is_empty() || other.meaning.trim().is_empty() {
+            continue;
+        }
+        if distinct(word) && chosen.iter().all(distinct) {
+            chosen.push(other.clone());
+        }
+    }
+}
+
+/// Loads the words that may stand in for `word`, of its JLPT level or of the other levels.
+async fn load_candidates(pool: &sqlx::SqlitePool, word: &WordRecord, same_level: bool) -> Result<Vec<Candidate>, sqlx::Error> {
+    let mut query = format!(
+        "SELECT {}, (SELECT pos FROM word_senses WHERE word_senses.word_id = words.id ORDER BY sense_index LIMIT 1) AS pos \
+         FROM words WHERE deleted = 0 AND id != ?",
+        WORD_COLUMNS
+    );
+    if same_level {
+        query.push_str(" AND jlpt = ?");
+    } else {
+        query.push_str(&format!(" AND jlpt != ? ORDER BY RANDOM() LIMIT {}", OTHER_LEVEL_CANDIDATES));
+    }
+    let rows = sqlx::query(&query).bind(word.id).bind(&word.jlpt).fetch_all(pool).await?;
+    Ok(rows
+        .iter()
+        .map(|row| Candidate {
+            word: WordRecord::from_row(row),
+            pos: row.get::<Option<String>, _>("pos").unwrap_or_default(),
+        })
+        .collect())
+}
+
+/// Picks up to `count` wrong answers for `word` from the whole words table, preferring its JLPT level.
+/// Other levels fill in when the level has too few, fewer are returned when the table runs out.
+pub async fn pick_distractors(pool: &sqlx::SqlitePool, word: &WordRecord, count: usize) -> Result<Vec<WordRecord>, sqlx::Error> {
+    let pos: String = sqlx::query_scalar("SELECT pos FROM word_senses WHERE word_id = ? ORDER BY sense_index LIMIT 1")
+        .bind(word.id)
+        .fetch_optional(pool)
+        .await?
+        .unwrap_or_default();
+
+    let mut chosen = Vec::new();
+    choose_distractors(word, &pos, &load_candidates(pool, word, true).await?, &mut chosen, count);
+    if chosen.len() < count {
+        choose_distractors(word, &pos, &load_candidates(pool, word, false).await?, &mut chosen, count);
+    }
+    Ok(chosen)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+
+    #[test]
+    fn test_choose_distractors() {
+        assert_eq!(word_class("v5r vt", ""), Some("verb".to_string()));
+        assert_eq!(word_class("", "To walk"), Some("verb".to_string()));
+        assert_eq!(word_class("", "book"), None);
+
+        let taberu = WordRecord { id: 1, ..test_word("食べる", "たべる", "to eat", "n5") };
+        let candidates: Vec<Candidate> = [
+            WordRecord { id: 2, ..test_word("飲む", "のむ", "to drink", "n5") },
+            WordRecord { id: 3, ..test_word("食事", "しょくじ", "meal", "n5") },
+            WordRecord { id: 4, ..test_word("食う", "くう", "to eat; to live on", "n5") },
+            WordRecord { id: 5, ..test_word("本", "ほん", "book", "n5") },
+            WordRecord { id: 6, ..test_word("食べる", "たべる", "to consume", "n5") },
+        ]
+        .into_iter()
+        .map(|word| Candidate { word, pos: String::new() })
+        .collect();
+
+        // same word class first, then shared kanji, synonyms and the same expression never
+        let mut chosen = Vec::new();
+        choose_distractors(&taberu, "", &candidates, &mut chosen, 2);
+        let mut ids: Vec<i64> = chosen.iter().map(|w| w.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![2, 3]);
+
+        let mut chosen = Vec::new();
+        choose_distractors(&taberu, "", &candidates, &mut chosen, 10);
+        assert_eq!(chosen.len(), 3);
+        assert!(chosen.iter().all(|w| w.id != 4 && w.id != 6));
+    }
+
+    #[tokio::test]
+    async fn test_pick_distractors() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            test_word("食べる", "たべる", "to eat", "n5"),
+            test_word("飲む", "のむ", "to drink", "n5"),
+            test_word("食事", "しょくじ", "meal", "n5"),
+            test_word("食う", "くう", "to eat; to live on", "n5"),
+            test_word("貪る", "むさぼる", "to devour", "n1"),
+        ]).await.unwrap();
+        let taberu = find_word_by_ids(&pool, vec![1]).await.unwrap().remove(0);
+
+        // the level has two usable words, the third comes from another level
+        let distractors = pick_distractors(&pool, &taberu, 3).await.unwrap();
+        let mut expressions: Vec<&str> = distractors.iter().map(|w| w.expression.as_str()).collect();
+        assert_eq!(&expressions[2..], ["貪る"]);
+        expressions.sort();
+        assert_eq!(expressions, vec!["貪る", "食事", "飲む"]);
+
+        // a small table gives what it has
+        assert_eq!(pick_distractors(&pool, &taberu, 10).await.unwrap().len(), 3);
+    }
+}
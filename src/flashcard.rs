@@ -7,8 +7,16 @@ use crate::Route;
 use tts::*;
 use crate::return_voice;
 use crate::utils::speak_text;
+use crate::review_log::{log_review, ReviewEntry, ReviewMode};
+use crate::scheduler::{grade_word, today, Grade};
+use crate::jmdict::word_details;
+use crate::furigana::{FuriganaSettings, Ruby};
+use crate::pitch::{pitch_accents, PitchReading};
+use crate::utils::align_furigana;
+use std::collections::HashSet;
 
 use futures_util::StreamExt;
+use std::time::Instant;
 
 
 /// This is the main flashcard generation component
@@ -21,6 +29,8 @@ pub fn GenerateCard() -> Element {
     let mut unfamiliar_only = use_signal(|| true);
     let mut random_shuffle = use_signal(|| true);
     let mut user_mark = use_signal(|| false);
+    let mut due_only = use_signal(|| false);
+    let mut selected_tags = use_signal(Vec::<String>::new);
     
     let navigator = use_navigator();
 
@@ -31,7 +41,17 @@ pub fn GenerateCard() -> Element {
     let pool_ge = db_pool.clone();
     let select_words = use_context::<Signal<Vec<WordRecord>>>();
 
-
+    // all tags in the DB for the tag picker, e.g. Genki lessons and JLPT tags
+    let pool_tags = db_pool.clone();
+    let all_tags = use_resource(move || {
+        let pool = pool_tags.clone();
+        async move {
+            list_tags(&pool).await.unwrap_or_else(|e| {
+                eprintln!("Error loading tags: {}", e);
+                Vec::new()
+            })
+        }
+    });
 
 
     rsx!(
@@ -75,6 +95,45 @@ pub fn GenerateCard() -> Element {
                         option { value: JLPTlv::N3.to_string(), "n3" }
                         option { value: JLPTlv::N4.to_string(), "n4" }
                         option { value: JLPTlv::N5.to_string(), "n5" }
+                        option { value: "any", "any level" }
+                    }
+                }
+            }
+
+            // Tag Row: words must have all the picked tags
+            div { class: "row mb-3 g-3 align-items-end",
+                div { class: "col-md-6",
+                    label { class: "form-label", r#for: "tagSelect", "Tags:" }
+                    select {
+                        class: "form-select",
+                        id: "tagSelect",
+                        value: "",
+                        oninput: move |evt| {
+                            let tag = evt.value();
+                            if !tag.is_empty() && !selected_tags.read().contains(&tag) {
+                                selected_tags.write().push(tag);
+                            }
+                        },
+                        option { value: "", "Add a tag filter..." }
+                        if let Some(tags) = all_tags() {
+                            for (tag, count) in tags {
+                                option { value: "{tag}", "{tag} ({count})" }
+                            }
+                        }
+                    }
+                }
+                div { class: "col-md-6 d-flex flex-wrap gap-2",
+                    for tag in selected_tags() {
+                        span {
+                            class: "badge bg-info text-dark",
+                            style: "cursor: pointer;",
+                            title: "Click to remove",
+                            onclick: {
+                                let tag = tag.clone();
+                                move |_| selected_tags.write().retain(|t| *t != tag)
+                            },
+                            "{tag} ✕"
+                        }
                     }
                 }
             }
@@ -128,6 +187,16 @@ pub fn GenerateCard() -> Element {
                             }
                             label { class: "form-check-label", r#for: "userMarkCheck", "User Mark" }
                         }
+                        div { class: "form-check",
+                            input {
+                                class: "form-check-input",
+                                r#type: "checkbox",
+                                id: "dueCheck",
+                                checked: due_only(),
+                                oninput: move |evt| due_only.set(evt.checked()),
+                            }
+                            label { class: "form-check-label", r#for: "dueCheck", "Due for Review (ignores Unfamiliar Only)" }
+                        }
                     }
                 }
             }
@@ -155,14 +224,32 @@ pub fn GenerateCard() -> Element {
                             let random = random_shuffle();
                             let unfamiliar_val = !unfamiliar_only();
                             let user_mark_val = user_mark();
+                            let due_val = due_only();
+                            let tags = selected_tags();
 
                             async move {
                                 eprintln!("Generate Cards Clicked! ..."); // Your logging
 
-                                let jlpt = JLPTlv::from_string(&jlpt_lv()).unwrap();
-            
+                                // "any" level has no JLPTlv, it is useful together with tags
+                                let mut select = match JLPTlv::from_string(&jlpt_lv()) {
+                                    Some(jlpt) => ProgressSelect::new().select_jlpt(jlpt),
+                                    None => ProgressSelect::new(),
+                                };
+                                for tag in &tags {
+                                    select = select.select_tag(tag);
+                                }
 
-                                match return_words_by_user_progress(&pool, jlpt, 0, unfamiliar_val, user_mark_val, num, random).await {
+                                // due cards come from the scheduler, otherwise filter by familiarity
+                                let select = if due_val {
+                                    let select = select.select_due(today());
+                                    if user_mark_val { select.select_user_mark(true) } else { select }
+                                } else {
+                                    select
+                                        .select_familiar(unfamiliar_val)
+                                        .select_user_mark(user_mark_val)
+                                };
+
+                                match select.limit(num).random(random).execute(&pool).await {
                                     Ok(records) => {
                                         if records.is_empty() {
                                             // Set the message and DO NOT navigate
@@ -203,8 +290,7 @@ pub fn GenerateCard() -> Element {
 /// Define the actions the user can perform
 #[derive(Debug, Clone, Copy)]
 enum FlashcardAction {
-    MarkFamiliar,
-    MarkUnfamiliar,
+    Grade(Grade),
     UserMark,
     DisplayAnswer,
     Pronounce,
@@ -228,6 +314,8 @@ pub fn DisplayCard(j_to_e: bool) -> Element {
     let mut show_reading = use_signal(|| true);
     let mut show_answer = use_signal(|| false);
     let mut is_marked = use_signal(|| false);
+    let mut card_shown_at = use_signal(Instant::now); // used to measure the response time
+    let mut current_word = use_signal(WordRecord::default); // used to look up the dictionary data
 
 
     // --- content for UI ---
@@ -235,9 +323,20 @@ pub fn DisplayCard(j_to_e: bool) -> Element {
     let mut reading = use_signal(|| "".to_string());
     let mut answer = use_signal(|| "".to_string());
 
+    // --- furigana over the expression, hidden on words the user knows ---
+    let furigana_settings = use_context::<Signal<FuriganaSettings>>();
+    let expression_ruby = align_furigana(&current_word.read().expression, &current_word.read().reading);
+    let known_expression: HashSet<String> = if furigana_settings.read().hides(&current_word.read().jlpt, current_word.read().familiar) {
+        HashSet::from([current_word.read().expression.clone()])
+    } else {
+        HashSet::new()
+    };
+
     // --- pool for db op ---
     let db_pool = use_context::<sqlx::SqlitePool>();
     let pool_action = db_pool.clone(); // pool for km_actions
+    let pool_details = db_pool.clone(); // pool for the dictionary lookup
+    let pool_pitch = db_pool.clone(); // pool for the pitch accent lookup
     // let pool_un = db_pool.clone(); // pool for unfamiliar op
     // let pool_fa = db_pool.clone(); // pool for familiar op
     // let pool_um = db_pool.clone(); // pool for user mark op
@@ -263,6 +362,8 @@ pub fn DisplayCard(j_to_e: bool) -> Element {
             eprintln!("Loading card at index {}: {:?}", card_index, word);
             reading.set(word.reading.clone());
             is_marked.set(word.user_mark);
+            card_shown_at.set(Instant::now());
+            current_word.set(word.clone());
             if j_to_e {
                 question.set(word.expression.clone());
                 answer.set(word.meaning.clone());
@@ -275,6 +376,24 @@ pub fn DisplayCard(j_to_e: bool) -> Element {
         }
     };
 
+    // JMdict senses of the current card, None when the dictionary was not imported or has no entry
+    let details = use_resource(move || {
+        let pool = pool_details.clone();
+        async move {
+            let word_id = current_word.read().id;
+            word_details(&pool, word_id).await.ok().flatten()
+        }
+    });
+
+    // pitch accents of the current card, empty when none were imported
+    let accents = use_resource(move || {
+        let pool = pool_pitch.clone();
+        async move {
+            let word = current_word();
+            pitch_accents(&pool, &word.expression, &word.reading).await.unwrap_or_default()
+        }
+    });
+
     // 2. --- Effect for Initial Load ---
     // `use_effect` runs after the component renders.
     // By calling our logic here, we load the very first card.
@@ -321,29 +440,16 @@ pub fn DisplayCard(j_to_e: bool) -> Element {
                 let pool = pool.clone();
 
                 match action {
-                    FlashcardAction::MarkUnfamiliar => {
-                        eprintln!("Marking word {} as 'Needs Practice'", word_id);
-                        match ProgressUpdate::new()
-                            .increment_practice_time()
-                            .set_familiar(false)
-                            .execute(&pool, word_id)
-                            .await
-                        {
-                            Ok(_) => eprintln!("id: {:?} updated unfamiliar successfully", word_id),
+                    FlashcardAction::Grade(grade) => {
+                        eprintln!("Grading word {} as {:?}", word_id, grade);
+                        match grade_word(&pool, word_id, grade).await {
+                            Ok(state) => eprintln!("id: {:?} graded, next review in {} days", word_id, state.interval_days),
                             Err(e) => eprintln!("Background update failed: {}", e),
                         }
-                         go_to_next_card();
-                    }
-                    FlashcardAction::MarkFamiliar => {
-                        eprintln!("Marking word {} as 'Got It!'", word_id);
-                        match ProgressUpdate::new()
-                            .increment_practice_time()
-                            .set_familiar(true)
-                            .execute(&pool, word_id)
-                            .await
-                        {
-                            Ok(_) => eprintln!("id: {:?} updated familiar successfully", word_id),
-                            Err(e) => eprintln!("Background update failed: {}", e),
+                        let response_ms = card_shown_at().elapsed().as_millis() as i64;
+                        let entry = ReviewEntry::new(word_id, ReviewMode::Flashcard, j_to_e, grade, response_ms);
+                        if let Err(e) = log_review(&pool, &entry).await {
+                            eprintln!("Failed to log review: {}", e);
                         }
                          go_to_next_card();
                     }
@@ -401,13 +507,21 @@ pub fn DisplayCard(j_to_e: bool) -> Element {
                 match event.key() {
                     // Check for 'n' or 'N'
                     Key::Character(s) if s.eq_ignore_ascii_case("n") => {
-                        eprintln!("n key pressed, marking as unfamiliar");
-                        km_actions.send(FlashcardAction::MarkUnfamiliar);
+                        eprintln!("n key pressed, grading as again");
+                        km_actions.send(FlashcardAction::Grade(Grade::Again));
+                    },
+                    Key::Character(s) if s.eq_ignore_ascii_case("h") => {
+                        eprintln!("h key pressed, grading as hard");
+                        km_actions.send(FlashcardAction::Grade(Grade::Hard));
                     },
                     // Add a key for the second button, e.g., 'G' for "Got it!"
                     Key::Character(s) if s.eq_ignore_ascii_case("g") => {
-                        eprintln!("g key pressed, marking as familiar");
-                        km_actions.send(FlashcardAction::MarkFamiliar);
+                        eprintln!("g key pressed, grading as good");
+                        km_actions.send(FlashcardAction::Grade(Grade::Good));
+                    },
+                    Key::Character(s) if s.eq_ignore_ascii_case("e") => {
+                        eprintln!("e key pressed, grading as easy");
+                        km_actions.send(FlashcardAction::Grade(Grade::Easy));
                     },
                     Key::Character(s) if s.eq_ignore_ascii_case("m") => {
                         eprintln!("m key pressed, toggling user mark");
@@ -456,7 +570,14 @@ pub fn DisplayCard(j_to_e: bool) -> Element {
                 div { class: "col d-flex justify-content-between align-items-center",
             
                     p { class: "lead my-3",
-                        if show_question() { "{question()}" } else { "" }
+                        // the furigana would give the reading away while it is hidden
+                        if !show_question() {
+                            ""
+                        } else if j_to_e && show_reading() {
+                            Ruby { segments: expression_ruby.clone(), hidden: known_expression.clone() }
+                        } else {
+                            "{question()}"
+                        }
                     }
 
                     button { 
@@ -470,7 +591,9 @@ pub fn DisplayCard(j_to_e: bool) -> Element {
 
                 if show_reading() {
                     div { class: "col d-flex justify-content-between align-items-center",
-                        p { class: "lead my-3", "{reading()}" }
+                        p { class: "lead my-3",
+                            PitchReading { reading: reading(), accents: accents().unwrap_or_default() }
+                        }
                         button { class: "btn btn-light",
                             onclick: move |_| km_actions.send(FlashcardAction::Pronounce),
                             "🔊"}
@@ -481,7 +604,33 @@ pub fn DisplayCard(j_to_e: bool) -> Element {
                 div { class: "col",
                     onclick: move |_| km_actions.send(FlashcardAction::DisplayAnswer),
                     if show_answer() {
-                        h3 { class: "display-5 text-success", "{answer()}" }
+                        h3 { class: "display-5 text-success",
+                            if j_to_e {
+                                "{answer()}"
+                            } else {
+                                Ruby { segments: expression_ruby.clone(), hidden: known_expression.clone() }
+                            }
+                        }
+                        if let Some(Some(detail)) = details() {
+                            div { class: "mt-2",
+                                if detail.common {
+                                    span { class: "badge bg-success me-2", "common" }
+                                }
+                                for form in detail.other_forms(&current_word.read().expression, &current_word.read().reading) {
+                                    span { class: "badge bg-dark border me-1", "{form}" }
+                                }
+                                ol { class: "mt-2 mb-0",
+                                    for sense in detail.senses.iter() {
+                                        li {
+                                            for (code, description) in sense.pos.iter().zip(sense.pos_descriptions.iter()) {
+                                                span { class: "badge bg-secondary me-1", title: "{description}", "{code}" }
+                                            }
+                                            "{sense.gloss_text()}"
+                                        }
+                                    }
+                                }
+                            }
+                        }
                     } else {
                         div { class: "alert alert-info", "Click to ", u {"s"} , "how answer" }
                     }
@@ -513,15 +662,25 @@ pub fn DisplayCard(j_to_e: bool) -> Element {
             div { class: "row my-3",
                 
                 div { class: "col",
-                    button { class: "btn btn-warning w-100", 
-                    onclick: move |_| km_actions.send(FlashcardAction::MarkUnfamiliar),
+                    button { class: "btn btn-danger w-100", 
+                    onclick: move |_| km_actions.send(FlashcardAction::Grade(Grade::Again)),
                     u {"N"}, "eed more practice" }
                 }
+                div { class: "col",
+                    button { class: "btn btn-warning w-100", 
+                    onclick: move |_| km_actions.send(FlashcardAction::Grade(Grade::Hard)),
+                    u {"H"}, "ard" }
+                }
                 div { class: "col",
                     button { class: "btn btn-success w-100", 
-                    onclick: move |_| km_actions.send(FlashcardAction::MarkFamiliar),                 
+                    onclick: move |_| km_actions.send(FlashcardAction::Grade(Grade::Good)),                 
                     u {"G"}, "ot it!" }
                 }
+                div { class: "col",
+                    button { class: "btn btn-info w-100", 
+                    onclick: move |_| km_actions.send(FlashcardAction::Grade(Grade::Easy)),                 
+                    u {"E"}, "asy" }
+                }
             }
         }
     }
@@ -0,0 +1,176 @@
+use std::collections::HashSet;
+
+use dioxus::prelude::*;
+use sqlx::Row;
+
+use crate::settings::{get_setting, set_setting};
+use crate::stories::is_above_level;
+use crate::utils::{furigana, RubySegment};
+
+/// When to show furigana, stored under the `furigana.*` keys of the settings table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuriganaSettings {
+    pub enabled: bool,
+    /// Furigana is hidden on words of this JLPT level and the easier ones, empty to show it on every level.
+    pub known_level: String,
+    /// Furigana is hidden on the words marked familiar in the reviews.
+    pub hide_familiar: bool,
+}
+
+impl Default for FuriganaSettings {
+    fn default() -> Self {
+        FuriganaSettings {
+            enabled: true,
+            known_level: String::new(),
+            hide_familiar: false,
+        }
+    }
+}
+
+impl FuriganaSettings {
+    /// Loads the saved settings, anything that was never saved keeps its default.
+    pub async fn load(pool: &sqlx::SqlitePool) -> Result<Self, sqlx::Error> {
+        let mut settings = FuriganaSettings::default();
+        if let Some(enabled) = get_setting(pool, "furigana.enabled").await? {
+            settings.enabled = enabled == "true";
+        }
+        if let Some(level) = get_setting(pool, "furigana.known_level").await? {
+            settings.known_level = level;
+        }
+        if let Some(hide_familiar) = get_setting(pool, "furigana.hide_familiar").await? {
+            settings.hide_familiar = hide_familiar == "true";
+        }
+        Ok(settings)
+    }
+
+    pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
+        set_setting(pool, "furigana.enabled", &self.enabled.to_string()).await?;
+        set_setting(pool, "furigana.known_level", &self.known_level).await?;
+        set_setting(pool, "furigana.hide_familiar", &self.hide_familiar.to_string()).await?;
+        Ok(())
+    }
+
+    /// True when a word of level `jlpt` is known well enough to be shown without furigana.
+    /// Words without a level are only hidden when they are familiar.
+    pub fn hides(&self, jlpt: &str, familiar: bool) -> bool {
+        if self.hide_familiar && familiar {
+            return true;
+        }
+        !self.known_level.is_empty() && !jlpt.is_empty() && !is_above_level(jlpt, &self.known_level)
+    }
+}
+
+/// The words of `base_forms` whose furigana `settings` hides, looked up in the words table.
+pub async fn known_words(
+    pool: &sqlx::SqlitePool,
+    base_forms: &[String],
+    settings: &FuriganaSettings,
+) -> Result<HashSet<String>, sqlx::Error> {
+    if base_forms.is_empty() || (settings.known_level.is_empty() && !settings.hide_familiar) {
+        return Ok(HashSet::new());
+    }
+    let placeholders = vec!["?"; base_forms.len()].join(", ");
+    let sql = format!(
+        "SELECT expression, MIN(jlpt) AS jlpt, MAX(familiar) AS familiar FROM words \
+         WHERE deleted = 0 AND expression IN ({}) GROUP BY expression",
+        placeholders
+    );
+    let mut query = sqlx::query(&sql);
+    for base_form in base_forms {
+        query = query.bind(base_form);
+    }
+    let rows = query.fetch_all(pool).await?;
+    Ok(rows
+        .iter()
+        .filter(|row| settings.hides(&row.get::<String, _>("jlpt"), row.get::<bool, _>("familiar")))
+        .map(|row| row.get("expression"))
+        .collect())
+}
+
+/// Renders ruby segments, `hidden` lists the base forms shown without furigana.
+/// Nothing gets furigana while it is turned off on the setting page.
+#[component]
+pub fn Ruby(segments: Vec<RubySegment>, #[props(default)] hidden: HashSet<String>) -> Element {
+    let settings = use_context::<Signal<FuriganaSettings>>();
+    let enabled = settings.read().enabled;
+
+    rsx! {
+        for segment in segments {
+            match segment.reading.as_ref() {
+                Some(reading) if enabled && !hidden.contains(&segment.base_form) => rsx! {
+                    ruby { "{segment.text}" rt { "{reading}" } }
+                },
+                _ => rsx! { "{segment.text}" },
+            }
+        }
+    }
+}
+
+/// A sentence with furigana over its kanji, following the furigana settings.
+#[component]
+pub fn RubyText(text: String) -> Element {
+    let db_pool = use_context::<sqlx::SqlitePool>();
+    let settings = use_context::<Signal<FuriganaSettings>>();
+
+    let ruby = use_resource(use_reactive!(|text| {
+        let pool = db_pool.clone();
+        let settings = settings();
+        async move {
+            let segments = furigana(&text).unwrap_or_else(|e| {
+                eprintln!("Error adding furigana to {}: {}", text, e);
+                vec![RubySegment { text: text.clone(), reading: None, base_form: text.clone() }]
+            });
+            let base_forms: Vec<String> = segments.iter().map(|s| s.base_form.clone()).collect();
+            let hidden = known_words(&pool, &base_forms, &settings).await.unwrap_or_else(|e| {
+                eprintln!("Error looking up known words: {}", e);
+                HashSet::new()
+            });
+            (segments, hidden)
+        }
+    }));
+
+    match &*ruby.read_unchecked() {
+        Some((segments, hidden)) => rsx! {
+            Ruby { segments: segments.clone(), hidden: hidden.clone() }
+        },
+        None => rsx! { "{text}" },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+
+    #[test]
+    fn test_hides() {
+        let settings = FuriganaSettings { known_level: "n4".to_string(), ..Default::default() };
+        assert!(settings.hides("n5", false));
+        assert!(settings.hides("n4", false));
+        assert!(!settings.hides("n3", false));
+        assert!(!settings.hides("", true));
+        assert!(!FuriganaSettings::default().hides("n5", true));
+
+        let settings = FuriganaSettings { hide_familiar: true, ..Default::default() };
+        assert!(settings.hides("n1", true));
+        assert!(!settings.hides("n5", false));
+    }
+
+    #[tokio::test]
+    async fn test_known_words_and_settings() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![test_word("食べる", "", "", "n5"), test_word("図書館", "", "", "n4"), test_word("概念", "", "", "n1")]).await.unwrap();
+
+        let words = vec!["食べる".to_string(), "図書館".to_string(), "概念".to_string(), "猫".to_string()];
+        assert!(known_words(&pool, &words, &FuriganaSettings::default()).await.unwrap().is_empty());
+
+        let settings = FuriganaSettings { known_level: "n5".to_string(), ..Default::default() };
+        assert_eq!(known_words(&pool, &words, &settings).await.unwrap(), HashSet::from(["食べる".to_string()]));
+
+        assert_eq!(FuriganaSettings::load(&pool).await.unwrap(), FuriganaSettings::default());
+        let settings = FuriganaSettings { enabled: false, known_level: "n3".to_string(), hide_familiar: true };
+        settings.save(&pool).await.unwrap();
+        assert_eq!(FuriganaSettings::load(&pool).await.unwrap(), settings);
+    }
+}
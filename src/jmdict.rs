@@ -0,0 +1,534 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::io::BufRead;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use sqlx::Row;
+
+/// JMdict priority codes that make a form "common", the same rule jisho.org and most dictionaries use.
+const COMMON_PRIORITIES: [&str; 5] = ["news1", "ichi1", "spec1", "spec2", "gai1"];
+
+/// A kanji (`keb`) or kana (`reb`) form of a JMdict entry.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JmdictForm {
+    pub text: String,
+    pub common: bool,
+    /// Kana forms only: the kanji forms this reading belongs to, empty means all of them.
+    pub restrictions: Vec<String>,
+    /// Kana forms only: the reading is not a true reading of the kanji forms.
+    pub no_kanji: bool,
+}
+
+/// One sense (meaning) of a JMdict entry.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JmdictSense {
+    /// Part-of-speech codes, e.g. "v5r", "vi". JMdict only lists them when they change,
+    /// the parser copies them from the previous sense.
+    pub pos: Vec<String>,
+    /// Misc codes, e.g. "uk" (usually written in kana), "col" (colloquial).
+    pub misc: Vec<String>,
+    /// English glosses.
+    pub glosses: Vec<String>,
+}
+
+/// One `<entry>` of JMdict.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JmdictEntry {
+    pub seq: i64,
+    pub kanji: Vec<JmdictForm>,
+    pub readings: Vec<JmdictForm>,
+    pub senses: Vec<JmdictSense>,
+}
+
+impl JmdictEntry {
+    /// An entry is common when any of its forms is.
+    pub fn is_common(&self) -> bool {
+        self.kanji.iter().chain(self.readings.iter()).any(|form| form.common)
+    }
+
+    /// All (expression, reading) pairs this entry can be written as, the way our `words` rows are keyed.
+    /// Kana forms are also paired with themselves, for words written in kana only.
+    pub fn keys(&self) -> Vec<(String, String)> {
+        let mut keys = Vec::new();
+        for reading in &self.readings {
+            if !reading.no_kanji {
+                for kanji in &self.kanji {
+                    if reading.restrictions.is_empty() || reading.restrictions.contains(&kanji.text) {
+                        keys.push((kanji.text.clone(), reading.text.clone()));
+                    }
+                }
+            }
+            keys.push((reading.text.clone(), reading.text.clone()));
+        }
+        keys
+    }
+
+    /// The headword, first kanji form or first reading.
+    fn headword(&self) -> Option<&str> {
+        self.kanji.first().or(self.readings.first()).map(|form| form.text.as_str())
+    }
+}
+
+/// Replaces the predefined XML entities. JMdict uses its own DTD entities for codes (`&v5r;`),
+/// those are replaced by their name, which is the code.
+fn unescape_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find(';') else {
+            result.push_str(&rest[start..]);
+            return result;
+        };
+        let name = &rest[start + 1..start + end];
+        match name {
+            "amp" => result.push('&'),
+            "lt" => result.push('<'),
+            "gt" => result.push('>'),
+            "quot" => result.push('"'),
+            "apos" => result.push('\''),
+            _ => result.push_str(name),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Reads the `<!ENTITY code "description">` declarations of the JMdict DOCTYPE.
+fn parse_entity_declarations(doctype: &str) -> Vec<(String, String)> {
+    let mut codes = Vec::new();
+    for declaration in doctype.split("<!ENTITY").skip(1) {
+        let declaration = declaration.trim_start();
+        let Some((name, rest)) = declaration.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let Some(rest) = rest.trim_start().strip_prefix('"') else {
+            continue;
+        };
+        if let Some((description, _)) = rest.split_once('"') {
+            codes.push((name.to_string(), description.to_string()));
+        }
+    }
+    codes
+}
+
+/// Streams a JMdict XML file, calling `on_entry` for every entry.
+/// Only English glosses are kept. Returns the code descriptions declared in the DOCTYPE,
+/// e.g. ("v5r", "Godan verb with 'ru' ending").
+pub fn parse_jmdict<R: BufRead>(
+    source: R,
+    mut on_entry: impl FnMut(JmdictEntry),
+) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut reader = Reader::from_reader(source);
+    reader.config_mut().trim_text(true);
+
+    let mut codes = Vec::new();
+    let mut buf = Vec::new();
+    let mut text = String::new();
+    let mut entry = JmdictEntry::default();
+    let mut form = JmdictForm::default();
+    let mut sense = JmdictSense::default();
+    let mut english_gloss = true;
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::DocType(e) => {
+                codes = parse_entity_declarations(std::str::from_utf8(&e)?);
+            }
+            Event::Start(e) => {
+                text.clear();
+                match e.local_name().as_ref() {
+                    b"entry" => entry = JmdictEntry::default(),
+                    b"k_ele" | b"r_ele" => form = JmdictForm::default(),
+                    b"sense" => sense = JmdictSense::default(),
+                    b"gloss" => {
+                        // glosses without xml:lang are English
+                        english_gloss = match e.try_get_attribute("xml:lang")? {
+                            Some(lang) => lang.value.as_ref() == b"eng",
+                            None => true,
+                        };
+                    }
+                    _ => {}
+                }
+            }
+            Event::Empty(e) if e.local_name().as_ref() == b"re_nokanji" => form.no_kanji = true,
+            Event::Text(e) => {
+                text.push_str(&unescape_entities(std::str::from_utf8(&e)?));
+            }
+            Event::End(e) => {
+                let value = std::mem::take(&mut text);
+                match e.local_name().as_ref() {
+                    b"ent_seq" => entry.seq = value.parse().unwrap_or(0),
+                    b"keb" | b"reb" => form.text = value,
+                    b"ke_pri" | b"re_pri" if COMMON_PRIORITIES.contains(&value.as_str()) => form.common = true,
+                    b"re_restr" => form.restrictions.push(value),
+                    b"k_ele" => entry.kanji.push(std::mem::take(&mut form)),
+                    b"r_ele" => entry.readings.push(std::mem::take(&mut form)),
+                    b"pos" => sense.pos.push(value),
+                    b"misc" => sense.misc.push(value),
+                    b"gloss" if english_gloss => sense.glosses.push(value),
+                    b"sense" => {
+                        if sense.pos.is_empty() {
+                            if let Some(previous) = entry.senses.last() {
+                                sense.pos = previous.pos.clone();
+                            }
+                        }
+                        // senses with only non English glosses are dropped
+                        if !sense.glosses.is_empty() {
+                            entry.senses.push(std::mem::take(&mut sense));
+                        }
+                    }
+                    b"entry" => on_entry(std::mem::take(&mut entry)),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(codes)
+}
+
+/// What a JMdict import matched.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JmdictImportReport {
+    /// Number of entries in the dictionary file.
+    pub entries: usize,
+    /// Number of words that got dictionary data.
+    pub matched: usize,
+    /// Words no entry matched, as "expression [reading]".
+    pub unmatched: Vec<String>,
+}
+
+impl fmt::Display for JmdictImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for word in &self.unmatched {
+            writeln!(f, "? {}", word)?;
+        }
+        write!(
+            f,
+            "{} dictionary entries, {} words matched, {} words not found",
+            self.entries,
+            self.matched,
+            self.unmatched.len()
+        )
+    }
+}
+
+/// Enriches the `words` rows with JMdict data: senses with their part-of-speech codes,
+/// alternate kanji/kana forms and the "common" flag, stored in side tables keyed by word id.
+/// A word matches an entry when its (expression, reading) is one of the entry's forms,
+/// when several entries match the one whose headword is the expression wins, then the common one.
+/// Importing again replaces the dictionary data, the words themselves are never changed.
+pub async fn import_jmdict<R: BufRead>(pool: &sqlx::SqlitePool, source: R) -> Result<JmdictImportReport, Box<dyn Error>> {
+    let rows = sqlx::query("SELECT id, expression, reading FROM words WHERE deleted = 0 ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+    let mut words: HashMap<(String, String), Vec<i64>> = HashMap::new();
+    let mut labels: Vec<(i64, String)> = Vec::new();
+    for row in &rows {
+        let (id, expression, reading): (i64, String, String) = (row.get("id"), row.get("expression"), row.get("reading"));
+        labels.push((id, format!("{} [{}]", expression, reading)));
+        words.entry((expression, reading)).or_default().push(id);
+    }
+
+    // best entry per word id, with its score
+    let mut matches: HashMap<i64, (u8, JmdictEntry)> = HashMap::new();
+    let mut entries = 0;
+    let codes = parse_jmdict(source, |entry| {
+        entries += 1;
+        let mut matched_ids: HashSet<i64> = HashSet::new();
+        for (expression, reading) in entry.keys() {
+            let Some(ids) = words.get(&(expression.clone(), reading)) else {
+                continue;
+            };
+            let score = 2 * u8::from(entry.headword() == Some(expression.as_str())) + u8::from(entry.is_common());
+            for id in ids {
+                if !matched_ids.insert(*id) {
+                    continue;
+                }
+                if !matches!(matches.get(id), Some((best, _)) if *best >= score) {
+                    matches.insert(*id, (score, entry.clone()));
+                }
+            }
+        }
+    })?;
+
+    let mut transaction = pool.begin().await?;
+    for (code, description) in &codes {
+        sqlx::query("INSERT OR REPLACE INTO dictionary_codes (code, description) VALUES (?, ?)")
+            .bind(code)
+            .bind(description)
+            .execute(&mut *transaction)
+            .await?;
+    }
+
+    for (word_id, (_, entry)) in &matches {
+        for table in ["word_dictionary", "word_senses", "word_forms"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE word_id = ?", table))
+                .bind(word_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        sqlx::query("INSERT INTO word_dictionary (word_id, jmdict_seq, common) VALUES (?, ?, ?)")
+            .bind(word_id)
+            .bind(entry.seq)
+            .bind(entry.is_common())
+            .execute(&mut *transaction)
+            .await?;
+
+        for (index, sense) in entry.senses.iter().enumerate() {
+            sqlx::query("INSERT INTO word_senses (word_id, sense_index, pos, misc, glosses) VALUES (?, ?, ?, ?, ?)")
+                .bind(word_id)
+                .bind(index as i64)
+                .bind(sense.pos.join(" "))
+                .bind(sense.misc.join(" "))
+                .bind(serde_json::to_string(&sense.glosses)?)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        let forms = entry.kanji.iter().map(|form| (form, false)).chain(entry.readings.iter().map(|form| (form, true)));
+        for (form, kana) in forms {
+            sqlx::query("INSERT OR IGNORE INTO word_forms (word_id, form, kana, common) VALUES (?, ?, ?, ?)")
+                .bind(word_id)
+                .bind(&form.text)
+                .bind(kana)
+                .bind(form.common)
+                .execute(&mut *transaction)
+                .await?;
+        }
+    }
+    transaction.commit().await?;
+
+    Ok(JmdictImportReport {
+        entries,
+        matched: matches.len(),
+        unmatched: labels
+            .into_iter()
+            .filter(|(id, _)| !matches.contains_key(id))
+            .map(|(_, label)| label)
+            .collect(),
+    })
+}
+
+/// A sense as shown in the app, with the code descriptions looked up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SenseDetail {
+    pub pos: Vec<String>,
+    /// Descriptions of the pos codes, in the same order, the code itself when unknown.
+    pub pos_descriptions: Vec<String>,
+    pub misc: Vec<String>,
+    pub glosses: Vec<String>,
+}
+
+impl SenseDetail {
+    /// The glosses as one line, e.g. "blue; azure".
+    pub fn gloss_text(&self) -> String {
+        self.glosses.join("; ")
+    }
+}
+
+/// The dictionary data of one word.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WordDetails {
+    pub jmdict_seq: i64,
+    pub common: bool,
+    pub senses: Vec<SenseDetail>,
+    /// Other ways to write the word, as (form, common).
+    pub kanji_forms: Vec<(String, bool)>,
+    pub kana_forms: Vec<(String, bool)>,
+}
+
+impl WordDetails {
+    /// Part-of-speech codes of the first sense, e.g. "v5r vt".
+    pub fn main_pos(&self) -> String {
+        self.senses.first().map(|sense| sense.pos.join(" ")).unwrap_or_default()
+    }
+
+    /// All senses numbered on separate lines, used for tooltips.
+    pub fn senses_text(&self) -> String {
+        self.senses
+            .iter()
+            .enumerate()
+            .map(|(i, sense)| format!("{}. {}", i + 1, sense.gloss_text()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The other ways to write the word, kanji forms first, without `expression` and `reading` themselves.
+    pub fn other_forms(&self, expression: &str, reading: &str) -> Vec<String> {
+        self.kanji_forms
+            .iter()
+            .chain(self.kana_forms.iter())
+            .map(|(form, _)| form.clone())
+            .filter(|form| form != expression && form != reading)
+            .collect()
+    }
+}
+
+/// Returns the dictionary data of the given words, words without any are left out.
+pub async fn word_details_map(pool: &sqlx::SqlitePool, word_ids: &[i64]) -> Result<HashMap<i64, WordDetails>, sqlx::Error> {
+    let mut details: HashMap<i64, WordDetails> = HashMap::new();
+    if word_ids.is_empty() {
+        return Ok(details);
+    }
+
+    let mut builder = sqlx::QueryBuilder::new("SELECT word_id, jmdict_seq, common FROM word_dictionary WHERE word_id IN (");
+    let mut separated = builder.separated(", ");
+    for id in word_ids {
+        separated.push_bind(*id);
+    }
+    builder.push(")");
+    for row in builder.build().fetch_all(pool).await? {
+        details.insert(row.get("word_id"), WordDetails {
+            jmdict_seq: row.get("jmdict_seq"),
+            common: row.get("common"),
+            ..Default::default()
+        });
+    }
+
+    let codes: HashMap<String, String> = sqlx::query("SELECT code, description FROM dictionary_codes")
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| (row.get("code"), row.get("description")))
+        .collect();
+
+    let mut builder = sqlx::QueryBuilder::new("SELECT word_id, pos, misc, glosses FROM word_senses WHERE word_id IN (");
+    let mut separated = builder.separated(", ");
+    for id in word_ids {
+        separated.push_bind(*id);
+    }
+    builder.push(") ORDER BY word_id, sense_index");
+    for row in builder.build().fetch_all(pool).await? {
+        let Some(word) = details.get_mut(&row.get::<i64, _>("word_id")) else {
+            continue;
+        };
+        let pos: Vec<String> = row.get::<String, _>("pos").split_whitespace().map(|p| p.to_string()).collect();
+        word.senses.push(SenseDetail {
+            pos_descriptions: pos.iter().map(|p| codes.get(p).cloned().unwrap_or_else(|| p.clone())).collect(),
+            pos,
+            misc: row.get::<String, _>("misc").split_whitespace().map(|m| m.to_string()).collect(),
+            glosses: serde_json::from_str(&row.get::<String, _>("glosses")).unwrap_or_default(),
+        });
+    }
+
+    let mut builder = sqlx::QueryBuilder::new("SELECT word_id, form, kana, common FROM word_forms WHERE word_id IN (");
+    let mut separated = builder.separated(", ");
+    for id in word_ids {
+        separated.push_bind(*id);
+    }
+    builder.push(") ORDER BY word_id, rowid");
+    for row in builder.build().fetch_all(pool).await? {
+        let Some(word) = details.get_mut(&row.get::<i64, _>("word_id")) else {
+            continue;
+        };
+        let form = (row.get::<String, _>("form"), row.get::<bool, _>("common"));
+        if row.get::<bool, _>("kana") {
+            word.kana_forms.push(form);
+        } else {
+            word.kanji_forms.push(form);
+        }
+    }
+
+    Ok(details)
+}
+
+/// Returns the dictionary data of one word, None if JMdict had no entry for it.
+pub async fn word_details(pool: &sqlx::SqlitePool, word_id: i64) -> Result<Option<WordDetails>, sqlx::Error> {
+    Ok(word_details_map(pool, &[word_id]).await?.remove(&word_id))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE JMdict [
+<!ENTITY n "noun (common) (futsuumeishi)">
+<!ENTITY v5r "Godan verb with 'ru' ending">
+<!ENTITY vi "intransitive verb">
+<!ENTITY uk "word usually written using kana alone">
+]>
+<JMdict>
+<entry>
+<ent_seq>1381380</ent_seq>
+<k_ele><keb>青</keb><ke_pri>ichi1</ke_pri></k_ele>
+<r_ele><reb>あお</reb><re_pri>ichi1</re_pri></r_ele>
+<sense><pos>&n;</pos><gloss>blue</gloss><gloss>azure</gloss><gloss xml:lang="ger">Blau</gloss></sense>
+<sense><gloss>green</gloss></sense>
+</entry>
+<entry>
+<ent_seq>1296400</ent_seq>
+<k_ele><keb>有る</keb></k_ele>
+<k_ele><keb>在る</keb><ke_pri>news1</ke_pri></k_ele>
+<r_ele><reb>ある</reb></r_ele>
+<sense><pos>&v5r;</pos><pos>&vi;</pos><misc>&uk;</misc><gloss>to be &amp; exist</gloss></sense>
+</entry>
+<entry>
+<ent_seq>9999999</ent_seq>
+<k_ele><keb>赤</keb></k_ele>
+<r_ele><reb>あか</reb></r_ele>
+<r_ele><reb>せき</reb><re_restr>石</re_restr></r_ele>
+<sense><pos>&n;</pos><gloss>red</gloss></sense>
+</entry>
+</JMdict>
+"#;
+
+    #[test]
+    fn test_parse_jmdict() {
+        let mut entries = Vec::new();
+        let codes = parse_jmdict(SAMPLE.as_bytes(), |entry| entries.push(entry)).expect("Failed to parse.");
+        assert_eq!(codes.len(), 4);
+        assert_eq!(codes[1], ("v5r".to_string(), "Godan verb with 'ru' ending".to_string()));
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].seq, 1381380);
+        assert!(entries[0].is_common());
+        assert_eq!(entries[0].senses[0].glosses, vec!["blue", "azure"]);
+        // pos is carried over to the next sense
+        assert_eq!(entries[0].senses[1].pos, vec!["n"]);
+        assert_eq!(entries[1].senses[0].pos, vec!["v5r", "vi"]);
+        assert_eq!(entries[1].senses[0].glosses, vec!["to be & exist"]);
+        // せき only belongs to 石, so it is not a reading of 赤
+        assert!(!entries[2].keys().contains(&("赤".to_string(), "せき".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_import_jmdict() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            test_word("青", "あお", "blue", "n5"),
+            test_word("ある", "ある", "to be", "n5"),
+            test_word("黒", "くろ", "black", "n5"),
+        ]).await.expect("Failed to insert words.");
+
+        let report = import_jmdict(&pool, SAMPLE.as_bytes()).await.expect("Failed to import.");
+        assert_eq!(report.entries, 3);
+        assert_eq!(report.matched, 2);
+        assert_eq!(report.unmatched, vec!["黒 [くろ]".to_string()]);
+
+        let blue = word_details(&pool, 1).await.unwrap().expect("No details for 青.");
+        assert!(blue.common);
+        assert_eq!(blue.senses.len(), 2);
+        assert_eq!(blue.senses[0].pos_descriptions, vec!["noun (common) (futsuumeishi)"]);
+        assert_eq!(blue.main_pos(), "n");
+
+        let aru = word_details(&pool, 2).await.unwrap().expect("No details for ある.");
+        assert_eq!(aru.kanji_forms, vec![("有る".to_string(), false), ("在る".to_string(), true)]);
+        assert_eq!(aru.senses[0].misc, vec!["uk"]);
+
+        // importing again replaces the data instead of adding to it
+        import_jmdict(&pool, SAMPLE.as_bytes()).await.expect("Failed to import.");
+        assert_eq!(word_details(&pool, 1).await.unwrap().unwrap().senses.len(), 2);
+        assert!(word_details(&pool, 3).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::BufRead;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+use crate::db::{WordRecord, WORD_COLUMNS};
+
+/// One kanji as stored in the `kanji` table, imported from KANJIDIC2.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KanjiRecord {
+    pub literal: String,
+    /// On'yomi in katakana, e.g. "ショク".
+    pub on_readings: Vec<String>,
+    /// Kun'yomi in hiragana, okurigana after a dot, e.g. "た.べる".
+    pub kun_readings: Vec<String>,
+    pub meanings: Vec<String>,
+    pub stroke_count: i64,
+    /// School grade, 1-6 for kyouiku kanji, 8 for the rest of jouyou, 9-10 for jinmeiyou.
+    pub grade: Option<i64>,
+    /// Pre-2010 JLPT level from KANJIDIC2, 4 is the easiest, 1 the hardest.
+    pub jlpt: Option<i64>,
+    /// Rank among the 2500 most used kanji in newspapers, 1 is the most used.
+    pub frequency: Option<i64>,
+
+    // User Progress Fields
+    pub practice_time: i64,
+    pub familiar: bool,
+}
+
+impl KanjiRecord {
+    fn from_row(row: &SqliteRow) -> Self {
+        let split = |column: &str| -> Vec<String> {
+            row.get::<String, _>(column).split_whitespace().map(|r| r.to_string()).collect()
+        };
+        KanjiRecord {
+            literal: row.get("literal"),
+            on_readings: split("on_readings"),
+            kun_readings: split("kun_readings"),
+            meanings: serde_json::from_str(&row.get::<String, _>("meanings")).unwrap_or_default(),
+            stroke_count: row.get("stroke_count"),
+            grade: row.get("grade"),
+            jlpt: row.get("jlpt"),
+            frequency: row.get("frequency"),
+            practice_time: row.get("practice_time"),
+            familiar: row.get("familiar"),
+        }
+    }
+}
+
+/// True for CJK ideographs. The iteration mark 々 and kana are not kanji.
+pub fn is_kanji(c: char) -> bool {
+    matches!(c as u32,
+        0x4E00..=0x9FFF     // CJK Unified Ideographs
+        | 0x3400..=0x4DBF   // Extension A
+        | 0xF900..=0xFAFF   // Compatibility Ideographs
+        | 0x20000..=0x2A6DF // Extension B
+    )
+}
+
+/// The distinct kanji of a text, in order of appearance, e.g. "食べ物" -> ['食', '物'].
+pub fn kanji_in(text: &str) -> Vec<char> {
+    let mut kanji: Vec<char> = Vec::new();
+    for c in text.chars().filter(|c| is_kanji(*c)) {
+        if !kanji.contains(&c) {
+            kanji.push(c);
+        }
+    }
+    kanji
+}
+
+/// Streams a KANJIDIC2 XML file, calling `on_kanji` for every character.
+/// Only Japanese readings and English meanings are kept.
+pub fn parse_kanjidic<R: BufRead>(source: R, mut on_kanji: impl FnMut(KanjiRecord)) -> Result<(), Box<dyn Error>> {
+    let mut reader = Reader::from_reader(source);
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut text = String::new();
+    let mut kanji = KanjiRecord::default();
+    // r_type of the current <reading>, or whether the current <meaning> is English
+    let mut reading_type = String::new();
+    let mut english_meaning = true;
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => {
+                text.clear();
+                match e.local_name().as_ref() {
+                    b"character" => kanji = KanjiRecord::default(),
+                    b"reading" => {
+                        reading_type = match e.try_get_attribute("r_type")? {
+                            Some(attr) => String::from_utf8_lossy(&attr.value).to_string(),
+                            None => String::new(),
+                        };
+                    }
+                    b"meaning" => english_meaning = e.try_get_attribute("m_lang")?.is_none(),
+                    _ => {}
+                }
+            }
+            Event::Text(e) => {
+                text.push_str(&e.unescape()?);
+            }
+            Event::End(e) => {
+                let value = std::mem::take(&mut text);
+                match e.local_name().as_ref() {
+                    b"literal" => kanji.literal = value,
+                    // the first stroke count is the right one, others are common miscounts
+                    b"stroke_count" if kanji.stroke_count == 0 => kanji.stroke_count = value.parse().unwrap_or(0),
+                    b"grade" => kanji.grade = value.parse().ok(),
+                    b"jlpt" => kanji.jlpt = value.parse().ok(),
+                    b"freq" => kanji.frequency = value.parse().ok(),
+                    b"reading" if reading_type == "ja_on" => kanji.on_readings.push(value),
+                    b"reading" if reading_type == "ja_kun" => kanji.kun_readings.push(value),
+                    b"meaning" if english_meaning => kanji.meanings.push(value),
+                    b"character" => on_kanji(std::mem::take(&mut kanji)),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(())
+}
+
+/// Imports or updates the `kanji` table from KANJIDIC2 and returns the number of kanji.
+/// Practice time and familiar are kept for kanji that are already in the table.
+pub async fn import_kanjidic<R: BufRead>(pool: &sqlx::SqlitePool, source: R) -> Result<usize, Box<dyn Error>> {
+    let mut characters = Vec::new();
+    parse_kanjidic(source, |kanji| characters.push(kanji))?;
+
+    let mut transaction = pool.begin().await?;
+    for kanji in &characters {
+        sqlx::query(
+            r#"
+            INSERT INTO kanji (literal, on_readings, kun_readings, meanings, stroke_count, grade, jlpt, frequency)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(literal) DO UPDATE SET
+                on_readings = excluded.on_readings,
+                kun_readings = excluded.kun_readings,
+                meanings = excluded.meanings,
+                stroke_count = excluded.stroke_count,
+                grade = excluded.grade,
+                jlpt = excluded.jlpt,
+                frequency = excluded.frequency
+            "#,
+        )
+        .bind(&kanji.literal)
+        .bind(kanji.on_readings.join(" "))
+        .bind(kanji.kun_readings.join(" "))
+        .bind(serde_json::to_string(&kanji.meanings)?)
+        .bind(kanji.stroke_count)
+        .bind(kanji.grade)
+        .bind(kanji.jlpt)
+        .bind(kanji.frequency)
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await?;
+
+    Ok(characters.len())
+}
+
+/// Looks up kanji, in the order given. Kanji that are not in the table are left out.
+pub async fn find_kanji(pool: &sqlx::SqlitePool, literals: &[char]) -> Result<Vec<KanjiRecord>, sqlx::Error> {
+    if literals.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut builder = sqlx::QueryBuilder::new("SELECT * FROM kanji WHERE literal IN (");
+    let mut separated = builder.separated(", ");
+    for literal in literals {
+        separated.push_bind(literal.to_string());
+    }
+    builder.push(")");
+
+    let mut found: HashMap<String, KanjiRecord> = builder
+        .build()
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| {
+            let kanji = KanjiRecord::from_row(row);
+            (kanji.literal.clone(), kanji)
+        })
+        .collect();
+
+    Ok(literals.iter().filter_map(|literal| found.remove(&literal.to_string())).collect())
+}
+
+/// The kanji used by `words`, in order of first appearance.
+pub async fn kanji_for_words(pool: &sqlx::SqlitePool, words: &[WordRecord]) -> Result<Vec<KanjiRecord>, sqlx::Error> {
+    let mut literals: Vec<char> = Vec::new();
+    for word in words {
+        for kanji in word.kanji() {
+            if !literals.contains(&kanji) {
+                literals.push(kanji);
+            }
+        }
+    }
+    find_kanji(pool, &literals).await
+}
+
+/// The vocabulary words in the database written with `literal`, easiest JLPT level first.
+pub async fn words_using_kanji(pool: &sqlx::SqlitePool, literal: &str) -> Result<Vec<WordRecord>, sqlx::Error> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM words WHERE deleted = 0 AND instr(expression, ?) > 0 ORDER BY jlpt DESC, id",
+        WORD_COLUMNS
+    ))
+    .bind(literal)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(WordRecord::from_row).collect())
+}
+
+/// Records one kanji drill answer.
+pub async fn update_kanji_progress(pool: &sqlx::SqlitePool, literal: &str, familiar: bool) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE kanji SET practice_time = practice_time + 1, familiar = ? WHERE literal = ?")
+        .bind(familiar)
+        .bind(literal)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Number of kanji in the table, 0 until KANJIDIC2 was imported.
+pub async fn count_kanji(pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM kanji").fetch_one(pool).await?;
+    Ok(count)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<kanjidic2>
+<character>
+<literal>食</literal>
+<misc><grade>2</grade><stroke_count>9</stroke_count><freq>328</freq><jlpt>3</jlpt></misc>
+<reading_meaning><rmgroup>
+<reading r_type="pinyin">shi2</reading>
+<reading r_type="ja_on">ショク</reading>
+<reading r_type="ja_on">ジキ</reading>
+<reading r_type="ja_kun">く.う</reading>
+<reading r_type="ja_kun">た.べる</reading>
+<meaning>eat</meaning>
+<meaning>food</meaning>
+<meaning m_lang="fr">manger</meaning>
+</rmgroup></reading_meaning>
+</character>
+<character>
+<literal>物</literal>
+<misc><grade>3</grade><stroke_count>8</stroke_count><stroke_count>7</stroke_count><jlpt>3</jlpt></misc>
+<reading_meaning><rmgroup>
+<reading r_type="ja_on">ブツ</reading>
+<reading r_type="ja_kun">もの</reading>
+<meaning>thing</meaning>
+</rmgroup></reading_meaning>
+</character>
+</kanjidic2>
+"#;
+
+    #[test]
+    fn test_kanji_in() {
+        assert_eq!(kanji_in("食べ物"), vec!['食', '物']);
+        assert_eq!(kanji_in("人々の人"), vec!['人']);
+        assert!(kanji_in("ありがとう").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_import_kanjidic() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            test_word("食べ物", "たべもの", "food", "n5"),
+            test_word("食事", "しょくじ", "meal", "n4"),
+        ]).await.expect("Failed to insert words.");
+
+        assert_eq!(import_kanjidic(&pool, SAMPLE.as_bytes()).await.expect("Failed to import."), 2);
+        assert_eq!(count_kanji(&pool).await.unwrap(), 2);
+
+        let words = ProgressSelect::new().execute(&pool).await.unwrap();
+        // 事 is not in the sample, so it is left out
+        let kanji = kanji_for_words(&pool, &words).await.unwrap();
+        assert_eq!(kanji.iter().map(|k| k.literal.as_str()).collect::<Vec<_>>(), vec!["食", "物"]);
+        assert_eq!(kanji[0].on_readings, vec!["ショク", "ジキ"]);
+        assert_eq!(kanji[0].kun_readings, vec!["く.う", "た.べる"]);
+        assert_eq!(kanji[0].meanings, vec!["eat", "food"]);
+        assert_eq!(kanji[0].grade, Some(2));
+        assert_eq!(kanji[1].stroke_count, 8);
+        assert_eq!(kanji[1].frequency, None);
+
+        let using = words_using_kanji(&pool, "食").await.unwrap();
+        assert_eq!(using.len(), 2);
+        assert_eq!(using[0].expression, "食べ物"); // n5 before n4
+
+        // re-importing keeps the drill progress
+        update_kanji_progress(&pool, "食", true).await.unwrap();
+        import_kanjidic(&pool, SAMPLE.as_bytes()).await.unwrap();
+        let eat = find_kanji(&pool, &['食']).await.unwrap();
+        assert_eq!(eat[0].practice_time, 1);
+        assert!(eat[0].familiar);
+    }
+}
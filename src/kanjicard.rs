@@ -0,0 +1,363 @@
+use dioxus::prelude::*;
+use crate::db::*;
+use crate::footer::{StatusMessage, StatusLevel};
+use crate::kanji::*;
+use crate::Route;
+
+use futures_util::StreamExt;
+use rand::seq::SliceRandom;
+use rand::rng;
+
+
+/// Settings page for a kanji drill, the kanji are taken from the vocabulary words.
+#[component]
+pub fn GenerateKanjiCard() -> Element {
+
+    let mut number_of_cards = use_signal(|| 15);
+    let mut jlpt_lv = use_signal(|| JLPTlv::N5.to_string());
+    let mut practiced_only = use_signal(|| false);
+    let mut unfamiliar_only = use_signal(|| true);
+    let mut random_shuffle = use_signal(|| true);
+
+    let navigator = use_navigator();
+    let mut status_message = use_context::<Signal<StatusMessage>>();
+    let db_pool = use_context::<sqlx::SqlitePool>();
+    let mut select_kanji = use_context::<Signal<Vec<KanjiRecord>>>();
+
+    rsx!(
+        div {
+            class: "container mt-2 p-4 border rounded shadow-sm bg-dark",
+            div { class: "row mb-3 g-3 align-items-end",
+                div { class: "col-md-6",
+                    label { class: "form-label", r#for: "numKanjiSelect", "Number of Kanji:" }
+                    select {
+                        class: "form-select",
+                        id: "numKanjiSelect",
+                        value: "{number_of_cards}",
+                        oninput: move |evt| {
+                            if let Ok(value) = evt.value().parse::<usize>() {
+                                number_of_cards.set(value);
+                            } else {
+                                eprintln!("Invalid input for number of kanji");
+                            }
+                        },
+                        option { value: "10", "10" }
+                        option { value: "15", "15" }
+                        option { value: "20", "20" }
+                        option { value: "25", "25" }
+                        option { value: "30", "30" }
+                    }
+                }
+                div { class: "col-md-6",
+                    label { class: "form-label", r#for: "kanjiJLPTSelect", "Kanji used by jlpt Level words:" }
+                    select {
+                        class: "form-select",
+                        id: "kanjiJLPTSelect",
+                        value: "{jlpt_lv}",
+                        oninput: move |evt| jlpt_lv.set(evt.value()),
+                        option { value: JLPTlv::N1.to_string(), "n1" }
+                        option { value: JLPTlv::N2.to_string(), "n2" }
+                        option { value: JLPTlv::N3.to_string(), "n3" }
+                        option { value: JLPTlv::N4.to_string(), "n4" }
+                        option { value: JLPTlv::N5.to_string(), "n5" }
+                        option { value: "any", "any level" }
+                    }
+                }
+            }
+
+            div { class: "row mb-3 g-3",
+                div { class: "col-md-6",
+                    div { class: "form-check",
+                        input {
+                            class: "form-check-input",
+                            r#type: "checkbox",
+                            id: "practicedWordsCheck",
+                            checked: practiced_only(),
+                            oninput: move |evt| practiced_only.set(evt.checked()),
+                        }
+                        label { class: "form-check-label", r#for: "practicedWordsCheck", "From Practiced Words Only" }
+                    }
+                    div { class: "form-check",
+                        input {
+                            class: "form-check-input",
+                            r#type: "checkbox",
+                            id: "unfamiliarKanjiCheck",
+                            checked: unfamiliar_only(),
+                            oninput: move |evt| unfamiliar_only.set(evt.checked()),
+                        }
+                        label { class: "form-check-label", r#for: "unfamiliarKanjiCheck", "Unfamiliar Kanji Only" }
+                    }
+                }
+                div { class: "col-md-6",
+                    div { class: "form-check",
+                        input {
+                            class: "form-check-input",
+                            r#type: "checkbox",
+                            id: "shuffleKanjiCheck",
+                            checked: random_shuffle(),
+                            oninput: move |evt| random_shuffle.set(evt.checked()),
+                        }
+                        label { class: "form-check-label", r#for: "shuffleKanjiCheck", "Random Shuffle Card" }
+                    }
+                }
+            }
+
+            div {
+                class: "row d-flex justify-content-end align-items-center",
+                div { class: "col-auto",
+                    button {
+                        class: "btn btn-primary btn-lg",
+                        r#type: "button",
+                        onclick: move |_| {
+                            let pool = db_pool.clone();
+
+                            let num = number_of_cards();
+                            let jlpt = JLPTlv::from_string(&jlpt_lv());
+                            let practiced = practiced_only();
+                            let unfamiliar = unfamiliar_only();
+                            let random = random_shuffle();
+
+                            async move {
+                                match count_kanji(&pool).await {
+                                    Ok(0) => {
+                                        status_message.set(StatusMessage {
+                                            message: "No kanji data, run `cargo run --bin import_kanjidic -- kanjidic2.xml` first.".to_string(),
+                                            level: StatusLevel::Warning,
+                                        });
+                                        return;
+                                    }
+                                    Ok(_) => {}
+                                    Err(e) => eprintln!("Error counting kanji: {}", e),
+                                }
+
+                                let mut select = match jlpt {
+                                    Some(jlpt) => ProgressSelect::new().select_jlpt(jlpt),
+                                    None => ProgressSelect::new(),
+                                };
+                                if practiced {
+                                    select = select.select_practice_time(1);
+                                }
+
+                                let kanji = match select.execute(&pool).await {
+                                    Ok(words) => kanji_for_words(&pool, &words).await,
+                                    Err(e) => Err(e),
+                                };
+
+                                match kanji {
+                                    Ok(mut kanji) => {
+                                        if unfamiliar {
+                                            kanji.retain(|k| !k.familiar);
+                                        }
+                                        if random {
+                                            kanji.shuffle(&mut rng());
+                                        }
+                                        kanji.truncate(num);
+
+                                        if kanji.is_empty() {
+                                            status_message.set(StatusMessage {
+                                                message: "No kanji found for these settings.".to_string(),
+                                                level: StatusLevel::Warning,
+                                            });
+                                        } else {
+                                            status_message.set(StatusMessage {
+                                                message: format!("{} kanji loaded successfully", kanji.len()),
+                                                level: StatusLevel::Success,
+                                            });
+                                            select_kanji.set(kanji);
+                                            navigator.push(Route::KanjiCard {});
+                                        }
+                                    }
+                                    Err(e) => {
+                                        eprintln!("Error fetching kanji: {}", e);
+                                        status_message.set(StatusMessage {
+                                            message: "A database error occurred.".to_string(),
+                                            level: StatusLevel::Error,
+                                        });
+                                    }
+                                }
+                            }
+                        },
+                        "Generate Kanji Card"
+                    }
+                }
+            }
+        }
+    )
+}
+
+/// Define the actions the user can perform in the kanji drill
+#[derive(Debug, Clone, Copy)]
+enum KanjiAction {
+    Answer { familiar: bool },
+    DisplayAnswer,
+}
+
+/// Kanji drill: shows a kanji, then its readings, meanings and the vocabulary words using it.
+#[component]
+pub fn KanjiCard() -> Element {
+    let navigator = use_navigator();
+    let mut index = use_signal(|| 0_usize);
+    let select_kanji = use_context::<Signal<Vec<KanjiRecord>>>();
+    let total_cards = select_kanji.len();
+    let mut status_message = use_context::<Signal<StatusMessage>>();
+    let mut show_answer = use_signal(|| false);
+
+    let db_pool = use_context::<sqlx::SqlitePool>();
+    let pool_action = db_pool.clone();
+    let pool_words = db_pool.clone();
+
+    let current = select_kanji.read().get(index()).cloned().unwrap_or_default();
+    let on_readings = current.on_readings.join("、");
+    let kun_readings = current.kun_readings.join("、");
+    let meanings = current.meanings.join(", ");
+
+    // vocabulary words written with the current kanji
+    let words = use_resource(move || {
+        let pool = pool_words.clone();
+        async move {
+            let literal = select_kanji.read().get(index()).map(|k| k.literal.clone()).unwrap_or_default();
+            words_using_kanji(&pool, &literal).await.unwrap_or_else(|e| {
+                eprintln!("Error loading words for kanji: {}", e);
+                Vec::new()
+            })
+        }
+    });
+
+    let km_actions = use_coroutine(move |mut rx: UnboundedReceiver<KanjiAction>| {
+        let pool = pool_action.clone();
+        async move {
+            while let Some(action) = rx.next().await {
+                match action {
+                    KanjiAction::Answer { familiar } => {
+                        let Some(literal) = select_kanji.read().get(index()).map(|k| k.literal.clone()) else {
+                            continue;
+                        };
+                        if let Err(e) = update_kanji_progress(&pool, &literal, familiar).await {
+                            eprintln!("Background update failed: {}", e);
+                        }
+
+                        let next_index = if index() + 1 < total_cards {
+                            index() + 1
+                        } else {
+                            status_message.set(StatusMessage {
+                                message: "End of cards, looping back to the start.".to_string(),
+                                level: StatusLevel::Info,
+                            });
+                            0
+                        };
+                        index.set(next_index);
+                        show_answer.set(false);
+                    }
+                    KanjiAction::DisplayAnswer => show_answer.set(true),
+                }
+            }
+        }
+    });
+
+    rsx! {
+        div {
+            class: "container h-75 d-flex flex-column",
+            tabindex: "0",
+            onmounted: move |evt| {
+                let element = evt.data();
+                spawn(async move {
+                    let _ = element.set_focus(true).await;
+                });
+            },
+            onkeydown: move |event: KeyboardEvent| {
+                match event.key() {
+                    Key::Character(s) if s.eq_ignore_ascii_case("n") => km_actions.send(KanjiAction::Answer { familiar: false }),
+                    Key::Character(s) if s.eq_ignore_ascii_case("g") => km_actions.send(KanjiAction::Answer { familiar: true }),
+                    Key::Character(s) if s.eq_ignore_ascii_case("s") => km_actions.send(KanjiAction::DisplayAnswer),
+                    _ => {}
+                }
+            },
+
+            // --- Top Controls ---
+            div { class: "row my-3 align-items-center",
+                div { class: "col-auto",
+                    button { class: "btn btn-secondary",
+                        onclick: move |_| {
+                            navigator.push(Route::GenerateKanjiCard {});
+                        },
+                        "Go Back"
+                    }
+                }
+                div { class: "col text-end",
+                    span { "{index() + 1} / {total_cards}" }
+                }
+            }
+
+            // --- Main Content ---
+            div { class: "row flex-grow-1 overflow-auto",
+                div { class: "col-md-4 d-flex flex-column align-items-center justify-content-center",
+                    p { class: "display-1 mb-0", "{current.literal}" }
+                    if current.familiar {
+                        span { class: "badge bg-success", "familiar" }
+                    }
+                }
+                div { class: "col-md-8",
+                    onclick: move |_| km_actions.send(KanjiAction::DisplayAnswer),
+                    if show_answer() {
+                        table { class: "table table-dark table-sm mb-3",
+                            tbody {
+                                tr { th { "On" } td { "{on_readings}" } }
+                                tr { th { "Kun" } td { "{kun_readings}" } }
+                                tr { th { "Meaning" } td { class: "text-success", "{meanings}" } }
+                                tr { th { "Strokes" } td { "{current.stroke_count}" } }
+                                if let Some(grade) = current.grade {
+                                    tr { th { "Grade" } td { "{grade}" } }
+                                }
+                                if let Some(jlpt) = current.jlpt {
+                                    tr { th { "Old JLPT" } td { "{jlpt}" } }
+                                }
+                            }
+                        }
+
+                        h5 { "Words using {current.literal}" }
+                        match &*words.read_unchecked() {
+                            Some(words) if !words.is_empty() => rsx! {
+                                table { class: "table table-dark table-striped table-hover table-sm",
+                                    tbody {
+                                        for word in words {
+                                            tr {
+                                                td {
+                                                    Link {
+                                                        to: Route::WordExplainer { word_to_explain: word.expression.clone() },
+                                                        class: "text-reset text-decoration-none",
+                                                        "{word.expression}"
+                                                    }
+                                                }
+                                                td { "{word.reading}" }
+                                                td { "{word.meaning}" }
+                                                td { "{word.jlpt}" }
+                                            }
+                                        }
+                                    }
+                                }
+                            },
+                            Some(_) => rsx! { p { class: "text-muted", "No vocabulary words use this kanji." } },
+                            None => rsx! { p { "Loading words..." } },
+                        }
+                    } else {
+                        div { class: "alert alert-info", "Click to ", u {"s"} , "how answer" }
+                    }
+                }
+            }
+
+            // --- Bottom Controls ---
+            div { class: "row my-3",
+                div { class: "col",
+                    button { class: "btn btn-danger w-100",
+                    onclick: move |_| km_actions.send(KanjiAction::Answer { familiar: false }),
+                    u {"N"}, "eed more practice" }
+                }
+                div { class: "col",
+                    button { class: "btn btn-success w-100",
+                    onclick: move |_| km_actions.send(KanjiAction::Answer { familiar: true }),
+                    u {"G"}, "ot it!" }
+                }
+            }
+        }
+    }
+}
@@ -6,15 +6,47 @@ pub mod testcard;
 pub mod utils;
 pub mod wordexplain_gemma;
 pub mod story;
+pub mod scheduler;
+pub mod review_log;
+pub mod migrations;
+pub mod word_import;
+pub mod anki;
+pub mod jmdict;
+pub mod kanji;
+pub mod kanjicard;
+pub mod backup;
+pub mod settings;
+pub mod llm;
+pub mod llm_json;
+pub mod stories;
+pub mod quiz;
+pub mod word_lookup;
+pub mod furigana;
+pub mod tokenizer;
+pub mod pitch;
+pub mod recall;
+pub mod distractor;
+pub mod cloze;
 
 use tts::*;
 
 use dioxus::prelude::*;
 use flashcard::{GenerateCard, DisplayCard};
 use wordlist::{WordListPage, WordListType};
-use testcard::{GnerateTestCard, TestCard};
-use wordexplain_gemma::WordExplainer;
-use story::{InteractiveStory, StoryGenerator};
+use testcard::{GnerateTestCard, ListeningCard, TestCard};
+use wordexplain_gemma::{WordExplainer, explanation_models, delete_explanations_by_model};
+use story::{InteractiveStory, StoryGenerator, StoryLibrary, SavedStory};
+use quiz::StoryQuiz;
+use recall::{ListeningRecall, RecallAnswer, RecallCard};
+use cloze::ClozeTest;
+use kanjicard::{GenerateKanjiCard, KanjiCard};
+use backup::{load_progress_backup, restore_progress, save_progress_backup, ProgressBackup, RestoreReport};
+use footer::{StatusMessage, StatusLevel};
+use llm::{LlmBackend, LlmConfig, LlmKind};
+use furigana::FuriganaSettings;
+use pitch::PitchSettings;
+use stories::JLPT_LEVELS;
+use std::sync::Arc;
 use db::*;
 use sqlx::SqlitePool;
 
@@ -35,12 +67,30 @@ pub enum Route {
     GnerateTestCard {},
     #[route("/testcard/:j_to_e")]
     TestCard { j_to_e: bool },
+    #[route("/recall/:answer")]
+    RecallCard { answer: RecallAnswer },
+    #[route("/listening/:j_to_e")]
+    ListeningCard { j_to_e: bool },
+    #[route("/listening/recall/:answer")]
+    ListeningRecall { answer: RecallAnswer },
+    #[route("/cloze/:typing")]
+    ClozeTest { typing: bool },
     #[route("/wordexplain/:word_to_explain")]
     WordExplainer {word_to_explain: String},
     #[route("/story/:story_text/:english_translation")]
     InteractiveStory { story_text: String, english_translation: String },
     #[route("/storyGen")]
     StoryGenerator {},
+    #[route("/stories")]
+    StoryLibrary {},
+    #[route("/stories/:story_id")]
+    SavedStory { story_id: i64 },
+    #[route("/stories/:story_id/quiz")]
+    StoryQuiz { story_id: i64 },
+    #[route("/kanji")]
+    GenerateKanjiCard {},
+    #[route("/kanji/study")]
+    KanjiCard {},
     #[route("/setting")]
     Setting {},
 
@@ -113,6 +163,14 @@ pub fn Navbar() -> Element { // Encapsulating navbar in its own component is goo
                                 "Flash Card"
                             }
                         }
+                        li { class: "nav-item",
+                            Link {
+                                class: "nav-link",
+                                to: Route::GenerateKanjiCard {},
+                                onclick: move |_| is_nav_open.set(false), // Close nav on link click
+                                "Kanji"
+                            }
+                        }
                         li { class: "nav-item",
                             Link {
                                 class: "nav-link",
@@ -138,6 +196,14 @@ pub fn Navbar() -> Element { // Encapsulating navbar in its own component is goo
                                 "Story"
                             }
                         }
+                        li { class: "nav-item",
+                            Link {
+                                class: "nav-link",
+                                to: Route::StoryLibrary {},
+                                onclick: move |_| is_nav_open.set(false), // Close nav on link click
+                                "Stories"
+                            }
+                        }
                         li { class: "nav-item",
                             Link {
                                 class: "nav-link",
@@ -175,6 +241,8 @@ fn Home() -> Element {
     let mut familiar_words = use_signal(|| 0);
     let mut unfamiliar_practiced = use_signal(|| 0);
     let mut marked_words = use_signal(|| 0);
+    let mut due_words = use_signal(|| 0);
+    let mut mode_counts = use_signal(Vec::new);
 
     let db_pool = use_context::<SqlitePool>();
 
@@ -229,6 +297,20 @@ fn Home() -> Element {
                 }
             };
             marked_words.set(marked_words1);
+
+            let due_words1 = match count_due_words(&pool, scheduler::today()).await {
+                Ok(count) => count,
+                Err(e) => {
+                    eprintln!("Error fetching due words: {}", e);
+                    0 // Default to 0 if there's an error
+                }
+            };
+            due_words.set(due_words1);
+
+            match review_log::count_reviews_by_mode(&pool).await {
+                Ok(counts) => mode_counts.set(counts),
+                Err(e) => eprintln!("Error fetching reviews by mode: {}", e),
+            }
         }
        
     });
@@ -286,6 +368,33 @@ fn Home() -> Element {
                         "Marked for Review"
                         span { class: "badge bg-warning text-dark rounded-pill fs-6", "{marked_words()}" }
                     }
+
+                    Link {
+                        to: Route::WordListPage { list_type: WordListType::Due },
+                        class: "list-group-item list-group-item-action d-flex justify-content-between align-items-center bg-transparent text-light",
+                        "Due for Review Today"
+                        span { class: "badge bg-danger rounded-pill fs-6", "{due_words()}" }
+                    }
+                }
+            }
+
+            // accuracy of each study mode, e.g. listening next to reading based tests
+            if !mode_counts.read().is_empty() {
+                div { class: "card shadow-sm bg-dark text-light mt-4",
+                    div { class: "card-header",
+                        h5 { class: "my-1", "Accuracy by Mode" }
+                    }
+                    ul { class: "list-group list-group-flush",
+                        for count in mode_counts() {
+                            li { class: "list-group-item d-flex justify-content-between align-items-center bg-transparent text-light",
+                                span { class: "text-capitalize", "{count.mode.to_string()}" }
+                                span {
+                                    span { class: "text-secondary me-3", "{count.total} reviews" }
+                                    span { class: "badge bg-info text-dark rounded-pill fs-6", {format!("{:.0}%", count.accuracy() * 100.0)} }
+                                }
+                            }
+                        }
+                    }
                 }
             }
 }
@@ -297,7 +406,52 @@ fn Home() -> Element {
 fn Setting() -> Element {
     // --- pool for db op ---
     let db_pool = use_context::<sqlx::SqlitePool>();
+    let pool_export = db_pool.clone();
+    let pool_import = db_pool.clone();
+    let pool_restore = db_pool.clone();
     let mut show_confirm_dialog = use_signal(|| false);
+    let mut status_message = use_context::<Signal<StatusMessage>>();
+
+    // --- progress backup ---
+    let mut backup_path = use_signal(|| "data/progress_backup.json".to_string());
+    // backup read from disk together with its dry-run report, waiting for confirmation
+    let mut pending_restore = use_signal(|| None::<(ProgressBackup, RestoreReport)>);
+
+    // --- LLM backend ---
+    let pool_llm = db_pool.clone();
+    let pool_llm_save = db_pool.clone();
+    let mut llm_backend = use_context::<Signal<Arc<dyn LlmBackend>>>();
+    let mut llm_config = use_signal(LlmConfig::default);
+    // --- furigana ---
+    let pool_furigana_save = db_pool.clone();
+    let mut furigana_settings = use_context::<Signal<FuriganaSettings>>();
+    let mut furigana_form = use_signal(|| furigana_settings.cloned());
+    // --- pitch accent ---
+    let pool_pitch_save = db_pool.clone();
+    let mut pitch_settings = use_context::<Signal<PitchSettings>>();
+    // --- explanation cache ---
+    let pool_cache = db_pool.clone();
+    let pool_cache_clear = db_pool.clone();
+    let mut cached_models = use_resource(move || {
+        let pool = pool_cache.clone();
+        async move {
+            explanation_models(&pool).await.unwrap_or_else(|e| {
+                eprintln!("Error listing cached explanations: {}", e);
+                Vec::new()
+            })
+        }
+    });
+
+    // show the saved settings in the form
+    let _ = use_resource(move || {
+        let pool = pool_llm.clone();
+        async move {
+            match LlmConfig::load(&pool).await {
+                Ok(config) => llm_config.set(config),
+                Err(e) => eprintln!("Error loading the LLM settings: {}", e),
+            }
+        }
+    });
 
 
     rsx!(
@@ -314,6 +468,381 @@ fn Setting() -> Element {
                     "Reset DB"
                 }
             }
+
+            h3 { class: "mt-3", "Backup your progress" }
+            div { class: "row g-2 align-items-center mb-3",
+                div { class: "col",
+                    input {
+                        class: "form-control",
+                        r#type: "text",
+                        placeholder: "Path of the backup file",
+                        value: "{backup_path}",
+                        oninput: move |evt| backup_path.set(evt.value()),
+                    }
+                }
+                div { class: "col-auto",
+                    button {
+                        class: "btn btn-primary",
+                        onclick: move |_| {
+                            let pool = pool_export.clone();
+                            let path = std::path::PathBuf::from(backup_path());
+                            async move {
+                                let message = match save_progress_backup(&pool, &path).await {
+                                    Ok(count) => StatusMessage {
+                                        message: format!("Progress of {} words saved to {}", count, path.display()),
+                                        level: StatusLevel::Success,
+                                    },
+                                    Err(e) => StatusMessage {
+                                        message: format!("Export failed: {}", e),
+                                        level: StatusLevel::Error,
+                                    },
+                                };
+                                status_message.set(message);
+                            }
+                        },
+                        "Export progress"
+                    }
+                }
+                div { class: "col-auto",
+                    button {
+                        class: "btn btn-outline-primary",
+                        onclick: move |_| {
+                            let pool = pool_import.clone();
+                            let path = std::path::PathBuf::from(backup_path());
+                            async move {
+                                let backup = match load_progress_backup(&path) {
+                                    Ok(backup) => backup,
+                                    Err(e) => {
+                                        status_message.set(StatusMessage {
+                                            message: format!("Could not read {}: {}", path.display(), e),
+                                            level: StatusLevel::Error,
+                                        });
+                                        return;
+                                    }
+                                };
+                                // dry run first, the modal applies it
+                                match restore_progress(&pool, &backup, true).await {
+                                    Ok(report) => pending_restore.set(Some((backup, report))),
+                                    Err(e) => status_message.set(StatusMessage {
+                                        message: format!("Import failed: {}", e),
+                                        level: StatusLevel::Error,
+                                    }),
+                                }
+                            }
+                        },
+                        "Import progress"
+                    }
+                }
+            }
+
+            h3 { class: "mt-3", "LLM backend" }
+            p { class: "text-secondary small", "Used by the word explainer and the story generator. Currently: {llm_backend.read().describe()}" }
+            div { class: "row g-2 mb-2",
+                div { class: "col-md-3",
+                    label { class: "form-label", r#for: "llmKindSelect", "Server" }
+                    select {
+                        class: "form-select",
+                        id: "llmKindSelect",
+                        value: llm_config.read().kind.to_string(),
+                        oninput: move |evt| {
+                            if let Ok(kind) = evt.value().parse::<LlmKind>() {
+                                let mut config = llm_config.write();
+                                config.kind = kind;
+                                config.base_url = kind.default_url();
+                            }
+                        },
+                        option { value: LlmKind::Ollama.to_string(), "Ollama" }
+                        option { value: LlmKind::OpenAi.to_string(), "OpenAI compatible (llama.cpp, LM Studio)" }
+                    }
+                }
+                div { class: "col-md-5",
+                    label { class: "form-label", r#for: "llmUrlInput", "URL" }
+                    input {
+                        class: "form-control",
+                        id: "llmUrlInput",
+                        r#type: "text",
+                        value: llm_config.read().base_url.clone(),
+                        oninput: move |evt| llm_config.write().base_url = evt.value(),
+                    }
+                }
+                div { class: "col-md-4",
+                    label { class: "form-label", r#for: "llmModelInput", "Model" }
+                    input {
+                        class: "form-control",
+                        id: "llmModelInput",
+                        r#type: "text",
+                        value: llm_config.read().model.clone(),
+                        oninput: move |evt| llm_config.write().model = evt.value(),
+                    }
+                }
+            }
+            div { class: "row g-2 align-items-end mb-3",
+                if llm_config.read().kind == LlmKind::OpenAi {
+                    div { class: "col-md-8",
+                        label { class: "form-label", r#for: "llmKeyInput", "API key (optional)" }
+                        input {
+                            class: "form-control",
+                            id: "llmKeyInput",
+                            r#type: "password",
+                            value: llm_config.read().api_key.clone(),
+                            oninput: move |evt| llm_config.write().api_key = evt.value(),
+                        }
+                    }
+                }
+                div { class: "col-md-2",
+                    label { class: "form-label", r#for: "llmRetriesInput", "JSON retries" }
+                    input {
+                        class: "form-control",
+                        id: "llmRetriesInput",
+                        r#type: "number",
+                        min: "0",
+                        max: "10",
+                        value: "{llm_config.read().json_retries}",
+                        oninput: move |evt| {
+                            if let Ok(retries) = evt.value().parse::<usize>() {
+                                llm_config.write().json_retries = retries.min(10);
+                            }
+                        },
+                    }
+                }
+                div { class: "col-auto ms-auto",
+                    button {
+                        class: "btn btn-primary",
+                        onclick: move |_| {
+                            let pool = pool_llm_save.clone();
+                            let config = llm_config();
+                            async move {
+                                let backend = match config.build() {
+                                    Ok(backend) => backend,
+                                    Err(e) => {
+                                        status_message.set(StatusMessage {
+                                            message: format!("Invalid LLM settings: {}", e),
+                                            level: StatusLevel::Error,
+                                        });
+                                        return;
+                                    }
+                                };
+                                match config.save(&pool).await {
+                                    Ok(_) => {
+                                        status_message.set(StatusMessage {
+                                            message: format!("Using {}", backend.describe()),
+                                            level: StatusLevel::Success,
+                                        });
+                                        llm_backend.set(backend);
+                                    }
+                                    Err(e) => status_message.set(StatusMessage {
+                                        message: format!("Saving the LLM settings failed: {}", e),
+                                        level: StatusLevel::Error,
+                                    }),
+                                }
+                            }
+                        },
+                        "Save LLM settings"
+                    }
+                }
+            }
+
+            h3 { class: "mt-3", "Furigana" }
+            p { class: "text-secondary small", "Readings shown above the kanji of stories, example sentences and flashcards." }
+            div { class: "row g-2 align-items-end mb-3",
+                div { class: "col-auto",
+                    div { class: "form-check form-switch",
+                        input {
+                            class: "form-check-input",
+                            id: "furiganaEnabled",
+                            r#type: "checkbox",
+                            checked: furigana_form.read().enabled,
+                            oninput: move |evt| furigana_form.write().enabled = evt.checked(),
+                        }
+                        label { class: "form-check-label", r#for: "furiganaEnabled", "Show furigana" }
+                    }
+                }
+                div { class: "col-md-4",
+                    label { class: "form-label", r#for: "furiganaLevelSelect", "Hide it on words I know up to" }
+                    select {
+                        class: "form-select",
+                        id: "furiganaLevelSelect",
+                        disabled: !furigana_form.read().enabled,
+                        value: furigana_form.read().known_level.clone(),
+                        oninput: move |evt| furigana_form.write().known_level = evt.value(),
+                        option { value: "", "Show on every level" }
+                        for level in JLPT_LEVELS {
+                            option { value: level, "{level.to_uppercase()}" }
+                        }
+                    }
+                }
+                div { class: "col-auto",
+                    div { class: "form-check",
+                        input {
+                            class: "form-check-input",
+                            id: "furiganaFamiliar",
+                            r#type: "checkbox",
+                            disabled: !furigana_form.read().enabled,
+                            checked: furigana_form.read().hide_familiar,
+                            oninput: move |evt| furigana_form.write().hide_familiar = evt.checked(),
+                        }
+                        label { class: "form-check-label", r#for: "furiganaFamiliar", "Hide it on familiar words" }
+                    }
+                }
+                div { class: "col-auto ms-auto",
+                    button {
+                        class: "btn btn-primary",
+                        onclick: move |_| {
+                            let pool = pool_furigana_save.clone();
+                            let settings = furigana_form();
+                            async move {
+                                match settings.save(&pool).await {
+                                    Ok(_) => {
+                                        status_message.set(StatusMessage {
+                                            message: "Furigana settings saved".to_string(),
+                                            level: StatusLevel::Success,
+                                        });
+                                        furigana_settings.set(settings);
+                                    }
+                                    Err(e) => status_message.set(StatusMessage {
+                                        message: format!("Saving the furigana settings failed: {}", e),
+                                        level: StatusLevel::Error,
+                                    }),
+                                }
+                            }
+                        },
+                        "Save furigana settings"
+                    }
+                }
+            }
+
+            h3 { class: "mt-3", "Pitch accent" }
+            p { class: "text-secondary small", "Drawn over the reading on flashcards, tests and word lists once accents are imported with import_pitch_accents." }
+            div { class: "form-check form-switch mb-3",
+                input {
+                    class: "form-check-input",
+                    id: "pitchShow",
+                    r#type: "checkbox",
+                    checked: pitch_settings.read().show,
+                    oninput: move |evt| {
+                        let pool = pool_pitch_save.clone();
+                        let settings = PitchSettings { show: evt.checked() };
+                        async move {
+                            match settings.save(&pool).await {
+                                Ok(_) => pitch_settings.set(settings),
+                                Err(e) => status_message.set(StatusMessage {
+                                    message: format!("Saving the pitch accent setting failed: {}", e),
+                                    level: StatusLevel::Error,
+                                }),
+                            }
+                        }
+                    },
+                }
+                label { class: "form-check-label", r#for: "pitchShow", "Show pitch accent" }
+            }
+
+            h3 { class: "mt-3", "Explanation cache" }
+            p { class: "text-secondary small", "Word explanations are kept so they show instantly and work offline. Clear the answers of a model to have them generated again." }
+            match &*cached_models.read_unchecked() {
+                Some(models) if !models.is_empty() => rsx! {
+                    table { class: "table table-dark table-sm",
+                        tbody {
+                            for (model, total) in models.clone() {
+                                tr {
+                                    td { "{model}" }
+                                    td { "{total} explanations" }
+                                    td { class: "text-end",
+                                        button {
+                                            class: "btn btn-outline-danger btn-sm",
+                                            onclick: {
+                                                let pool = pool_cache_clear.clone();
+                                                let model = model.clone();
+                                                move |_| {
+                                                    let pool = pool.clone();
+                                                    let model = model.clone();
+                                                    async move {
+                                                        match delete_explanations_by_model(&pool, &model).await {
+                                                            Ok(deleted) => status_message.set(StatusMessage {
+                                                                message: format!("{} cached explanations of {} deleted", deleted, model),
+                                                                level: StatusLevel::Success,
+                                                            }),
+                                                            Err(e) => status_message.set(StatusMessage {
+                                                                message: format!("Clearing the cache failed: {}", e),
+                                                                level: StatusLevel::Error,
+                                                            }),
+                                                        }
+                                                        cached_models.restart();
+                                                    }
+                                                }
+                                            },
+                                            "Clear"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(_) => rsx! { p { class: "text-muted small", "No cached explanations yet." } },
+                None => rsx! { p { "Loading..." } },
+            }
+        }
+
+        // Summary of the dry run, only shown while a restore is waiting for confirmation.
+        if let Some((backup, report)) = pending_restore() {
+            div {
+                class: "modal fade show d-block",
+                style: "background-color: rgba(0, 0, 0, 0.5);",
+
+                div { class: "modal-dialog modal-dialog-centered modal-dialog-scrollable",
+                    div { class: "modal-content",
+                        div { class: "modal-header",
+                            h5 { class: "modal-title", "Import progress" }
+                        }
+                        div { class: "modal-body",
+                            p { "{report.restored} of {backup.words.len()} words will be restored and {report.reviews_added} reviews added to the history." }
+                            p { "The progress of these words in the current database will be overwritten." }
+                            if !report.missing.is_empty() {
+                                p { class: "text-warning", "{report.missing.len()} words are not in the database and will be skipped:" }
+                                ul { class: "small",
+                                    for word in report.missing.iter() {
+                                        li { "{word}" }
+                                    }
+                                }
+                            }
+                        }
+                        div { class: "modal-footer",
+                            button {
+                                class: "btn btn-secondary",
+                                onclick: move |_| pending_restore.set(None),
+                                "Cancel"
+                            }
+                            button {
+                                class: "btn btn-primary",
+                                onclick: move |_| {
+                                    let pool = pool_restore.clone();
+                                    let pending = pending_restore.take();
+                                    async move {
+                                        let Some((backup, _)) = pending else {
+                                            return;
+                                        };
+                                        let message = match restore_progress(&pool, &backup, false).await {
+                                            Ok(report) => StatusMessage {
+                                                message: format!(
+                                                    "{} words restored, {} reviews added",
+                                                    report.restored, report.reviews_added
+                                                ),
+                                                level: StatusLevel::Success,
+                                            },
+                                            Err(e) => StatusMessage {
+                                                message: format!("Import failed: {}", e),
+                                                level: StatusLevel::Error,
+                                            },
+                                        };
+                                        status_message.set(message);
+                                    }
+                                },
+                                "Apply"
+                            }
+                        }
+                    }
+                }
+            }
         }
 
         
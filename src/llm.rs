@@ -0,0 +1,531 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use ollama_rs::{Ollama, generation::completion::request::GenerationRequest};
+use ollama_rs::generation::parameters::{FormatType, JsonStructure};
+use serde::Deserialize;
+
+use crate::settings::{get_setting, set_setting};
+
+/// Error type of the LLM backends, Send + Sync so requests can run on any task.
+pub type LlmError = Box<dyn Error + Send + Sync>;
+
+/// How many times a JSON answer that can't be parsed is asked for again, unless set on the setting page.
+pub const DEFAULT_JSON_RETRIES: usize = 2;
+
+/// A text generation backend. The app only needs "prompt in, text out",
+/// the pages get the backend from the `Signal<Arc<dyn LlmBackend>>` context.
+pub trait LlmBackend: Send + Sync {
+    /// Name of the model, e.g. "gemma3:4b".
+    fn model(&self) -> String;
+
+    /// Short description for the status bar, e.g. "Ollama gemma3:4b".
+    fn describe(&self) -> String;
+
+    /// Generates the whole response for `prompt`.
+    fn generate<'a>(&'a self, prompt: &'a str) -> BoxFuture<'a, Result<String, LlmError>>;
+
+    /// Generates the response as a stream of text chunks.
+    /// Backends that can't stream yield the whole response as one chunk.
+    fn generate_stream<'a>(&'a self, prompt: &'a str) -> BoxStream<'a, Result<String, LlmError>> {
+        stream::once(self.generate(prompt)).boxed()
+    }
+
+    /// Like `generate_stream`, but asks the server for JSON following `schema` (a JSON schema).
+    /// Backends without a JSON mode ignore the schema and rely on the prompt.
+    fn generate_json_stream<'a>(
+        &'a self,
+        prompt: &'a str,
+        schema: &'a serde_json::Value,
+    ) -> BoxStream<'a, Result<String, LlmError>> {
+        let _ = schema;
+        self.generate_stream(prompt)
+    }
+
+    /// How many times `llm_json::generate_json` asks again after an answer that can't be parsed.
+    fn json_retries(&self) -> usize {
+        DEFAULT_JSON_RETRIES
+    }
+}
+
+/// Which kind of server the LLM settings point to.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LlmKind {
+    #[default]
+    Ollama,
+    /// Any server with an OpenAI style `/chat/completions` endpoint (llama.cpp, LM Studio, ...)
+    OpenAi,
+}
+
+/// The name stored in the `llm.kind` setting.
+impl fmt::Display for LlmKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LlmKind::Ollama => "ollama",
+            LlmKind::OpenAi => "openai",
+        })
+    }
+}
+
+impl FromStr for LlmKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "ollama" => Ok(LlmKind::Ollama),
+            "openai" => Ok(LlmKind::OpenAi),
+            _ => Err(format!("Unknown LLM server kind: {}", kind)),
+        }
+    }
+}
+
+impl LlmKind {
+    /// Address the server listens on out of the box.
+    pub fn default_url(&self) -> String {
+        match self {
+            LlmKind::Ollama => "http://127.0.0.1:11434".to_string(),
+            LlmKind::OpenAi => "http://127.0.0.1:8080/v1".to_string(),
+        }
+    }
+}
+
+/// The LLM settings, stored under the `llm.*` keys of the settings table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlmConfig {
+    pub kind: LlmKind,
+    pub base_url: String,
+    pub model: String,
+    /// Only sent to OpenAI compatible servers, most local servers don't need one.
+    pub api_key: String,
+    /// Retries with a corrective prompt when a JSON answer can't be parsed.
+    pub json_retries: usize,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        LlmConfig {
+            kind: LlmKind::Ollama,
+            base_url: LlmKind::Ollama.default_url(),
+            model: "gemma3:4b".to_string(),
+            api_key: String::new(),
+            json_retries: DEFAULT_JSON_RETRIES,
+        }
+    }
+}
+
+impl LlmConfig {
+    /// Loads the saved settings, anything that was never saved keeps its default.
+    pub async fn load(pool: &sqlx::SqlitePool) -> Result<Self, sqlx::Error> {
+        let mut config = LlmConfig::default();
+        if let Some(kind) = get_setting(pool, "llm.kind").await?.and_then(|kind| kind.parse::<LlmKind>().ok()) {
+            config.kind = kind;
+            config.base_url = kind.default_url();
+        }
+        if let Some(base_url) = get_setting(pool, "llm.base_url").await? {
+            config.base_url = base_url;
+        }
+        if let Some(model) = get_setting(pool, "llm.model").await? {
+            config.model = model;
+        }
+        if let Some(api_key) = get_setting(pool, "llm.api_key").await? {
+            config.api_key = api_key;
+        }
+        if let Some(retries) = get_setting(pool, "llm.json_retries").await?.and_then(|retries| retries.parse().ok()) {
+            config.json_retries = retries;
+        }
+        Ok(config)
+    }
+
+    pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
+        set_setting(pool, "llm.kind", &self.kind.to_string()).await?;
+        set_setting(pool, "llm.base_url", self.base_url.trim()).await?;
+        set_setting(pool, "llm.model", self.model.trim()).await?;
+        set_setting(pool, "llm.api_key", self.api_key.trim()).await?;
+        set_setting(pool, "llm.json_retries", &self.json_retries.to_string()).await?;
+        Ok(())
+    }
+
+    /// Creates the backend described by the settings.
+    pub fn build(&self) -> Result<Arc<dyn LlmBackend>, LlmError> {
+        if self.model.trim().is_empty() {
+            return Err("No model name set.".into());
+        }
+        let backend: Arc<dyn LlmBackend> = match self.kind {
+            LlmKind::Ollama => Arc::new(
+                OllamaBackend::new(&self.base_url, &self.model)?.with_json_retries(self.json_retries),
+            ),
+            LlmKind::OpenAi => Arc::new(
+                OpenAiBackend::new(&self.base_url, &self.model, &self.api_key).with_json_retries(self.json_retries),
+            ),
+        };
+        Ok(backend)
+    }
+}
+
+
+/// Ollama server, through ollama-rs.
+pub struct OllamaBackend {
+    client: Ollama,
+    model: String,
+    json_retries: usize,
+}
+
+impl OllamaBackend {
+    pub fn new(base_url: &str, model: &str) -> Result<Self, LlmError> {
+        Ok(OllamaBackend {
+            client: Ollama::try_new(base_url.trim())?,
+            model: model.trim().to_string(),
+            json_retries: DEFAULT_JSON_RETRIES,
+        })
+    }
+
+    pub fn with_json_retries(mut self, retries: usize) -> Self {
+        self.json_retries = retries;
+        self
+    }
+
+    fn stream_request<'a>(&'a self, request: GenerationRequest<'a>) -> BoxStream<'a, Result<String, LlmError>> {
+        stream::once(async move {
+            let responses = self.client.generate_stream(request).await?;
+            // each chunk holds the responses that arrived in one read, usually a single token
+            Ok::<_, LlmError>(responses.map(|chunk| -> Result<String, LlmError> {
+                Ok(chunk?.into_iter().map(|response| response.response).collect())
+            }))
+        })
+        .try_flatten()
+        .boxed()
+    }
+}
+
+impl LlmBackend for OllamaBackend {
+    fn model(&self) -> String {
+        self.model.clone()
+    }
+
+    fn describe(&self) -> String {
+        format!("Ollama {}", self.model)
+    }
+
+    fn generate<'a>(&'a self, prompt: &'a str) -> BoxFuture<'a, Result<String, LlmError>> {
+        Box::pin(async move {
+            let request = GenerationRequest::new(self.model.clone(), prompt);
+            let response = self.client.generate(request).await?;
+            Ok(response.response)
+        })
+    }
+
+    fn generate_stream<'a>(&'a self, prompt: &'a str) -> BoxStream<'a, Result<String, LlmError>> {
+        self.stream_request(GenerationRequest::new(self.model.clone(), prompt))
+    }
+
+    fn generate_json_stream<'a>(
+        &'a self,
+        prompt: &'a str,
+        schema: &'a serde_json::Value,
+    ) -> BoxStream<'a, Result<String, LlmError>> {
+        // structured outputs need Ollama 0.5, plain JSON mode is the fallback for a schema schemars rejects
+        let format = match schema.clone().try_into() {
+            Ok(schema) => FormatType::StructuredJson(Box::new(JsonStructure::new_for_schema(schema))),
+            Err(e) => {
+                eprintln!("Unusable JSON schema, falling back to JSON mode: {}", e);
+                FormatType::Json
+            }
+        };
+        self.stream_request(GenerationRequest::new(self.model.clone(), prompt).format(format))
+    }
+
+    fn json_retries(&self) -> usize {
+        self.json_retries
+    }
+}
+
+
+/// Server with an OpenAI compatible chat completions API, e.g. llama.cpp `llama-server` or LM Studio.
+pub struct OpenAiBackend {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+    api_key: String,
+    json_retries: usize,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletion {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatChunk {
+    choices: Vec<ChatDelta>,
+}
+
+#[derive(Deserialize)]
+struct ChatDelta {
+    delta: ChatMessage,
+}
+
+/// Takes the complete server-sent event lines out of `buffer` and returns the text they add,
+/// a line cut in the middle stays in the buffer until the rest arrives.
+fn take_stream_deltas(buffer: &mut Vec<u8>) -> String {
+    let mut text = String::new();
+    while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+        let line: Vec<u8> = buffer.drain(..=end).collect();
+        let line = String::from_utf8_lossy(&line);
+        let Some(data) = line.trim().strip_prefix("data:") else {
+            continue;
+        };
+        let data = data.trim();
+        if data == "[DONE]" {
+            continue;
+        }
+        match serde_json::from_str::<ChatChunk>(data) {
+            Ok(chunk) => {
+                for choice in chunk.choices {
+                    text.push_str(&choice.delta.content.unwrap_or_default());
+                }
+            }
+            Err(e) => eprintln!("Skipping unreadable stream event {}: {}", data, e),
+        }
+    }
+    text
+}
+
+/// Extracts the text of the first choice from a `/chat/completions` response body.
+fn parse_chat_completion(body: &str) -> Result<String, LlmError> {
+    let completion: ChatCompletion = serde_json::from_str(body)?;
+    completion
+        .choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.message.content)
+        .ok_or_else(|| "The server returned no completion.".into())
+}
+
+impl OpenAiBackend {
+    /// `base_url` is the API root including the version, e.g. "http://127.0.0.1:1234/v1".
+    pub fn new(base_url: &str, model: &str, api_key: &str) -> Self {
+        OpenAiBackend {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim().trim_end_matches('/').to_string(),
+            model: model.trim().to_string(),
+            api_key: api_key.trim().to_string(),
+            json_retries: DEFAULT_JSON_RETRIES,
+        }
+    }
+
+    pub fn with_json_retries(mut self, retries: usize) -> Self {
+        self.json_retries = retries;
+        self
+    }
+
+    /// `schema` turns on the structured output mode of the server.
+    fn request(&self, prompt: &str, stream: bool, schema: Option<&serde_json::Value>) -> reqwest::RequestBuilder {
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "messages": [{ "role": "user", "content": prompt }],
+            "stream": stream,
+        });
+        if let Some(schema) = schema {
+            body["response_format"] = serde_json::json!({
+                "type": "json_schema",
+                "json_schema": { "name": "response", "schema": schema },
+            });
+        }
+        let request = self.client.post(format!("{}/chat/completions", self.base_url)).json(&body);
+        if self.api_key.is_empty() {
+            request
+        } else {
+            request.bearer_auth(&self.api_key)
+        }
+    }
+
+    fn stream_request<'a>(&'a self, request: reqwest::RequestBuilder) -> BoxStream<'a, Result<String, LlmError>> {
+        stream::once(async move {
+            let response = request.send().await?;
+            let status = response.status();
+            if !status.is_success() {
+                let body = response.text().await?;
+                return Err(format!("{} returned {}: {}", self.base_url, status, body).into());
+            }
+            let mut buffer = Vec::new();
+            Ok::<_, LlmError>(response.bytes_stream().map(move |bytes| -> Result<String, LlmError> {
+                buffer.extend_from_slice(&bytes?);
+                Ok(take_stream_deltas(&mut buffer))
+            }))
+        })
+        .try_flatten()
+        .boxed()
+    }
+}
+
+impl LlmBackend for OpenAiBackend {
+    fn model(&self) -> String {
+        self.model.clone()
+    }
+
+    fn describe(&self) -> String {
+        format!("{} at {}", self.model, self.base_url)
+    }
+
+    fn generate<'a>(&'a self, prompt: &'a str) -> BoxFuture<'a, Result<String, LlmError>> {
+        Box::pin(async move {
+            let response = self.request(prompt, false, None).send().await?;
+            let status = response.status();
+            let body = response.text().await?;
+            if !status.is_success() {
+                return Err(format!("{} returned {}: {}", self.base_url, status, body).into());
+            }
+            parse_chat_completion(&body)
+        })
+    }
+
+    fn generate_stream<'a>(&'a self, prompt: &'a str) -> BoxStream<'a, Result<String, LlmError>> {
+        self.stream_request(self.request(prompt, true, None))
+    }
+
+    fn generate_json_stream<'a>(
+        &'a self,
+        prompt: &'a str,
+        schema: &'a serde_json::Value,
+    ) -> BoxStream<'a, Result<String, LlmError>> {
+        self.stream_request(self.request(prompt, true, Some(schema)))
+    }
+
+    fn json_retries(&self) -> usize {
+        self.json_retries
+    }
+}
+
+
+/// Deterministic backend for tests: returns the given responses in order and
+/// keeps returning the last one once they run out. The prompts are recorded.
+pub struct MockBackend {
+    responses: Vec<String>,
+    next: AtomicUsize,
+    prompts: Mutex<Vec<String>>,
+    json_retries: usize,
+}
+
+impl MockBackend {
+    pub fn new(responses: Vec<String>) -> Self {
+        MockBackend {
+            responses,
+            next: AtomicUsize::new(0),
+            prompts: Mutex::new(Vec::new()),
+            json_retries: DEFAULT_JSON_RETRIES,
+        }
+    }
+
+    pub fn with_json_retries(mut self, retries: usize) -> Self {
+        self.json_retries = retries;
+        self
+    }
+
+    /// The prompts received so far, oldest first.
+    pub fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().map(|prompts| prompts.clone()).unwrap_or_default()
+    }
+}
+
+impl LlmBackend for MockBackend {
+    fn model(&self) -> String {
+        "mock".to_string()
+    }
+
+    fn describe(&self) -> String {
+        "mock backend".to_string()
+    }
+
+    fn generate<'a>(&'a self, prompt: &'a str) -> BoxFuture<'a, Result<String, LlmError>> {
+        Box::pin(async move {
+            if let Ok(mut prompts) = self.prompts.lock() {
+                prompts.push(prompt.to_string());
+            }
+            let index = self.next.fetch_add(1, Ordering::SeqCst).min(self.responses.len().saturating_sub(1));
+            self.responses
+                .get(index)
+                .cloned()
+                .ok_or_else(|| "The mock backend has no responses.".into())
+        })
+    }
+
+    fn json_retries(&self) -> usize {
+        self.json_retries
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::setup_test_db;
+
+    #[tokio::test]
+    async fn test_mock_backend() {
+        let mock = MockBackend::new(vec!["first".to_string(), "second".to_string()]);
+        assert_eq!(mock.generate("a").await.unwrap(), "first");
+        assert_eq!(mock.generate("b").await.unwrap(), "second");
+        assert_eq!(mock.generate("c").await.unwrap(), "second");
+        assert_eq!(mock.prompts(), vec!["a", "b", "c"]);
+
+        let chunks: Vec<String> = mock.generate_stream("d").map(|chunk| chunk.unwrap()).collect().await;
+        assert_eq!(chunks, vec!["second"]);
+
+        assert!(MockBackend::new(Vec::new()).generate("a").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_config_round_trip() {
+        let pool = setup_test_db().await;
+
+        assert_eq!(LlmConfig::load(&pool).await.unwrap(), LlmConfig::default());
+
+        let config = LlmConfig {
+            kind: LlmKind::OpenAi,
+            base_url: "http://127.0.0.1:1234/v1/".to_string(),
+            model: "qwen2.5-7b-instruct".to_string(),
+            api_key: String::new(),
+            json_retries: 4,
+        };
+        config.save(&pool).await.unwrap();
+        assert_eq!(LlmConfig::load(&pool).await.unwrap(), config);
+
+        let backend = config.build().unwrap();
+        assert_eq!(backend.model(), "qwen2.5-7b-instruct");
+        assert_eq!(backend.describe(), "qwen2.5-7b-instruct at http://127.0.0.1:1234/v1");
+        assert_eq!(backend.json_retries(), 4);
+        assert!(LlmConfig { model: " ".to_string(), ..LlmConfig::default() }.build().is_err());
+    }
+
+    #[test]
+    fn test_parse_chat_completion() {
+        let body = r#"{"id":"chatcmpl-1","object":"chat.completion","choices":[{"index":0,"message":{"role":"assistant","content":"{\"story\": \"...\"}"},"finish_reason":"stop"}]}"#;
+        assert_eq!(parse_chat_completion(body).unwrap(), r#"{"story": "..."}"#);
+        assert!(parse_chat_completion(r#"{"choices":[]}"#).is_err());
+    }
+
+    #[test]
+    fn test_take_stream_deltas() {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice("data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"{\\\"sto\"}}]}\n\ndata: {\"choi".as_bytes());
+        assert_eq!(take_stream_deltas(&mut buffer), "{\"sto");
+        buffer.extend_from_slice("ces\":[{\"delta\":{\"content\":\"ry\\\": \\\"青\"}}]}\n\ndata: [DONE]\n\n".as_bytes());
+        assert_eq!(take_stream_deltas(&mut buffer), "ry\": \"青");
+        assert!(buffer.is_empty());
+    }
+}
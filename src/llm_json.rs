@@ -0,0 +1,261 @@
+use std::error::Error;
+
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::llm::LlmBackend;
+
+/// Repairs the common ways LLMs break JSON and returns the first JSON object of `text`:
+/// - text or code fences around the object, and anything after it
+/// - trailing commas before '}' or ']'
+/// - unescaped quotes and raw line breaks inside strings
+/// - an answer cut off before the closing brackets
+///
+/// Valid JSON is returned unchanged. None when there is no '{' at all.
+pub fn repair_json(text: &str) -> Option<String> {
+    let start = text.find('{')?;
+    let chars: Vec<char> = text[start..].chars().collect();
+
+    let mut repaired = String::with_capacity(chars.len());
+    let mut open: Vec<char> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+                repaired.push(c);
+            } else if c == '\\' {
+                escaped = true;
+                repaired.push(c);
+            } else if c == '"' {
+                // a quote ends the string only if JSON can continue after it,
+                // otherwise it is a quote the model forgot to escape
+                let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+                if matches!(next, None | Some(',') | Some('}') | Some(']') | Some(':')) {
+                    in_string = false;
+                    repaired.push(c);
+                } else {
+                    repaired.push_str("\\\"");
+                }
+            } else if c == '\n' {
+                repaired.push_str("\\n");
+            } else if c == '\t' {
+                repaired.push_str("\\t");
+            } else if !c.is_control() {
+                repaired.push(c);
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                repaired.push(c);
+            }
+            '{' | '[' => {
+                open.push(c);
+                repaired.push(c);
+            }
+            '}' | ']' => {
+                remove_trailing_comma(&mut repaired);
+                open.pop();
+                repaired.push(c);
+                if open.is_empty() {
+                    return Some(repaired);
+                }
+            }
+            _ => repaired.push(c),
+        }
+    }
+
+    // the answer was cut off, close what is still open
+    if in_string {
+        if escaped {
+            repaired.pop();
+        }
+        repaired.push('"');
+    }
+    remove_trailing_comma(&mut repaired);
+    for bracket in open.iter().rev() {
+        repaired.push(if *bracket == '{' { '}' } else { ']' });
+    }
+    Some(repaired)
+}
+
+fn remove_trailing_comma(json: &mut String) {
+    let trimmed = json.trim_end();
+    if trimmed.ends_with(',') {
+        let end = trimmed.len() - 1;
+        json.truncate(end);
+    }
+}
+
+/// Repairs the LLM output and deserializes it into `T`,
+/// the error says what is missing or wrong so it can be sent back to the model.
+pub fn parse_llm_output<T: DeserializeOwned>(raw_text: &str) -> Result<T, Box<dyn Error>> {
+    let json = repair_json(raw_text).ok_or("The answer contains no JSON object.")?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// JSON schema of an object whose properties are all required.
+pub fn object_schema(properties: &[(&str, Value)]) -> Value {
+    let required: Vec<&str> = properties.iter().map(|(name, _)| *name).collect();
+    let properties: serde_json::Map<String, Value> = properties
+        .iter()
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+    serde_json::json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+/// JSON schema of a string.
+pub fn string_schema() -> Value {
+    serde_json::json!({ "type": "string" })
+}
+
+/// What `generate_json` reports while it works.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonProgress<'a> {
+    /// The text generated so far by the current attempt.
+    Partial(&'a str),
+    /// The answer could not be used, the model is asked again.
+    /// `attempt` is the number of the new attempt, starting at 2.
+    Retry { attempt: usize, attempts: usize, error: String },
+}
+
+/// Asks `llm` for a JSON answer following `schema` and deserializes it into `T`.
+/// Output that can't be repaired or doesn't fit `T` is sent back to the model with the
+/// parse error, up to `llm.json_retries()` times. Errors of the backend itself are not retried.
+pub async fn generate_json<T: DeserializeOwned>(
+    llm: &dyn LlmBackend,
+    prompt: &str,
+    schema: &Value,
+    mut on_progress: impl FnMut(JsonProgress),
+) -> Result<T, Box<dyn Error>> {
+    let attempts = llm.json_retries() + 1;
+    let mut current_prompt = prompt.to_string();
+    let mut last_error = String::new();
+
+    for attempt in 1..=attempts {
+        if attempt > 1 {
+            on_progress(JsonProgress::Retry { attempt, attempts, error: last_error.clone() });
+        }
+
+        let mut response = String::new();
+        let mut chunks = llm.generate_json_stream(&current_prompt, schema);
+        while let Some(chunk) = chunks.next().await {
+            response.push_str(&chunk.map_err(|e| e as Box<dyn Error>)?);
+            on_progress(JsonProgress::Partial(&response));
+        }
+        drop(chunks);
+
+        match parse_llm_output(&response) {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                eprintln!("Attempt {}/{}: unusable answer from {}: {}\n{}", attempt, attempts, llm.describe(), e, response);
+                last_error = e.to_string();
+                current_prompt = corrective_prompt(prompt, &response, &last_error);
+            }
+        }
+    }
+
+    Err(format!(
+        "{} did not answer with valid JSON after {} attempts: {}",
+        llm.describe(),
+        attempts,
+        last_error
+    )
+    .into())
+}
+
+/// The original prompt followed by the rejected answer and why it was rejected.
+fn corrective_prompt(prompt: &str, answer: &str, error: &str) -> String {
+    format!(
+        "{}\n\nYour previous answer could not be used: {}\nPrevious answer:\n{}\n\n\
+         Answer again with only the JSON object, following the template exactly. \
+         Escape double quotes inside strings as \\\".",
+        prompt, error, answer
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockBackend;
+
+    #[derive(serde::Deserialize, Debug)]
+    struct Story {
+        story: String,
+        english: String,
+    }
+
+    #[test]
+    fn test_repair_json() {
+        // valid JSON is left alone
+        let valid = r#"{"valid": "esc \" ok", "n": [1, {"x": null}]}"#;
+        assert_eq!(repair_json(&format!("{} trailing {{", valid)).unwrap(), valid);
+
+        assert_eq!(
+            repair_json("```json\n{\"a\": \"x\", \"b\": [1, 2,],}\n```").unwrap(),
+            r#"{"a": "x", "b": [1, 2]}"#
+        );
+        assert_eq!(
+            repair_json(r#"Sure! {"story": "彼は"こんにちは"と言った。", "english": "He said "hi"."}"#).unwrap(),
+            r#"{"story": "彼は\"こんにちは\"と言った。", "english": "He said \"hi\"."}"#
+        );
+        assert_eq!(repair_json("{\"a\": \"line1\nline2\"}").unwrap(), r#"{"a": "line1\nline2"}"#);
+        assert_eq!(repair_json(r#"{"a": "cut off"#).unwrap(), r#"{"a": "cut off"}"#);
+        assert_eq!(repair_json(r#"{"a": {"b": 1,"#).unwrap(), r#"{"a": {"b": 1}}"#);
+        assert_eq!(repair_json("no json here"), None);
+
+        let story: Story = parse_llm_output("```json\n{\"story\": \"青い空。\", \"english\": \"A \"blue\" sky.\",}\n```").unwrap();
+        assert_eq!(story.story, "青い空。");
+        assert_eq!(story.english, "A \"blue\" sky.");
+        assert!(parse_llm_output::<Story>(r#"{"story": "青い空。"}"#).unwrap_err().to_string().contains("english"));
+    }
+
+    #[test]
+    fn test_object_schema() {
+        let schema = object_schema(&[("story", string_schema()), ("english", string_schema())]);
+        assert_eq!(schema["required"], serde_json::json!(["story", "english"]));
+        assert_eq!(schema["properties"]["english"]["type"], "string");
+    }
+
+    #[tokio::test]
+    async fn test_generate_json_retries() {
+        let schema = object_schema(&[("story", string_schema()), ("english", string_schema())]);
+        let mock = MockBackend::new(vec![
+            r#"{"story": "青い空。"}"#.to_string(),
+            r#"{"story": "青い空。", "english": "A blue sky."}"#.to_string(),
+        ]);
+
+        let mut retries = Vec::new();
+        let story: Story = generate_json(&mock, "Write a story.", &schema, |progress| {
+            if let JsonProgress::Retry { attempt, attempts, .. } = progress {
+                retries.push((attempt, attempts));
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(story.english, "A blue sky.");
+        assert_eq!(retries, vec![(2, 3)]);
+
+        let prompts = mock.prompts();
+        assert_eq!(prompts[0], "Write a story.");
+        assert!(prompts[1].starts_with("Write a story."));
+        assert!(prompts[1].contains("missing field `english`"));
+
+        // gives up once the retries are used
+        let mock = MockBackend::new(vec!["no json".to_string()]).with_json_retries(1);
+        let error = generate_json::<Story>(&mock, "Write a story.", &schema, |_| {}).await.unwrap_err();
+        assert!(error.to_string().contains("after 2 attempts"));
+        assert_eq!(mock.prompts().len(), 2);
+    }
+}
@@ -8,15 +8,20 @@
 
 use dioxus::prelude::*;
 use dioxus::desktop::{Config, WindowBuilder, LogicalSize};
-use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use std::str::FromStr;
 use std::time::Duration;
 
 use dxgui::Route;
 use dxgui::db::WordRecord;
+use dxgui::kanji::KanjiRecord;
 use dxgui::db::DB_URL;
+use dxgui::migrations::run_migrations;
 use dxgui::footer::{Footer, StatusLevel, StatusMessage};
+use dxgui::llm::{LlmBackend, LlmConfig};
+use dxgui::furigana::FuriganaSettings;
+use dxgui::pitch::PitchSettings;
 
-use ollama_rs::Ollama;
 use std::sync::Arc;
 
 
@@ -65,6 +70,10 @@ fn App() -> Element {
     // Provide the status signal to the context so it can be consumed by Footer
     provide_context(status_signal.clone());
 
+    // this will be used in the kanji drill to hold the retrieved kanji
+    let select_kanji = use_signal(Vec::<KanjiRecord>::new);
+    provide_context(select_kanji);
+
     // This will be used to hold the words to use in the story generation
     let words_to_use = use_signal(|| Vec::<String>::new());
     provide_context(words_to_use.clone());
@@ -74,22 +83,57 @@ fn App() -> Element {
     let global= include_str!("../assets/main.css");
     // let header_svg = include_str!("../assets/header.svg");
 
+    // The LLM backend used by the explainer and the story generator, the default Ollama
+    // setup until the saved settings are loaded, replaced when the settings change.
+    let mut llm_backend = use_signal(|| {
+        LlmConfig::default().build().expect("The default LLM settings are valid.")
+    });
+    provide_context::<Signal<Arc<dyn LlmBackend>>>(llm_backend);
+
+    // when to show furigana, replaced by the saved settings once the db is open
+    let mut furigana_settings = use_signal(FuriganaSettings::default);
+    provide_context(furigana_settings);
+
+    // whether pitch accents are drawn over readings, replaced by the saved setting once the db is open
+    let mut pitch_settings = use_signal(PitchSettings::default);
+    provide_context(pitch_settings);
+
     // initiate db pool for all children component
     let db_pool = use_resource(move || async move {
         eprintln!("use_resource for db pool called");
-        SqlitePoolOptions::new()
+        let options = SqliteConnectOptions::from_str(DB_URL)?.create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
             .max_connections(5)
             // Proactively close connections that have been idle for 10 minutes.
             // This is safer than letting them die from a server-side timeout.
             .idle_timeout(Duration::from_secs(600)) 
             // Optionally, force connections to be recycled every 30 minutes.
             .max_lifetime(Duration::from_secs(1800))
-            .connect(DB_URL)
-            .await
-    });
+            .connect_with(options)
+            .await?;
 
-    // Provide the Ollama client to the context
-    provide_context(Arc::new(Ollama::default()));
+        // Upgrade the schema in place before any component touches the pool.
+        let applied = run_migrations(&pool).await?;
+        if !applied.is_empty() {
+            eprintln!("Applied database migrations: {:?}", applied);
+        }
+
+        // switch to the LLM backend saved on the setting page
+        match LlmConfig::load(&pool).await.map(|config| config.build()) {
+            Ok(Ok(backend)) => llm_backend.set(backend),
+            Ok(Err(e)) => eprintln!("Invalid LLM settings, using the default: {}", e),
+            Err(e) => eprintln!("Error loading the LLM settings: {}", e),
+        }
+        match FuriganaSettings::load(&pool).await {
+            Ok(settings) => furigana_settings.set(settings),
+            Err(e) => eprintln!("Error loading the furigana settings: {}", e),
+        }
+        match PitchSettings::load(&pool).await {
+            Ok(settings) => pitch_settings.set(settings),
+            Err(e) => eprintln!("Error loading the pitch accent settings: {}", e),
+        }
+        Ok::<_, sqlx::Error>(pool)
+    });
 
     rsx! {
         head {
@@ -0,0 +1,421 @@
+use sqlx::Row;
+use crate::review_log::now_timestamp;
+
+/// One step of a schema migration.
+enum Step {
+    /// Plain SQL statement, should be safe to run on a database that already has the object
+    /// (e.g. `CREATE TABLE IF NOT EXISTS`).
+    Sql(&'static str),
+    /// `ALTER TABLE ... ADD COLUMN`, skipped when the column already exists.
+    /// Databases built before the schema_version table existed may already have some columns.
+    AddColumn {
+        table: &'static str,
+        column: &'static str,
+        definition: &'static str,
+    },
+}
+
+/// A numbered schema version, applied once and recorded in `schema_version`.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    steps: &'static [Step],
+}
+
+/// All migrations in order. Never edit a released migration, add a new one instead.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create words table",
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS words (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                expression TEXT NOT NULL,
+                reading TEXT NOT NULL,
+                meaning TEXT NOT NULL,
+                jlpt TEXT NOT NULL,
+
+                -- User Progress Fields --
+                practice_time INTEGER NOT NULL DEFAULT 0,
+                familiar      BOOLEAN NOT NULL DEFAULT 0,
+                user_mark     BOOLEAN NOT NULL DEFAULT 0
+            )
+            "#,
+        )],
+    },
+    Migration {
+        version: 2,
+        description: "add spaced repetition columns",
+        steps: &[
+            Step::AddColumn { table: "words", column: "ease", definition: "REAL NOT NULL DEFAULT 2.5" },
+            Step::AddColumn { table: "words", column: "interval_days", definition: "INTEGER NOT NULL DEFAULT 0" },
+            Step::AddColumn { table: "words", column: "due_day", definition: "INTEGER NOT NULL DEFAULT 0" },
+            Step::AddColumn { table: "words", column: "lapses", definition: "INTEGER NOT NULL DEFAULT 0" },
+        ],
+    },
+    Migration {
+        version: 3,
+        description: "create reviews table",
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS reviews (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    word_id     INTEGER NOT NULL REFERENCES words(id) ON DELETE CASCADE,
+                    reviewed_at INTEGER NOT NULL,
+                    mode        TEXT NOT NULL,
+                    j_to_e      BOOLEAN NOT NULL,
+                    grade       INTEGER NOT NULL,
+                    response_ms INTEGER NOT NULL DEFAULT 0
+                )
+                "#,
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_reviews_word ON reviews(word_id)"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_reviews_time ON reviews(reviewed_at)"),
+        ],
+    },
+    Migration {
+        version: 4,
+        description: "create tags and word_tags tables",
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS tags (
+                    id   INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL UNIQUE
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS word_tags (
+                    word_id INTEGER NOT NULL REFERENCES words(id) ON DELETE CASCADE,
+                    tag_id  INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                    PRIMARY KEY (word_id, tag_id)
+                )
+                "#,
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_word_tags_tag ON word_tags(tag_id)"),
+        ],
+    },
+    Migration {
+        version: 5,
+        description: "add soft delete column and word key index",
+        steps: &[
+            Step::AddColumn { table: "words", column: "deleted", definition: "BOOLEAN NOT NULL DEFAULT 0" },
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_words_key ON words(expression, reading)"),
+        ],
+    },
+    Migration {
+        version: 6,
+        description: "create JMdict dictionary tables",
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS word_dictionary (
+                    word_id    INTEGER PRIMARY KEY REFERENCES words(id) ON DELETE CASCADE,
+                    jmdict_seq INTEGER NOT NULL,
+                    common     BOOLEAN NOT NULL DEFAULT 0
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS word_senses (
+                    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                    word_id     INTEGER NOT NULL REFERENCES words(id) ON DELETE CASCADE,
+                    sense_index INTEGER NOT NULL,
+                    pos         TEXT NOT NULL DEFAULT '', -- space separated JMdict codes, e.g. "v5r vi"
+                    misc        TEXT NOT NULL DEFAULT '',
+                    glosses     TEXT NOT NULL             -- JSON array of strings
+                )
+                "#,
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_word_senses_word ON word_senses(word_id)"),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS word_forms (
+                    word_id INTEGER NOT NULL REFERENCES words(id) ON DELETE CASCADE,
+                    form    TEXT NOT NULL,
+                    kana    BOOLEAN NOT NULL,
+                    common  BOOLEAN NOT NULL DEFAULT 0,
+                    PRIMARY KEY (word_id, kana, form)
+                )
+                "#,
+            ),
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS dictionary_codes (
+                    code        TEXT PRIMARY KEY,
+                    description TEXT NOT NULL
+                )
+                "#,
+            ),
+        ],
+    },
+    Migration {
+        version: 7,
+        description: "create kanji table",
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS kanji (
+                literal      TEXT PRIMARY KEY,
+                on_readings  TEXT NOT NULL DEFAULT '',   -- space separated, katakana
+                kun_readings TEXT NOT NULL DEFAULT '',   -- space separated, okurigana after a dot
+                meanings     TEXT NOT NULL DEFAULT '[]', -- JSON array of strings
+                stroke_count INTEGER NOT NULL DEFAULT 0,
+                grade        INTEGER,
+                jlpt         INTEGER,                    -- pre-2010 4 level JLPT from KANJIDIC2
+                frequency    INTEGER,
+
+                -- User Progress Fields --
+                practice_time INTEGER NOT NULL DEFAULT 0,
+                familiar      BOOLEAN NOT NULL DEFAULT 0
+            )
+            "#,
+        )],
+    },
+    Migration {
+        version: 8,
+        description: "create settings table",
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS settings (
+                key   TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )
+            "#,
+        )],
+    },
+    Migration {
+        version: 9,
+        description: "create word_explanations table",
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS word_explanations (
+                    word           TEXT PRIMARY KEY,
+                    explain        TEXT NOT NULL,
+                    examples       TEXT NOT NULL DEFAULT '[]', -- JSON array of {sentence, translation}
+                    model          TEXT NOT NULL,
+                    prompt_version INTEGER NOT NULL,
+                    created_at     INTEGER NOT NULL            -- unix timestamp in seconds
+                )
+                "#,
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_word_explanations_model ON word_explanations(model)"),
+        ],
+    },
+    Migration {
+        version: 10,
+        description: "create stories table",
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS stories (
+                id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                story      TEXT NOT NULL,
+                english    TEXT NOT NULL,
+                words      TEXT NOT NULL DEFAULT '[]', -- JSON array of the words the story was asked to use
+                jlpt       TEXT NOT NULL DEFAULT '',
+                model      TEXT NOT NULL DEFAULT '',
+                created_at INTEGER NOT NULL            -- unix timestamp in seconds
+            )
+            "#,
+        )],
+    },
+    Migration {
+        version: 11,
+        description: "create quiz_results table",
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS quiz_results (
+                    id       INTEGER PRIMARY KEY AUTOINCREMENT,
+                    story_id INTEGER NOT NULL REFERENCES stories(id) ON DELETE CASCADE,
+                    score    INTEGER NOT NULL,
+                    total    INTEGER NOT NULL,
+                    taken_at INTEGER NOT NULL  -- unix timestamp in seconds
+                )
+                "#,
+            ),
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_quiz_results_story ON quiz_results(story_id)"),
+        ],
+    },
+    Migration {
+        version: 12,
+        description: "create pitch_accents table",
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS pitch_accents (
+                expression TEXT NOT NULL,
+                reading    TEXT NOT NULL,
+                accents    TEXT NOT NULL, -- comma separated downstep positions, 0 for heiban
+                PRIMARY KEY (expression, reading)
+            )
+            "#,
+        )],
+    },
+    Migration {
+        version: 13,
+        description: "create example_sentences table",
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS example_sentences (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                sentence    TEXT NOT NULL UNIQUE,
+                translation TEXT NOT NULL DEFAULT ''
+            )
+            "#,
+        )],
+    },
+];
+
+/// The schema version a fully migrated database has.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Returns the schema version of the database, 0 if it was never migrated.
+pub async fn current_version(pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
+    create_version_table(pool).await?;
+    let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+    Ok(version.unwrap_or(0))
+}
+
+async fn create_version_table(pool: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version     INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at  INTEGER NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Upgrades the database in place by applying every migration newer than its current version.
+/// Each migration runs in its own transaction, existing rows and user progress are kept.
+/// Returns the versions that were applied, empty if the database was already up to date.
+pub async fn run_migrations(pool: &sqlx::SqlitePool) -> Result<Vec<i64>, sqlx::Error> {
+    let current = current_version(pool).await?;
+    let mut applied = Vec::new();
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let mut transaction = pool.begin().await?;
+
+        for step in migration.steps {
+            match step {
+                Step::Sql(sql) => {
+                    sqlx::query(sql).execute(&mut *transaction).await?;
+                }
+                Step::AddColumn { table, column, definition } => {
+                    let exists: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+                        .bind(*table)
+                        .bind(*column)
+                        .fetch_one(&mut *transaction)
+                        .await?;
+                    if exists == 0 {
+                        let sql = format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition);
+                        sqlx::query(&sql).execute(&mut *transaction).await?;
+                    }
+                }
+            }
+        }
+
+        sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.description)
+            .bind(now_timestamp())
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+        eprintln!("Applied migration {}: {}", migration.version, migration.description);
+        applied.push(migration.version);
+    }
+
+    Ok(applied)
+}
+
+/// Lists the applied migrations as (version, description), oldest first.
+pub async fn applied_migrations(pool: &sqlx::SqlitePool) -> Result<Vec<(i64, String)>, sqlx::Error> {
+    create_version_table(pool).await?;
+    let rows = sqlx::query("SELECT version, description FROM schema_version ORDER BY version")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(|row| (row.get("version"), row.get("description"))).collect())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup_empty_db() -> sqlx::SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("Failed to create in-memory db pool.")
+    }
+
+    #[tokio::test]
+    async fn test_fresh_database_migrates_to_latest() {
+        let pool = setup_empty_db().await;
+
+        let applied = run_migrations(&pool).await.expect("Failed to run migrations.");
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        assert_eq!(current_version(&pool).await.unwrap(), latest_version());
+
+        // running again is a no-op
+        let applied = run_migrations(&pool).await.expect("Failed to run migrations.");
+        assert!(applied.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_database_keeps_progress() {
+        let pool = setup_empty_db().await;
+
+        // a database built by the old build_db, before schema_version existed
+        sqlx::query(
+            r#"
+            CREATE TABLE words (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                expression TEXT NOT NULL,
+                reading TEXT NOT NULL,
+                meaning TEXT NOT NULL,
+                jlpt TEXT NOT NULL,
+                practice_time INTEGER NOT NULL DEFAULT 0,
+                familiar      BOOLEAN NOT NULL DEFAULT 0,
+                user_mark     BOOLEAN NOT NULL DEFAULT 0,
+                ease          REAL NOT NULL DEFAULT 2.5
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO words (expression, reading, meaning, jlpt, practice_time, familiar, user_mark) VALUES ('青', 'あお', 'blue', 'n5', 3, 1, 1)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        run_migrations(&pool).await.expect("Failed to upgrade legacy database.");
+
+        let word = find_word_by_ids(&pool, vec![1]).await.unwrap();
+        assert_eq!(word[0].practice_time, 3);
+        assert!(word[0].familiar);
+        assert!(word[0].user_mark);
+        assert_eq!(word[0].lapses, 0);
+        assert_eq!(applied_migrations(&pool).await.unwrap().len(), MIGRATIONS.len());
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::BufRead;
+
+use dioxus::prelude::*;
+use sqlx::Row;
+
+use crate::db::WordRecord;
+use crate::settings::{get_setting, set_setting};
+
+/// The pitch accent of one word as stored in the `pitch_accents` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PitchEntry {
+    pub expression: String,
+    pub reading: String,
+    /// Mora after which the pitch drops, 0 for heiban (no drop). Most used first.
+    pub accents: Vec<u8>,
+}
+
+/// Parses one line of a Kanjium style accents file: `expression<TAB>reading<TAB>accents`.
+/// The reading is empty for words written in kana, accents are comma separated and
+/// may carry a part of speech, e.g. "(名)0,(副)1". None for lines without a usable accent.
+pub fn parse_accent_line(line: &str) -> Option<PitchEntry> {
+    let mut fields = line.trim_end_matches(['\r', '\n']).split('\t');
+    let expression = fields.next()?.trim();
+    let reading = fields.next()?.trim();
+    let accents: Vec<u8> = fields
+        .next()?
+        .split(',')
+        .filter_map(|accent| {
+            let accent = match accent.rfind(')') {
+                Some(end) => &accent[end + 1..],
+                None => accent,
+            };
+            accent.trim().parse().ok()
+        })
+        .collect();
+    if expression.is_empty() || accents.is_empty() {
+        return None;
+    }
+    Some(PitchEntry {
+        expression: expression.to_string(),
+        reading: if reading.is_empty() { expression.to_string() } else { reading.to_string() },
+        accents,
+    })
+}
+
+/// Imports or replaces the accents of a Kanjium style accents file and returns the number of words.
+/// Lines that can't be read, like comments, are skipped.
+pub async fn import_pitch_accents<R: BufRead>(pool: &sqlx::SqlitePool, source: R) -> Result<usize, Box<dyn Error>> {
+    let mut transaction = pool.begin().await?;
+    let mut count = 0;
+    for line in source.lines() {
+        let Some(entry) = parse_accent_line(&line?) else {
+            continue;
+        };
+        sqlx::query(
+            r#"
+            INSERT INTO pitch_accents (expression, reading, accents) VALUES (?, ?, ?)
+            ON CONFLICT(expression, reading) DO UPDATE SET accents = excluded.accents
+            "#,
+        )
+        .bind(&entry.expression)
+        .bind(&entry.reading)
+        .bind(join_accents(&entry.accents))
+        .execute(&mut *transaction)
+        .await?;
+        count += 1;
+    }
+    transaction.commit().await?;
+    Ok(count)
+}
+
+fn join_accents(accents: &[u8]) -> String {
+    accents.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(",")
+}
+
+fn split_accents(accents: &str) -> Vec<u8> {
+    accents.split(',').filter_map(|a| a.parse().ok()).collect()
+}
+
+/// The accents of a word, empty when the dataset has none. Words written in kana
+/// may have an empty reading in the words table, their expression is the reading.
+pub async fn pitch_accents(pool: &sqlx::SqlitePool, expression: &str, reading: &str) -> Result<Vec<u8>, sqlx::Error> {
+    let reading = if reading.is_empty() { expression } else { reading };
+    let accents: Option<String> = sqlx::query_scalar("SELECT accents FROM pitch_accents WHERE expression = ? AND reading = ?")
+        .bind(expression)
+        .bind(reading)
+        .fetch_optional(pool)
+        .await?;
+    Ok(accents.map(|a| split_accents(&a)).unwrap_or_default())
+}
+
+/// Returns the accents of the given words by word id, words without any are left out.
+pub async fn pitch_accent_map(pool: &sqlx::SqlitePool, words: &[WordRecord]) -> Result<HashMap<i64, Vec<u8>>, sqlx::Error> {
+    if words.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let mut builder = sqlx::QueryBuilder::new(
+        "SELECT w.id, p.accents FROM words w JOIN pitch_accents p \
+         ON p.expression = w.expression \
+         AND p.reading = CASE WHEN w.reading = '' THEN w.expression ELSE w.reading END \
+         WHERE w.id IN (",
+    );
+    let mut separated = builder.separated(", ");
+    for word in words {
+        separated.push_bind(word.id);
+    }
+    builder.push(")");
+    Ok(builder
+        .build()
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| (row.get("id"), split_accents(&row.get::<String, _>("accents"))))
+        .collect())
+}
+
+/// Splits a kana reading into morae, small ya/yu/yo and vowels join the kana before them.
+pub fn split_morae(reading: &str) -> Vec<String> {
+    let mut morae: Vec<String> = Vec::new();
+    for c in reading.chars() {
+        let joins = matches!(c, 'ゃ' | 'ゅ' | 'ょ' | 'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' | 'ゎ'
+            | 'ャ' | 'ュ' | 'ョ' | 'ァ' | 'ィ' | 'ゥ' | 'ェ' | 'ォ' | 'ヮ');
+        match morae.last_mut() {
+            Some(mora) if joins => mora.push(c),
+            _ => morae.push(c.to_string()),
+        }
+    }
+    morae
+}
+
+/// Whether each of `morae` morae is high with the downstep after mora `accent`.
+/// The first mora is low unless the accent is on it, heiban (0) stays high to the end.
+pub fn pitch_pattern(morae: usize, accent: u8) -> Vec<bool> {
+    let accent = accent as usize;
+    (0..morae)
+        .map(|i| match accent {
+            0 => i > 0,
+            1 => i == 0,
+            _ => i > 0 && i < accent,
+        })
+        .collect()
+}
+
+/// Whether pitch accents are shown, stored under the `pitch.*` keys of the settings table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PitchSettings {
+    pub show: bool,
+}
+
+impl Default for PitchSettings {
+    fn default() -> Self {
+        PitchSettings { show: true }
+    }
+}
+
+impl PitchSettings {
+    /// Loads the saved settings, anything that was never saved keeps its default.
+    pub async fn load(pool: &sqlx::SqlitePool) -> Result<Self, sqlx::Error> {
+        let mut settings = PitchSettings::default();
+        if let Some(show) = get_setting(pool, "pitch.show").await? {
+            settings.show = show == "true";
+        }
+        Ok(settings)
+    }
+
+    pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
+        set_setting(pool, "pitch.show", &self.show.to_string()).await
+    }
+}
+
+/// The reading with a line over its high morae and a downstep mark, one pattern per accent.
+/// Just the reading when there is no accent data or pitch accents are hidden.
+#[component]
+pub fn PitchReading(reading: String, accents: Vec<u8>) -> Element {
+    let settings = use_context::<Signal<PitchSettings>>();
+    if !settings.read().show || accents.is_empty() {
+        return rsx! { "{reading}" };
+    }
+    let morae = split_morae(&reading);
+
+    rsx! {
+        for (n, accent) in accents.iter().copied().enumerate() {
+            if n > 0 {
+                span { class: "text-secondary mx-1", "・" }
+            }
+            span { class: "pitch-accent", title: "Accent {accent}",
+                for (i, (mora, high)) in morae.iter().zip(pitch_pattern(morae.len(), accent)).enumerate() {
+                    span {
+                        class: if high { "pitch-high" } else { "pitch-low" },
+                        class: if accent as usize == i + 1 { "pitch-drop" },
+                        "{mora}"
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+
+    #[test]
+    fn test_parse_and_pattern() {
+        assert_eq!(
+            parse_accent_line("食べる\tたべる\t2\n"),
+            Some(PitchEntry { expression: "食べる".to_string(), reading: "たべる".to_string(), accents: vec![2] })
+        );
+        assert_eq!(parse_accent_line("あいにく\t\t(名)0,(副)1").unwrap().accents, vec![0, 1]);
+        assert_eq!(parse_accent_line("あいにく\t\t(名)0").unwrap().reading, "あいにく");
+        assert_eq!(parse_accent_line("あいにく\t\t"), None);
+        assert_eq!(parse_accent_line("# comment"), None);
+
+        assert_eq!(split_morae("きょうしゃ"), vec!["きょ", "う", "しゃ"]);
+        assert_eq!(split_morae("ティーカップ"), vec!["ティ", "ー", "カ", "ッ", "プ"]);
+
+        assert_eq!(pitch_pattern(3, 0), vec![false, true, true]);
+        assert_eq!(pitch_pattern(3, 1), vec![true, false, false]);
+        assert_eq!(pitch_pattern(3, 2), vec![false, true, false]);
+        assert_eq!(pitch_pattern(3, 3), vec![false, true, true]);
+    }
+
+    #[tokio::test]
+    async fn test_import_and_lookup() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            test_word("箸", "はし", "", ""),
+            test_word("橋", "はし", "", ""),
+            test_word("もう", "", "", ""),
+        ]).await.unwrap();
+
+        let source = "箸\tはし\t1\n橋\tはし\t2\nもう\t\t0,1\nbroken line\n";
+        assert_eq!(import_pitch_accents(&pool, source.as_bytes()).await.unwrap(), 3);
+        // importing again replaces the accents
+        import_pitch_accents(&pool, "箸\tはし\t1,0\n".as_bytes()).await.unwrap();
+
+        assert_eq!(pitch_accents(&pool, "箸", "はし").await.unwrap(), vec![1, 0]);
+        assert_eq!(pitch_accents(&pool, "もう", "").await.unwrap(), vec![0, 1]);
+        assert!(pitch_accents(&pool, "端", "はし").await.unwrap().is_empty());
+
+        let words = find_word_by_ids(&pool, vec![1, 2, 3]).await.unwrap();
+        let map = pitch_accent_map(&pool, &words).await.unwrap();
+        assert_eq!(map.len(), 3);
+        let bridge = words.iter().find(|w| w.expression == "橋").unwrap();
+        assert_eq!(map[&bridge.id], vec![2]);
+
+        assert_eq!(PitchSettings::load(&pool).await.unwrap(), PitchSettings::default());
+        PitchSettings { show: false }.save(&pool).await.unwrap();
+        assert!(!PitchSettings::load(&pool).await.unwrap().show);
+    }
+}
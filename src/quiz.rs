@@ -0,0 +1,552 @@
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Instant;
+
+use dioxus::prelude::*;
+use serde::Deserialize;
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+
+use crate::db::{find_word_ids, WordField};
+use crate::footer::{StatusMessage, StatusLevel};
+use crate::llm::LlmBackend;
+use crate::llm_json::{generate_json, object_schema, string_schema, JsonProgress};
+use crate::review_log::{log_review, now_timestamp, ReviewEntry, ReviewMode};
+use crate::scheduler::Grade;
+use crate::stories::{find_story, StoryRecord};
+use crate::Route;
+
+/// Number of questions asked about a story.
+pub const QUIZ_QUESTIONS: usize = 4;
+
+/// One multiple-choice question about a story.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "RawQuestion")]
+pub struct QuizQuestion {
+    pub question: String,
+    /// Always four different choices.
+    pub choices: Vec<String>,
+    /// Index of the right choice in `choices`.
+    pub answer: usize,
+    /// The story word the question is about, empty when it is not about one word.
+    pub word: String,
+}
+
+/// A question as written by the LLM, `answer` counts from 1.
+#[derive(Deserialize)]
+struct RawQuestion {
+    question: String,
+    choices: Vec<String>,
+    answer: usize,
+    #[serde(default)]
+    word: String,
+}
+
+impl TryFrom<RawQuestion> for QuizQuestion {
+    type Error = String;
+
+    // the messages are sent back to the model when the JSON is asked for again
+    fn try_from(raw: RawQuestion) -> Result<Self, Self::Error> {
+        if raw.question.trim().is_empty() {
+            return Err("a question is empty".to_string());
+        }
+        if raw.choices.len() != 4 {
+            return Err(format!("each question needs exactly 4 choices, got {}", raw.choices.len()));
+        }
+        if raw.choices.iter().any(|choice| choice.trim().is_empty()) {
+            return Err("a choice is empty".to_string());
+        }
+        let mut distinct = raw.choices.clone();
+        distinct.sort();
+        distinct.dedup();
+        if distinct.len() != 4 {
+            return Err("the 4 choices of a question must be different".to_string());
+        }
+        if !(1..=4).contains(&raw.answer) {
+            return Err(format!("answer must be the number of the right choice, 1 to 4, got {}", raw.answer));
+        }
+        Ok(QuizQuestion {
+            question: raw.question.trim().to_string(),
+            choices: raw.choices.iter().map(|choice| choice.trim().to_string()).collect(),
+            answer: raw.answer - 1,
+            word: raw.word.trim().to_string(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct QuizResponse {
+    questions: Vec<QuizQuestion>,
+}
+
+/// JSON schema of `QuizResponse`, sent to backends with a structured output mode.
+fn quiz_schema() -> serde_json::Value {
+    let question = object_schema(&[
+        ("question", string_schema()),
+        ("choices", serde_json::json!({ "type": "array", "items": string_schema(), "minItems": 4, "maxItems": 4 })),
+        ("answer", serde_json::json!({ "type": "integer", "minimum": 1, "maximum": 4 })),
+        ("word", string_schema()),
+    ]);
+    object_schema(&[("questions", serde_json::json!({ "type": "array", "items": question }))])
+}
+
+/// Asks the LLM for QUIZ_QUESTIONS comprehension questions about `story`.
+/// The response is streamed, `on_progress` gets the text generated so far after every chunk
+/// and is told when an unusable answer is asked for again.
+pub async fn generate_quiz(
+    llm: Arc<dyn LlmBackend>,
+    story: &StoryRecord,
+    on_progress: impl FnMut(JsonProgress),
+) -> Result<Vec<QuizQuestion>, Box<dyn Error>> {
+    let word_list = story.words.join("、 ");
+    let prompt = format!(
+        r#"You are a Japanese teacher writing a reading comprehension quiz for a learner.
+
+        Story:
+        {story}
+
+        Follow these instructions precisely:
+        1. Write {count} multiple-choice questions in simple Japanese that check the learner understood the story.
+        2. Each question has exactly 4 different choices in Japanese, and only one of them is right according to the story.
+        3. "answer" is the number (1 to 4) of the right choice. Vary the position of the right choice.
+        4. When a question is about one of these words, put the word in "word": {word_list}. Otherwise leave "word" empty.
+        5. Structure your entire response as a single, valid JSON object following the template below. Do not add any text before or after the JSON object.
+
+        JSON Template:
+        {{
+          "questions": [
+            {{"question": "...", "choices": ["...", "...", "...", "..."], "answer": 1, "word": "..."}}
+          ]
+        }}"#,
+        story = story.story,
+        count = QUIZ_QUESTIONS,
+    );
+
+    let response: QuizResponse = generate_json(&*llm, &prompt, &quiz_schema(), on_progress).await?;
+    if response.questions.is_empty() {
+        return Err("The model wrote no questions.".into());
+    }
+    Ok(response.questions)
+}
+
+/// A finished quiz, as stored in the `quiz_results` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuizResult {
+    pub id: i64,
+    pub story_id: i64,
+    pub score: i64,
+    pub total: i64,
+    pub taken_at: i64, // unix timestamp in seconds
+}
+
+impl QuizResult {
+    fn from_row(row: &SqliteRow) -> Self {
+        QuizResult {
+            id: row.get("id"),
+            story_id: row.get("story_id"),
+            score: row.get("score"),
+            total: row.get("total"),
+            taken_at: row.get("taken_at"),
+        }
+    }
+}
+
+pub async fn save_quiz_result(pool: &sqlx::SqlitePool, story_id: i64, score: i64, total: i64) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query("INSERT INTO quiz_results (story_id, score, total, taken_at) VALUES (?, ?, ?, ?)")
+        .bind(story_id)
+        .bind(score)
+        .bind(total)
+        .bind(now_timestamp())
+        .execute(pool)
+        .await?;
+    Ok(result.last_insert_rowid())
+}
+
+/// Lists the results of the quizzes taken on a story, newest first.
+pub async fn quiz_results_for_story(pool: &sqlx::SqlitePool, story_id: i64) -> Result<Vec<QuizResult>, sqlx::Error> {
+    let rows = sqlx::query("SELECT * FROM quiz_results WHERE story_id = ? ORDER BY taken_at DESC, id DESC")
+        .bind(story_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(QuizResult::from_row).collect())
+}
+
+/// Logs the answer to a question about `word` in the review history, as Good when it was right
+/// and Again when it was wrong. The schedule of the word is left alone.
+/// Returns false when the word is not in the words table.
+pub async fn log_quiz_answer(pool: &sqlx::SqlitePool, word: &str, correct: bool, response_ms: i64) -> Result<bool, sqlx::Error> {
+    let Some(word_id) = find_word_ids(pool, WordField::Expression, word).await?.first().copied() else {
+        return Ok(false);
+    };
+    let grade = if correct { Grade::Good } else { Grade::Again };
+    log_review(pool, &ReviewEntry::new(word_id, ReviewMode::Quiz, true, grade, response_ms)).await?;
+    Ok(true)
+}
+
+
+/// Comprehension quiz on a saved story, answered with the 1-4 keys like TestCard.
+#[component]
+pub fn StoryQuiz(story_id: i64) -> Element {
+    let navigator = use_navigator();
+    let llm = use_context::<Signal<Arc<dyn LlmBackend>>>();
+    let mut status_message = use_context::<Signal<StatusMessage>>();
+    let db_pool = use_context::<sqlx::SqlitePool>();
+    let pool_generate = db_pool.clone();
+    let pool_pick = db_pool.clone();
+    let pool_next = db_pool.clone();
+
+    let mut story = use_signal(|| None::<StoryRecord>);
+    let mut questions = use_signal(Vec::<QuizQuestion>::new);
+    let mut index = use_signal(|| 0_usize);
+    let mut selected_answer = use_signal(|| None as Option<usize>);
+    let mut score = use_signal(|| 0_i64);
+    let mut finished = use_signal(|| false);
+    let mut question_shown_at = use_signal(Instant::now); // used to measure the response time
+
+    // the raw text streamed so far, and the request in flight
+    let mut raw_output = use_signal(String::new);
+    let mut current_task = use_signal(|| None::<Task>);
+
+    let mut results = use_resource(move || {
+        let pool = db_pool.clone();
+        async move { quiz_results_for_story(&pool, story_id).await }
+    });
+
+    let mut restart_quiz = move || {
+        index.set(0);
+        selected_answer.set(None);
+        score.set(0);
+        finished.set(false);
+        question_shown_at.set(Instant::now());
+    };
+
+    // loads the story and asks the LLM for new questions
+    let generate = use_callback(move |()| {
+        if let Some(task) = current_task.take() {
+            task.cancel();
+        }
+        let llm = llm.peek().clone();
+        let pool = pool_generate.clone();
+        questions.set(Vec::new());
+        raw_output.set(String::new());
+        restart_quiz();
+
+        let task = spawn(async move {
+            let record = match find_story(&pool, story_id).await {
+                Ok(Some(record)) => record,
+                Ok(None) => {
+                    status_message.set(StatusMessage {
+                        message: "This story was deleted.".to_string(),
+                        level: StatusLevel::Error,
+                    });
+                    current_task.set(None);
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("Error loading story {}: {}", story_id, e);
+                    status_message.set(StatusMessage {
+                        message: "A database error occurred.".to_string(),
+                        level: StatusLevel::Error,
+                    });
+                    current_task.set(None);
+                    return;
+                }
+            };
+            story.set(Some(record.clone()));
+            status_message.set(StatusMessage {
+                message: format!("Asking {} for quiz questions...", llm.describe()),
+                level: StatusLevel::Info,
+            });
+
+            let result = generate_quiz(llm, &record, |progress| match progress {
+                JsonProgress::Partial(partial) => raw_output.set(partial.to_string()),
+                JsonProgress::Retry { attempt, attempts, error } => {
+                    raw_output.set(String::new());
+                    status_message.set(StatusMessage {
+                        message: format!("Unusable questions ({}), asking again ({}/{})...", error, attempt, attempts),
+                        level: StatusLevel::Warning,
+                    });
+                }
+            })
+            .await;
+
+            match result {
+                Ok(generated) => {
+                    status_message.set(StatusMessage {
+                        message: "Press 1-4 to answer, Enter for the next question.".to_string(),
+                        level: StatusLevel::Info,
+                    });
+                    questions.set(generated);
+                    question_shown_at.set(Instant::now());
+                }
+                Err(e) => {
+                    eprintln!("Error generating quiz: {}", e);
+                    status_message.set(StatusMessage {
+                        message: format!("Error generating quiz: {}", e),
+                        level: StatusLevel::Error,
+                    });
+                }
+            }
+            current_task.set(None);
+        });
+        current_task.set(Some(task));
+    });
+
+    use_effect(move || generate.call(()));
+
+    let pick_answer = use_callback(move |choice: usize| {
+        if selected_answer().is_some() || finished() {
+            return;
+        }
+        let Some(question) = questions.read().get(index()).cloned() else {
+            return;
+        };
+        selected_answer.set(Some(choice));
+        let correct = choice == question.answer;
+        if correct {
+            score += 1;
+        }
+        if !question.word.is_empty() {
+            let pool = pool_pick.clone();
+            let response_ms = question_shown_at().elapsed().as_millis() as i64;
+            spawn(async move {
+                match log_quiz_answer(&pool, &question.word, correct, response_ms).await {
+                    Ok(true) => eprintln!("Logged quiz answer for '{}'", question.word),
+                    Ok(false) => eprintln!("'{}' is not in the words table, answer not logged", question.word),
+                    Err(e) => eprintln!("Failed to log quiz answer: {}", e),
+                }
+            });
+        }
+    });
+
+    let next_question = use_callback(move |()| {
+        if selected_answer().is_none() || finished() {
+            return;
+        }
+        if index() + 1 < questions.len() {
+            index += 1;
+            selected_answer.set(None);
+            question_shown_at.set(Instant::now());
+            return;
+        }
+
+        finished.set(true);
+        let total = questions.len() as i64;
+        let pool = pool_next.clone();
+        spawn(async move {
+            if let Err(e) = save_quiz_result(&pool, story_id, score(), total).await {
+                eprintln!("Error saving quiz result: {}", e);
+            }
+            results.restart();
+        });
+        status_message.set(StatusMessage {
+            message: format!("Quiz finished! Your score: {}/{}", score(), total),
+            level: StatusLevel::Success,
+        });
+    });
+
+    // the same colors as TestCard once an answer is picked
+    let get_card_class = move |i: usize| {
+        let right = questions.read().get(index()).map(|question| question.answer);
+        match selected_answer() {
+            Some(_) if Some(i) == right => "card mb-2 bg-success text-light",
+            Some(selected) if i == selected => "card mb-2 bg-danger text-light",
+            _ => "card clickable mb-2 bg-dark text-light",
+        }
+    };
+
+    let total_questions = questions.len();
+
+    rsx! {
+        div {
+            class: "container h-75 d-flex flex-column",
+            tabindex: "0",
+            onmounted: move |evt| {
+                // Focus the div when mounted to capture key events
+                let element = evt.data();
+                spawn(async move {
+                    let _ = element.set_focus(true).await;
+                });
+            },
+            onkeydown: move |event: KeyboardEvent| {
+                match event.key() {
+                    Key::Character(s) if s == "1" => pick_answer.call(0),
+                    Key::Character(s) if s == "2" => pick_answer.call(1),
+                    Key::Character(s) if s == "3" => pick_answer.call(2),
+                    Key::Character(s) if s == "4" => pick_answer.call(3),
+                    Key::Enter => next_question.call(()),
+                    Key::Character(s) if s.eq_ignore_ascii_case("n") => next_question.call(()),
+                    _ => {}
+                }
+            },
+
+            // --- Top Controls ---
+            div { class: "row my-3",
+                div { class: "col-auto",
+                    button { class: "btn btn-secondary",
+                        onclick: move |_| {
+                            navigator.push(Route::SavedStory { story_id });
+                        },
+                        "Go Back"
+                    }
+                }
+                div { class: "col d-flex align-items-center",
+                    h4 { class: "mb-0", "Comprehension Quiz" }
+                }
+            }
+
+            if let Some(record) = story() {
+                details { class: "mb-3",
+                    summary { class: "text-secondary", "Read the story again" }
+                    p { class: "mt-2", "{record.story}" }
+                }
+            }
+
+            if current_task.read().is_some() {
+                div { class: "p-3 border rounded",
+                    div { class: "spinner-border spinner-border-sm text-info me-2", role: "status" }
+                    button {
+                        class: "btn btn-outline-danger btn-sm",
+                        onclick: move |_| {
+                            if let Some(task) = current_task.take() {
+                                task.cancel();
+                            }
+                            status_message.set(StatusMessage {
+                                message: "Quiz generation cancelled.".to_string(),
+                                level: StatusLevel::Warning,
+                            });
+                        },
+                        "Cancel"
+                    }
+                    pre { class: "small text-secondary text-wrap mb-0 mt-2", "{raw_output}" }
+                }
+            } else if finished() {
+                div { class: "p-4 border rounded text-center",
+                    h3 { "Score: {score} / {total_questions}" }
+                    div { class: "d-flex justify-content-center gap-2 mt-3",
+                        button { class: "btn btn-primary", onclick: move |_| restart_quiz(), "Try again" }
+                        button { class: "btn btn-outline-primary", onclick: move |_| generate.call(()), "New questions" }
+                    }
+                }
+            } else if let Some(question) = questions.read().get(index()).cloned() {
+                // --- Question ---
+                div { class: "row flex-grow-1 d-flex flex-column justify-content-center",
+                    p { class: "lead my-3", "{question.question}" }
+                    div { class: "d-flex flex-column gap-3",
+                        for (i, choice) in question.choices.iter().enumerate() {
+                            div {
+                                class: get_card_class(i),
+                                style: "cursor: pointer;",
+                                onclick: move |_| pick_answer.call(i),
+                                div { class: "card-body d-flex align-items-center",
+                                    span { class: "badge bg-secondary me-3", style: "width: 2rem;", u { "{i + 1}" } }
+                                    span { "{choice}" }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // --- Progress Bar ---
+                div { class: "row my-3 align-items-center",
+                    div { class: "col",
+                        div { class: "progress",
+                            div {
+                                class: "progress-bar",
+                                role: "progressbar",
+                                style: "width: {((index() + 1) as f32 / total_questions as f32) * 100.0}%",
+                            }
+                        }
+                    }
+                    div { class: "col-auto", span { "{index() + 1} / {total_questions}" } }
+                    div { class: "col-auto",
+                        button {
+                            class: "btn btn-primary",
+                            disabled: selected_answer().is_none(),
+                            onclick: move |_| next_question.call(()),
+                            "Next (Enter)"
+                        }
+                    }
+                }
+            } else {
+                div { class: "text-center text-muted p-5 border rounded",
+                    "No questions yet."
+                    button { class: "btn btn-primary ms-2", onclick: move |_| generate.call(()), "Generate questions" }
+                }
+            }
+
+            // --- Past results ---
+            match &*results.read_unchecked() {
+                Some(Ok(list)) if !list.is_empty() => rsx! {
+                    div { class: "mt-3 small text-secondary",
+                        "Past scores: "
+                        for result in list.iter().take(10) {
+                            span { class: "badge bg-secondary me-1", "{result.score}/{result.total}" }
+                        }
+                    }
+                },
+                Some(Err(e)) => rsx! { div { class: "alert alert-danger", "Error loading quiz results: {e}" } },
+                _ => rsx! {},
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+    use crate::llm::MockBackend;
+    use crate::stories::save_story;
+
+    #[tokio::test]
+    async fn test_generate_quiz_validates_questions() {
+        let story = StoryRecord { story: "青い空の下で猫が寝ています。".to_string(), words: vec!["猫".to_string()], ..Default::default() };
+        // the first answer has three choices and is asked for again
+        let mock = Arc::new(MockBackend::new(vec![
+            r#"{"questions": [{"question": "何が寝ていますか。", "choices": ["猫", "犬", "鳥"], "answer": 1, "word": "猫"}]}"#.to_string(),
+            r#"{"questions": [{"question": "何が寝ていますか。", "choices": ["犬", "猫", "鳥", "魚"], "answer": 2, "word": "猫"}, {"question": "空は何色ですか。", "choices": ["赤", "青", "白", "黒"], "answer": 2}]}"#.to_string(),
+        ]));
+
+        let questions = generate_quiz(mock.clone(), &story, |_| {}).await.unwrap();
+        assert_eq!(questions.len(), 2);
+        assert_eq!(questions[0].answer, 1);
+        assert_eq!(questions[0].choices[questions[0].answer], "猫");
+        assert_eq!(questions[1].word, "");
+        assert!(mock.prompts()[0].contains("青い空の下で猫が寝ています。"));
+        assert!(mock.prompts()[1].contains("exactly 4 choices, got 3"));
+
+        let raw = r#"{"question": "?", "choices": ["a", "b", "c", "d"], "answer": 5}"#;
+        assert!(serde_json::from_str::<QuizQuestion>(raw).is_err());
+        let raw = r#"{"question": "?", "choices": ["a", "a", "c", "d"], "answer": 1}"#;
+        assert!(serde_json::from_str::<QuizQuestion>(raw).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_quiz_results() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            test_word("猫", "ねこ", "cat", "n5"),
+        ]).await.unwrap();
+
+        let story_id = save_story(&pool, &StoryRecord { story: "猫が寝ています。".to_string(), ..Default::default() }).await.unwrap();
+        save_quiz_result(&pool, story_id, 2, 4).await.unwrap();
+        let latest = save_quiz_result(&pool, story_id, 4, 4).await.unwrap();
+        let results = quiz_results_for_story(&pool, story_id).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].id, latest);
+        assert_eq!(results[0].score, 4);
+
+        assert!(log_quiz_answer(&pool, "猫", false, 1200).await.unwrap());
+        assert!(!log_quiz_answer(&pool, "未知", true, 1200).await.unwrap());
+        let word_id = find_word_ids(&pool, WordField::Expression, "猫").await.unwrap()[0];
+        let reviews = crate::review_log::reviews_for_word(&pool, word_id).await.unwrap();
+        assert_eq!(reviews.len(), 1);
+        assert_eq!(reviews[0].mode, ReviewMode::Quiz);
+        assert_eq!(reviews[0].grade, Grade::Again);
+
+        crate::stories::delete_story(&pool, story_id).await.unwrap();
+        assert!(quiz_results_for_story(&pool, story_id).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,476 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+use std::time::Instant;
+
+use dioxus::prelude::*;
+use wana_kana::{ConvertJapanese, Options};
+
+use crate::db::*;
+use crate::footer::{StatusMessage, StatusLevel};
+use crate::review_log::{log_review, ReviewEntry, ReviewMode};
+use crate::scheduler::{grade_word, Grade};
+use crate::utils::speak_text;
+use crate::{return_voice, Route};
+use tts::Gender;
+
+/// What the user types on a recall card.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecallAnswer {
+    Reading,
+    Meaning,
+}
+
+// Display and FromStr are used by the router to build and parse the URL.
+impl Display for RecallAnswer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let s = match self {
+            RecallAnswer::Reading => "reading",
+            RecallAnswer::Meaning => "meaning",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseRecallAnswerError;
+
+impl Display for ParseRecallAnswerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "Invalid RecallAnswer in URL segment")
+    }
+}
+
+impl Error for ParseRecallAnswerError {}
+
+impl FromStr for RecallAnswer {
+    type Err = ParseRecallAnswerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reading" => Ok(RecallAnswer::Reading),
+            "meaning" => Ok(RecallAnswer::Meaning),
+            _ => Err(ParseRecallAnswerError),
+        }
+    }
+}
+
+/// How a typed answer was graded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecallResult {
+    Correct,
+    /// Accepted, but spelled a little differently from `expected`.
+    Typo { expected: String },
+    Wrong,
+}
+
+impl RecallResult {
+    /// The grade given to the word, a typo counts as a hard recall.
+    pub fn grade(&self) -> Grade {
+        match self {
+            RecallResult::Correct => Grade::Good,
+            RecallResult::Typo { .. } => Grade::Hard,
+            RecallResult::Wrong => Grade::Again,
+        }
+    }
+}
+
+/// Number of single character edits to turn `a` into `b`.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Typos accepted in an answer, none for short words where one letter makes another word.
+fn allowed_typos(answer: &str) -> usize {
+    match answer.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Lowercases a meaning and drops notes in brackets and a leading "to"/"a"/"an"/"the",
+/// so "To eat (food)" and "eat" compare equal.
+pub fn normalize_meaning(meaning: &str) -> String {
+    let mut text = String::new();
+    let mut depth = 0usize;
+    for c in meaning.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => text.extend(c.to_lowercase()),
+            _ => {}
+        }
+    }
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let words = match words.first() {
+        Some(&"to") | Some(&"a") | Some(&"an") | Some(&"the") if words.len() > 1 => &words[1..],
+        _ => &words[..],
+    };
+    words.join(" ")
+}
+
+/// The typed reading in hiragana, romaji and katakana are converted.
+pub fn kana_answer(input: &str) -> String {
+    input.split_whitespace().collect::<String>().to_hiragana()
+}
+
+/// Converts romaji to hiragana while it is typed, a final "n" waits for the next letter.
+pub fn kana_as_typed(input: &str) -> String {
+    input.to_hiragana_with_opt(Options { imemode: true, ..Default::default() })
+}
+
+/// The reading must match exactly, in any kana or in romaji. A word may list
+/// several readings, kana words without a reading are read as written.
+pub fn grade_reading(input: &str, expression: &str, reading: &str) -> RecallResult {
+    let answer = kana_answer(input);
+    let reading = if reading.trim().is_empty() { expression } else { reading };
+    let matches = reading
+        .split([',', ';', '、', '・'])
+        .map(kana_answer)
+        .any(|reading| !reading.is_empty() && reading == answer);
+    if matches { RecallResult::Correct } else { RecallResult::Wrong }
+}
+
+/// Any of the comma or semicolon separated meanings is accepted, with a typo or two in long ones.
+pub fn grade_meaning(input: &str, meaning: &str) -> RecallResult {
+    let answer = normalize_meaning(input);
+    if answer.is_empty() {
+        return RecallResult::Wrong;
+    }
+    let meanings: Vec<(String, String)> = meaning
+        .split([',', ';'])
+        .map(|m| (m.trim().to_string(), normalize_meaning(m)))
+        .filter(|(_, normalized)| !normalized.is_empty())
+        .collect();
+    if meanings.iter().any(|(_, normalized)| *normalized == answer) {
+        return RecallResult::Correct;
+    }
+    meanings
+        .into_iter()
+        .map(|(original, normalized)| (levenshtein(&answer, &normalized), original, normalized))
+        .filter(|(distance, _, normalized)| *distance <= allowed_typos(normalized))
+        .min_by_key(|(distance, _, _)| *distance)
+        .map(|(_, expected, _)| RecallResult::Typo { expected })
+        .unwrap_or(RecallResult::Wrong)
+}
+
+/// Grades a typed answer for `word`.
+pub fn grade_answer(word: &WordRecord, answer: RecallAnswer, input: &str) -> RecallResult {
+    match answer {
+        RecallAnswer::Reading => grade_reading(input, &word.expression, &word.reading),
+        RecallAnswer::Meaning => grade_meaning(input, &word.meaning),
+    }
+}
+
+/// Updates the schedule of the word with the grade of the answer and logs it as a recall review.
+/// A word that was only heard is logged as a listening review and keeps its schedule.
+pub async fn record_recall(
+    pool: &sqlx::SqlitePool,
+    word_id: i64,
+    result: &RecallResult,
+    listening: bool,
+    response_ms: i64,
+) -> Result<(), sqlx::Error> {
+    let grade = result.grade();
+    let mode = if listening {
+        ReviewMode::Listening
+    } else {
+        grade_word(pool, word_id, grade).await?;
+        ReviewMode::Recall
+    };
+    // the card always shows the Japanese side
+    log_review(pool, &ReviewEntry::new(word_id, mode, true, grade, response_ms)).await?;
+    Ok(())
+}
+
+/// Cards where the reading or the meaning of the selected words is typed from memory.
+/// Enter checks the answer, Enter again goes to the next card.
+/// When `listening`, the word is played instead of shown until the answer is checked.
+#[component]
+pub fn RecallCard(answer: RecallAnswer, #[props(default)] listening: bool) -> Element {
+    let navigator = use_navigator();
+    let select_words = use_context::<Signal<Vec<WordRecord>>>();
+    let mut status_message = use_context::<Signal<StatusMessage>>();
+    let db_pool = use_context::<sqlx::SqlitePool>();
+    let total_cards = select_words.len();
+
+    let mut index = use_signal(|| 0_usize);
+    let mut input = use_signal(String::new);
+    let mut result = use_signal(|| None::<RecallResult>);
+    let mut score = use_signal(|| 0);
+    let mut card_shown_at = use_signal(Instant::now); // used to measure the response time
+    let mut input_element = use_signal(|| None::<std::rc::Rc<MountedData>>);
+
+    // --- voice for tts ---
+    let voice = return_voice("ja", Gender::Male)?;
+    let pronounce = use_callback(move |()| {
+        if let Some(word) = select_words.get(index()) {
+            let text = if word.reading.is_empty() { word.expression.clone() } else { word.reading.clone() };
+            speak_text(text, voice.clone(), 5, Some(1.1), Some(0.9));
+        }
+    });
+
+    // play each new card when listening
+    use_effect(move || {
+        index();
+        if listening {
+            pronounce.call(());
+        }
+    });
+
+    let check_answer = use_callback(move |()| {
+        if result().is_some() {
+            return;
+        }
+        let Some(word) = select_words.read().get(index()).cloned() else {
+            return;
+        };
+        let graded = grade_answer(&word, answer, &input());
+        if graded != RecallResult::Wrong {
+            score += 1;
+        }
+        result.set(Some(graded.clone()));
+
+        let pool = db_pool.clone();
+        let response_ms = card_shown_at().elapsed().as_millis() as i64;
+        spawn(async move {
+            if let Err(e) = record_recall(&pool, word.id, &graded, listening, response_ms).await {
+                eprintln!("Failed to record the answer for word {}: {}", word.id, e);
+                status_message.set(StatusMessage {
+                    message: "A database error occurred.".to_string(),
+                    level: StatusLevel::Error,
+                });
+            }
+        });
+    });
+
+    let next_card = use_callback(move |()| {
+        if result().is_none() {
+            return;
+        }
+        if index() + 1 < total_cards {
+            index += 1;
+        } else {
+            status_message.set(StatusMessage {
+                message: format!("Recall finished! Your score: {}/{}", score(), total_cards),
+                level: StatusLevel::Info,
+            });
+            index.set(0);
+            score.set(0);
+        }
+        input.set(String::new());
+        result.set(None);
+        card_shown_at.set(Instant::now());
+        if let Some(element) = input_element() {
+            spawn(async move {
+                let _ = element.set_focus(true).await;
+            });
+        }
+    });
+
+    let Some(word) = select_words.read().get(index()).cloned() else {
+        return rsx! {
+            div { class: "container p-4",
+                div { class: "alert alert-info", "No words selected, pick a word list first." }
+                button { class: "btn btn-secondary", onclick: move |_| { navigator.push(Route::Home {}); }, "Go Back" }
+            }
+        };
+    };
+
+    let (prompt, placeholder) = match answer {
+        RecallAnswer::Reading => ("Type the reading", "romaji or kana, e.g. taberu"),
+        RecallAnswer::Meaning => ("Type the meaning", "any one of the meanings"),
+    };
+
+    rsx! {
+        div { class: "container h-75 d-flex flex-column",
+            // --- Top Controls ---
+            div { class: "row my-3",
+                div { class: "col-auto",
+                    button { class: "btn btn-secondary",
+                        onclick: move |_| {
+                            navigator.push(Route::GnerateTestCard {});
+                        },
+                        "Go Back"
+                    }
+                }
+                div { class: "col d-flex align-items-center",
+                    h4 { class: "mb-0", "{prompt}" }
+                }
+            }
+
+            // --- Main Content ---
+            div { class: "row flex-grow-1 d-flex flex-column justify-content-center",
+                if listening {
+                    div { class: "my-3",
+                        button { class: "btn btn-light btn-lg", onclick: move |_| pronounce.call(()), "🔊 Play again" }
+                    }
+                } else {
+                    p { class: "display-5 my-3", "{word.expression}" }
+                    if answer == RecallAnswer::Meaning {
+                        p { class: "lead text-secondary", "{word.reading}" }
+                    }
+                }
+                input {
+                    class: "form-control form-control-lg",
+                    r#type: "text",
+                    placeholder: placeholder,
+                    value: "{input}",
+                    readonly: result().is_some(),
+                    onmounted: move |evt| {
+                        let element = evt.data();
+                        input_element.set(Some(element.clone()));
+                        spawn(async move {
+                            let _ = element.set_focus(true).await;
+                        });
+                    },
+                    oninput: move |evt| {
+                        let value = evt.value();
+                        input.set(if answer == RecallAnswer::Reading { kana_as_typed(&value) } else { value });
+                    },
+                    onkeydown: move |evt| {
+                        if evt.key() == Key::Enter {
+                            if result().is_none() {
+                                check_answer.call(());
+                            } else {
+                                next_card.call(());
+                            }
+                        }
+                    },
+                }
+
+                match result() {
+                    Some(RecallResult::Correct) => rsx! {
+                        div { class: "alert alert-success mt-3", "Correct!" }
+                    },
+                    Some(RecallResult::Typo { expected }) => rsx! {
+                        div { class: "alert alert-warning mt-3", "Accepted, it is spelled \"{expected}\"." }
+                    },
+                    Some(RecallResult::Wrong) => rsx! {
+                        div { class: "alert alert-danger mt-3", "Not quite." }
+                    },
+                    None => rsx! {},
+                }
+                if result().is_some() {
+                    div { class: "mt-1",
+                        p { class: "lead mb-1", "{word.expression} 【{word.reading}】" }
+                        p { class: "text-secondary", "{word.meaning}" }
+                    }
+                }
+            }
+
+            // --- Progress Bar ---
+            div { class: "row my-3 align-items-center",
+                div { class: "col",
+                    div { class: "progress",
+                        div {
+                            class: "progress-bar",
+                            role: "progressbar",
+                            style: "width: {((index() + 1) as f32 / total_cards as f32) * 100.0}%",
+                        }
+                    }
+                }
+                div { class: "col-auto", span { "{index() + 1} / {total_cards}" } }
+                div { class: "col-auto", span { class: "text-success", "Score: {score}" } }
+            }
+
+            // --- Bottom Controls ---
+            div { class: "row my-3",
+                div { class: "col",
+                    button {
+                        class: "btn btn-outline-danger w-100",
+                        disabled: result().is_some(),
+                        onclick: move |_| {
+                            input.set(String::new());
+                            check_answer.call(());
+                        },
+                        "I don't know"
+                    }
+                }
+                div { class: "col",
+                    if result().is_none() {
+                        button { class: "btn btn-primary w-100", onclick: move |_| check_answer.call(()), "Check (Enter)" }
+                    } else {
+                        button { class: "btn btn-primary w-100", onclick: move |_| next_card.call(()), "Next (Enter)" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Recall cards where the word is only heard.
+#[component]
+pub fn ListeningRecall(answer: RecallAnswer) -> Element {
+    rsx! { RecallCard { answer, listening: true } }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::review_log::reviews_for_word;
+
+    #[test]
+    fn test_grade_reading() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(grade_reading("taberu", "食べる", "たべる"), RecallResult::Correct);
+        assert_eq!(grade_reading("タベル", "食べる", "たべる"), RecallResult::Correct);
+        assert_eq!(grade_reading("hon", "本", "ほん"), RecallResult::Correct);
+        // the reading has to be exact
+        assert_eq!(grade_reading("tabero", "食べる", "たべる"), RecallResult::Wrong);
+        assert_eq!(grade_reading("nichi", "日", "ひ; にち"), RecallResult::Correct);
+        assert_eq!(grade_reading("mou", "もう", ""), RecallResult::Correct);
+        assert_eq!(grade_reading("", "もう", ""), RecallResult::Wrong);
+        assert_eq!(kana_as_typed("hon"), "ほn");
+    }
+
+    #[test]
+    fn test_grade_meaning() {
+        let meaning = "to eat (food); to live on, to feed";
+        assert_eq!(grade_meaning("eat", meaning), RecallResult::Correct);
+        assert_eq!(grade_meaning("To Live On", meaning), RecallResult::Correct);
+        assert_eq!(grade_meaning("liv on", meaning), RecallResult::Typo { expected: "to live on".to_string() });
+        assert_eq!(grade_meaning("to drink", meaning), RecallResult::Wrong);
+        assert_eq!(grade_meaning("libary", "library"), RecallResult::Typo { expected: "library".to_string() });
+        // no typos in short words
+        assert_eq!(grade_meaning("sky", "skin"), RecallResult::Wrong);
+        assert_eq!(grade_meaning(" ", "sky"), RecallResult::Wrong);
+        assert_eq!(RecallResult::Typo { expected: String::new() }.grade(), Grade::Hard);
+    }
+
+    #[tokio::test]
+    async fn test_record_recall() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![test_word("食べる", "たべる", "", "")])
+            .await
+            .unwrap();
+
+        record_recall(&pool, 1, &RecallResult::Correct, false, 1200).await.unwrap();
+        let word = &find_word_by_ids(&pool, vec![1]).await.unwrap()[0];
+        assert_eq!(word.practice_time, 1);
+        assert!(word.familiar);
+
+        record_recall(&pool, 1, &RecallResult::Wrong, false, 3000).await.unwrap();
+        assert!(!find_word_by_ids(&pool, vec![1]).await.unwrap()[0].familiar);
+        let reviews = reviews_for_word(&pool, 1).await.unwrap();
+        assert_eq!(reviews.len(), 2);
+        assert!(reviews.iter().all(|review| review.mode == ReviewMode::Recall));
+
+        // a listening answer is logged on its own and leaves the schedule alone
+        record_recall(&pool, 1, &RecallResult::Correct, true, 900).await.unwrap();
+        assert_eq!(find_word_by_ids(&pool, vec![1]).await.unwrap()[0].practice_time, 2);
+        assert_eq!(reviews_for_word(&pool, 1).await.unwrap()[2].mode, ReviewMode::Listening);
+    }
+}
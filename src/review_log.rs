@@ -0,0 +1,276 @@
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::scheduler::{Grade, SECONDS_PER_DAY};
+
+/// Which study mode produced a review.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReviewMode {
+    Flashcard,
+    Test,
+    /// A story comprehension quiz question about the word.
+    Quiz,
+    /// The reading or meaning typed from memory.
+    Recall,
+    /// The word was only heard, not read. Doesn't change the schedule.
+    Listening,
+    /// The word filled into the blank of an example sentence.
+    Cloze,
+}
+
+/// The name stored in the `mode` column.
+impl fmt::Display for ReviewMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ReviewMode::Flashcard => "flashcard",
+            ReviewMode::Test => "test",
+            ReviewMode::Quiz => "quiz",
+            ReviewMode::Recall => "recall",
+            ReviewMode::Listening => "listening",
+            ReviewMode::Cloze => "cloze",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for ReviewMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "flashcard" => Ok(ReviewMode::Flashcard),
+            "test" => Ok(ReviewMode::Test),
+            "quiz" => Ok(ReviewMode::Quiz),
+            "recall" => Ok(ReviewMode::Recall),
+            "listening" => Ok(ReviewMode::Listening),
+            "cloze" => Ok(ReviewMode::Cloze),
+            _ => Err(format!("Unknown review mode: {}", mode)),
+        }
+    }
+}
+
+/// One answer given by the user, as stored in the `reviews` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReviewEntry {
+    pub id: i64,
+    pub word_id: i64,
+    pub reviewed_at: i64, // unix timestamp in seconds
+    pub mode: ReviewMode,
+    pub j_to_e: bool,
+    pub grade: Grade,
+    pub response_ms: i64,
+}
+
+impl ReviewEntry {
+    /// Creates a new entry stamped with the current time, the id is assigned by the DB.
+    pub fn new(word_id: i64, mode: ReviewMode, j_to_e: bool, grade: Grade, response_ms: i64) -> Self {
+        ReviewEntry {
+            id: 0,
+            word_id,
+            reviewed_at: now_timestamp(),
+            mode,
+            j_to_e,
+            grade,
+            response_ms,
+        }
+    }
+
+    fn from_row(row: &SqliteRow) -> Self {
+        ReviewEntry {
+            id: row.get("id"),
+            word_id: row.get("word_id"),
+            reviewed_at: row.get("reviewed_at"),
+            mode: row.get::<String, _>("mode").parse().unwrap_or(ReviewMode::Flashcard),
+            j_to_e: row.get("j_to_e"),
+            grade: Grade::from_i64(row.get("grade")).unwrap_or(Grade::Again),
+            response_ms: row.get("response_ms"),
+        }
+    }
+}
+
+/// Number of reviews answered on one day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyReviewCount {
+    pub day: i64, // days since the unix epoch, same as scheduler::today()
+    pub total: i64,
+    pub again: i64,
+}
+
+/// Number of reviews of one mode and how many of them were graded Again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeReviewCount {
+    pub mode: ReviewMode,
+    pub total: i64,
+    pub again: i64,
+}
+
+impl ModeReviewCount {
+    /// Share of the reviews that were not graded Again, between 0 and 1.
+    pub fn accuracy(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.total - self.again) as f64 / self.total as f64
+    }
+}
+
+/// Returns the current unix timestamp in seconds.
+pub fn now_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Writes one review to the log and returns its id.
+pub async fn log_review(pool: &sqlx::SqlitePool, entry: &ReviewEntry) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO reviews (word_id, reviewed_at, mode, j_to_e, grade, response_ms)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(entry.word_id)
+    .bind(entry.reviewed_at)
+    .bind(entry.mode.to_string())
+    .bind(entry.j_to_e)
+    .bind(entry.grade.to_i64())
+    .bind(entry.response_ms)
+    .execute(pool)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+/// Returns the full review history of a word, oldest first.
+pub async fn reviews_for_word(pool: &sqlx::SqlitePool, word_id: i64) -> Result<Vec<ReviewEntry>, sqlx::Error> {
+    let rows = sqlx::query("SELECT * FROM reviews WHERE word_id = ? ORDER BY reviewed_at, id")
+        .bind(word_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(ReviewEntry::from_row).collect())
+}
+
+/// Returns all reviews answered on `day` (days since the unix epoch, UTC), oldest first.
+pub async fn reviews_for_day(pool: &sqlx::SqlitePool, day: i64) -> Result<Vec<ReviewEntry>, sqlx::Error> {
+    let rows = sqlx::query("SELECT * FROM reviews WHERE reviewed_at >= ? AND reviewed_at < ? ORDER BY reviewed_at, id")
+        .bind(day * SECONDS_PER_DAY)
+        .bind((day + 1) * SECONDS_PER_DAY)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(ReviewEntry::from_row).collect())
+}
+
+/// Counts reviews per day, most recent day first.
+pub async fn count_reviews_by_day(pool: &sqlx::SqlitePool) -> Result<Vec<DailyReviewCount>, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT reviewed_at / ? AS day,
+               COUNT(id) AS total,
+               SUM(CASE WHEN grade = ? THEN 1 ELSE 0 END) AS again
+        FROM reviews
+        GROUP BY day
+        ORDER BY day DESC
+        "#,
+    )
+    .bind(SECONDS_PER_DAY)
+    .bind(Grade::Again.to_i64())
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| DailyReviewCount {
+            day: row.get("day"),
+            total: row.get("total"),
+            again: row.get("again"),
+        })
+        .collect())
+}
+
+/// Counts reviews per mode, so a weak mode like listening shows up next to the others.
+pub async fn count_reviews_by_mode(pool: &sqlx::SqlitePool) -> Result<Vec<ModeReviewCount>, sqlx::Error> {
+    let rows = sqlx::query(
+        r#"
+        SELECT mode,
+               COUNT(id) AS total,
+               SUM(CASE WHEN grade = ? THEN 1 ELSE 0 END) AS again
+        FROM reviews
+        GROUP BY mode
+        ORDER BY total DESC
+        "#,
+    )
+    .bind(Grade::Again.to_i64())
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            Some(ModeReviewCount {
+                mode: row.get::<String, _>("mode").parse().ok()?,
+                total: row.get("total"),
+                again: row.get("again"),
+            })
+        })
+        .collect())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+
+    #[tokio::test]
+    async fn test_review_log() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            test_word("一", "いち", "one", "n5"),
+            test_word("二", "に", "two", "n5"),
+        ]).await.expect("Failed to insert words.");
+
+        let mut first = ReviewEntry::new(1, ReviewMode::Flashcard, true, Grade::Again, 1200);
+        first.reviewed_at = 3 * SECONDS_PER_DAY + 10;
+        let mut second = ReviewEntry::new(1, ReviewMode::Test, false, Grade::Good, 800);
+        second.reviewed_at = 4 * SECONDS_PER_DAY + 10;
+        let mut third = ReviewEntry::new(2, ReviewMode::Flashcard, true, Grade::Easy, 500);
+        third.reviewed_at = 4 * SECONDS_PER_DAY + 20;
+        for entry in [&first, &second, &third] {
+            log_review(&pool, entry).await.expect("Failed to log review.");
+        }
+
+        let history = reviews_for_word(&pool, 1).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].grade, Grade::Again);
+        assert_eq!(history[1].mode, ReviewMode::Test);
+        assert!(!history[1].j_to_e);
+
+        let day = reviews_for_day(&pool, 4).await.unwrap();
+        assert_eq!(day.len(), 2);
+        assert_eq!(day[1].word_id, 2);
+
+        let counts = count_reviews_by_day(&pool).await.unwrap();
+        assert_eq!(counts, vec![
+            DailyReviewCount { day: 4, total: 2, again: 0 },
+            DailyReviewCount { day: 3, total: 1, again: 1 },
+        ]);
+
+        log_review(&pool, &ReviewEntry::new(2, ReviewMode::Listening, true, Grade::Again, 700)).await.unwrap();
+        let by_mode = count_reviews_by_mode(&pool).await.unwrap();
+        assert_eq!(by_mode[0], ModeReviewCount { mode: ReviewMode::Flashcard, total: 2, again: 1 });
+        let listening = by_mode.iter().find(|count| count.mode == ReviewMode::Listening).unwrap();
+        assert_eq!((listening.total, listening.accuracy()), (1, 0.0));
+
+        for mode in [ReviewMode::Flashcard, ReviewMode::Recall, ReviewMode::Cloze] {
+            assert_eq!(mode.to_string().parse::<ReviewMode>(), Ok(mode));
+        }
+        assert!("typing".parse::<ReviewMode>().is_err());
+
+        // deleting a word removes its history
+        delete_words(&pool, vec![1]).await.unwrap();
+        assert!(reviews_for_word(&pool, 1).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::db::{find_word_by_ids, ProgressUpdate, WordRecord};
+
+/// starting ease factor for a new word, same as the DB default
+pub const DEFAULT_EASE: f64 = 2.5;
+/// the ease factor never drops below this value (SM-2)
+pub const MIN_EASE: f64 = 1.3;
+
+pub const SECONDS_PER_DAY: i64 = 60 * 60 * 24;
+
+/// The answer grade the user gives after reviewing a word.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Grade {
+    Again,
+    Hard,
+    Good,
+    Easy,
+}
+
+impl Grade {
+    /// Converts the grade to the integer stored in the database.
+    pub fn to_i64(&self) -> i64 {
+        match self {
+            Grade::Again => 1,
+            Grade::Hard => 2,
+            Grade::Good => 3,
+            Grade::Easy => 4,
+        }
+    }
+    pub fn from_i64(value: i64) -> Option<Grade> {
+        match value {
+            1 => Some(Grade::Again),
+            2 => Some(Grade::Hard),
+            3 => Some(Grade::Good),
+            4 => Some(Grade::Easy),
+            _ => None,
+        }
+    }
+    /// Good and Easy count as knowing the word, this drives the `familiar` flag.
+    pub fn is_familiar(&self) -> bool {
+        matches!(self, Grade::Good | Grade::Easy)
+    }
+}
+
+/// Returns today's day number (days since the unix epoch, UTC).
+/// `due_day` in the words table uses the same unit.
+pub fn today() -> i64 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    secs / SECONDS_PER_DAY
+}
+
+/// The spaced repetition state of a single word.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReviewState {
+    pub ease: f64,
+    pub interval_days: i64,
+    pub due_day: i64,
+    pub lapses: i64,
+}
+
+impl Default for ReviewState {
+    fn default() -> Self {
+        ReviewState {
+            ease: DEFAULT_EASE,
+            interval_days: 0,
+            due_day: 0,
+            lapses: 0,
+        }
+    }
+}
+
+impl ReviewState {
+    pub fn from_word(word: &WordRecord) -> Self {
+        ReviewState {
+            ease: word.ease,
+            interval_days: word.interval_days,
+            due_day: word.due_day,
+            lapses: word.lapses,
+        }
+    }
+
+    /// Computes the next state after answering with `grade` on day `today`.
+    /// This is a SM-2 variant with Anki style Hard/Easy buttons:
+    /// - Again resets the interval, counts a lapse and keeps the word due today
+    /// - Hard grows the interval slowly and lowers the ease
+    /// - Good uses the 1 day, 6 days, interval * ease steps
+    /// - Easy jumps further and raises the ease
+    pub fn next(&self, grade: Grade, today: i64) -> ReviewState {
+        let prev = self.interval_days;
+        let (ease, interval, lapses) = match grade {
+            Grade::Again => ((self.ease - 0.2).max(MIN_EASE), 0, self.lapses + 1),
+            Grade::Hard => {
+                let interval = ((prev as f64) * 1.2).round() as i64;
+                ((self.ease - 0.15).max(MIN_EASE), interval.max(1), self.lapses)
+            }
+            Grade::Good => (self.ease, Self::good_interval(prev, self.ease), self.lapses),
+            Grade::Easy => {
+                let interval = ((Self::good_interval(prev, self.ease) as f64) * 1.3).round() as i64;
+                (self.ease + 0.15, interval.max(4), self.lapses)
+            }
+        };
+
+        ReviewState {
+            ease,
+            interval_days: interval,
+            due_day: today + interval,
+            lapses,
+        }
+    }
+
+    fn good_interval(prev: i64, ease: f64) -> i64 {
+        match prev {
+            0 => 1,
+            1 => 6,
+            _ => (((prev as f64) * ease).round() as i64).max(prev + 1),
+        }
+    }
+}
+
+/// Grades a word and writes the result back to the database.
+/// It increments the practice time, updates the `familiar` flag and stores the next review state.
+pub async fn grade_word(pool: &sqlx::SqlitePool, word_id: i64, grade: Grade) -> Result<ReviewState, sqlx::Error> {
+    let words = find_word_by_ids(pool, vec![word_id]).await?;
+    let Some(word) = words.first() else {
+        return Err(sqlx::Error::RowNotFound);
+    };
+
+    let next = ReviewState::from_word(word).next(grade, today());
+
+    ProgressUpdate::new()
+        .increment_practice_time()
+        .set_familiar(grade.is_familiar())
+        .set_schedule(next)
+        .execute(pool, word_id)
+        .await?;
+
+    Ok(next)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+
+    #[test]
+    fn test_good_interval_steps() {
+        let day = 100;
+        let first = ReviewState::default().next(Grade::Good, day);
+        assert_eq!(first.interval_days, 1);
+        assert_eq!(first.due_day, 101);
+
+        let second = first.next(Grade::Good, 101);
+        assert_eq!(second.interval_days, 6);
+
+        let third = second.next(Grade::Good, 107);
+        assert_eq!(third.interval_days, 15); // 6 * 2.5
+        assert_eq!(third.due_day, 122);
+    }
+
+    #[test]
+    fn test_again_counts_lapse_and_lowers_ease() {
+        let state = ReviewState { ease: 1.4, interval_days: 20, due_day: 50, lapses: 0 };
+        let next = state.next(Grade::Again, 50);
+        assert_eq!(next.interval_days, 0);
+        assert_eq!(next.due_day, 50);
+        assert_eq!(next.lapses, 1);
+        assert_eq!(next.ease, MIN_EASE);
+    }
+
+    #[test]
+    fn test_easy_beats_good() {
+        let state = ReviewState { ease: 2.5, interval_days: 10, due_day: 0, lapses: 0 };
+        let good = state.next(Grade::Good, 0);
+        let easy = state.next(Grade::Easy, 0);
+        let hard = state.next(Grade::Hard, 0);
+        assert!(easy.interval_days > good.interval_days);
+        assert!(hard.interval_days < good.interval_days);
+        assert!(easy.ease > good.ease && hard.ease < good.ease);
+    }
+
+    #[tokio::test]
+    async fn test_grade_word_and_due_query() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            test_word("一", "いち", "one", "n5"),
+            test_word("二", "に", "two", "n5"),
+        ]).await.expect("Failed to insert words.");
+
+        grade_word(&pool, 1, Grade::Good).await.expect("Failed to grade word.");
+        grade_word(&pool, 2, Grade::Again).await.expect("Failed to grade word.");
+
+        let word = find_word_by_ids(&pool, vec![1]).await.unwrap();
+        assert_eq!(word[0].practice_time, 1);
+        assert!(word[0].familiar);
+        assert_eq!(word[0].due_day, today() + 1);
+
+        // only the word graded Again is due today
+        let due = ProgressSelect::new()
+            .select_due(today())
+            .execute(&pool)
+            .await
+            .expect("Failed to select due words.");
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].id, 2);
+        assert_eq!(due[0].lapses, 1);
+        assert_eq!(count_due_words(&pool, today()).await.unwrap(), 1);
+    }
+}
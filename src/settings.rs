@@ -0,0 +1,40 @@
+use sqlx::Row;
+
+/// Returns the value stored for `key` in the `settings` table, None if it was never set.
+pub async fn get_setting(pool: &sqlx::SqlitePool, key: &str) -> Result<Option<String>, sqlx::Error> {
+    let row = sqlx::query("SELECT value FROM settings WHERE key = ?")
+        .bind(key)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|row| row.get("value")))
+}
+
+/// Stores `value` for `key`, replacing the previous value.
+pub async fn set_setting(pool: &sqlx::SqlitePool, key: &str, value: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO settings (key, value) VALUES (?, ?) ON CONFLICT(key) DO UPDATE SET value = excluded.value")
+        .bind(key)
+        .bind(value)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::setup_test_db;
+
+    #[tokio::test]
+    async fn test_settings_round_trip() {
+        let pool = setup_test_db().await;
+
+        assert_eq!(get_setting(&pool, "llm.model").await.unwrap(), None);
+        set_setting(&pool, "llm.model", "gemma3:4b").await.unwrap();
+        set_setting(&pool, "llm.model", "qwen3:8b").await.unwrap();
+        set_setting(&pool, "llm.kind", "ollama").await.unwrap();
+
+        assert_eq!(get_setting(&pool, "llm.model").await.unwrap().as_deref(), Some("qwen3:8b"));
+        assert_eq!(get_setting(&pool, "llm.kind").await.unwrap().as_deref(), Some("ollama"));
+    }
+}
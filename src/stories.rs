@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+use crate::review_log::now_timestamp;
+use crate::scheduler::SECONDS_PER_DAY;
+
+/// JLPT levels from the easiest to the hardest.
+pub const JLPT_LEVELS: [&str; 5] = ["n5", "n4", "n3", "n2", "n1"];
+
+/// True when `level` is harder than `target`, e.g. "n2" is above "n4".
+/// Empty or unknown levels are never above.
+pub fn is_above_level(level: &str, target: &str) -> bool {
+    let rank = |level: &str| JLPT_LEVELS.iter().position(|l| *l == level);
+    match (rank(level), rank(target)) {
+        (Some(level), Some(target)) => level > target,
+        _ => false,
+    }
+}
+
+/// The hardest of the given levels, empty or unknown levels are skipped.
+pub fn hardest_level<'a>(levels: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    levels.into_iter().filter(|level| JLPT_LEVELS.contains(level)).reduce(|hardest, level| {
+        if is_above_level(level, hardest) { level } else { hardest }
+    })
+}
+
+/// A generated story, as stored in the `stories` table.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StoryRecord {
+    pub id: i64,
+    pub story: String,
+    pub english: String,
+    /// The words the story was asked to use.
+    pub words: Vec<String>,
+    /// Hardest JLPT level of the words, e.g. "n3", empty when none of them is in the words table.
+    pub jlpt: String,
+    pub model: String,
+    pub created_at: i64, // unix timestamp in seconds
+}
+
+impl StoryRecord {
+    fn from_row(row: &SqliteRow) -> Self {
+        StoryRecord {
+            id: row.get("id"),
+            story: row.get("story"),
+            english: row.get("english"),
+            words: serde_json::from_str(&row.get::<String, _>("words")).unwrap_or_default(),
+            jlpt: row.get("jlpt"),
+            model: row.get("model"),
+            created_at: row.get("created_at"),
+        }
+    }
+
+    /// The first `chars` characters of the story, for lists.
+    pub fn preview(&self, chars: usize) -> String {
+        let mut preview: String = self.story.chars().take(chars).collect();
+        if self.story.chars().count() > chars {
+            preview.push('…');
+        }
+        preview
+    }
+
+    /// The words joined for display, e.g. "青、作法".
+    pub fn word_list(&self) -> String {
+        self.words.join("、")
+    }
+
+    /// Whole days since the story was generated.
+    pub fn age_days(&self) -> i64 {
+        (now_timestamp() - self.created_at) / SECONDS_PER_DAY
+    }
+}
+
+/// Returns the JLPT level of each of `words` found in the words table,
+/// the hardest one when a word is listed at several levels.
+pub async fn jlpt_levels(pool: &sqlx::SqlitePool, words: &[String]) -> Result<HashMap<String, String>, sqlx::Error> {
+    if words.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let placeholders = vec!["?"; words.len()].join(", ");
+    let sql = format!(
+        "SELECT expression, MIN(jlpt) AS jlpt FROM words WHERE expression IN ({}) AND jlpt != '' GROUP BY expression",
+        placeholders
+    );
+    let mut query = sqlx::query(&sql);
+    for word in words {
+        query = query.bind(word);
+    }
+    let rows = query.fetch_all(pool).await?;
+    Ok(rows.iter().map(|row| (row.get("expression"), row.get("jlpt"))).collect())
+}
+
+/// Saves a story and returns its id, `created_at` is set to now.
+pub async fn save_story(pool: &sqlx::SqlitePool, story: &StoryRecord) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO stories (story, english, words, jlpt, model, created_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&story.story)
+    .bind(&story.english)
+    .bind(serde_json::to_string(&story.words).unwrap_or_else(|_| "[]".to_string()))
+    .bind(&story.jlpt)
+    .bind(&story.model)
+    .bind(now_timestamp())
+    .execute(pool)
+    .await?;
+    Ok(result.last_insert_rowid())
+}
+
+/// Lists the saved stories, newest first.
+pub async fn list_stories(pool: &sqlx::SqlitePool) -> Result<Vec<StoryRecord>, sqlx::Error> {
+    let rows = sqlx::query("SELECT * FROM stories ORDER BY created_at DESC, id DESC")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(StoryRecord::from_row).collect())
+}
+
+pub async fn find_story(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<StoryRecord>, sqlx::Error> {
+    let row = sqlx::query("SELECT * FROM stories WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.as_ref().map(StoryRecord::from_row))
+}
+
+/// Deletes a story and its quiz results.
+pub async fn delete_story(pool: &sqlx::SqlitePool, id: i64) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query("DELETE FROM quiz_results WHERE story_id = ?")
+        .bind(id)
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query("DELETE FROM stories WHERE id = ?")
+        .bind(id)
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+
+    #[tokio::test]
+    async fn test_story_library() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            test_word("青", "あお", "blue", "n5"),
+            test_word("作法", "さほう", "manners", "n3"),
+        ]).await.unwrap();
+
+        let words = vec!["青".to_string(), "作法".to_string(), "未知".to_string()];
+        let levels = jlpt_levels(&pool, &words).await.unwrap();
+        assert_eq!(levels.len(), 2);
+        assert_eq!(levels["作法"], "n3");
+        assert!(is_above_level("n3", "n4"));
+        assert!(!is_above_level("n5", "n4"));
+        assert!(!is_above_level("", "n4"));
+        assert_eq!(hardest_level(["n5", "", "n3", "n4"]), Some("n3"));
+        assert_eq!(hardest_level([""]), None);
+
+        let first = save_story(&pool, &StoryRecord {
+            story: "青い空。".to_string(),
+            english: "A blue sky.".to_string(),
+            words: words.clone(),
+            jlpt: "n3".to_string(),
+            model: "gemma3:4b".to_string(),
+            ..Default::default()
+        }).await.unwrap();
+        let second = save_story(&pool, &StoryRecord { story: "二つ目の話です。".to_string(), ..Default::default() }).await.unwrap();
+
+        let stories = list_stories(&pool).await.unwrap();
+        assert_eq!(stories.iter().map(|s| s.id).collect::<Vec<_>>(), vec![second, first]);
+
+        let story = find_story(&pool, first).await.unwrap().unwrap();
+        assert_eq!(story.words, words);
+        assert_eq!(story.jlpt, "n3");
+        assert_eq!(story.age_days(), 0);
+        assert_eq!(story.preview(2), "青い…");
+
+        delete_story(&pool, first).await.unwrap();
+        assert_eq!(find_story(&pool, first).await.unwrap(), None);
+        assert_eq!(list_stories(&pool).await.unwrap().len(), 1);
+    }
+}
@@ -1,20 +1,21 @@
 
 use rand::seq::IndexedRandom;
-use wana_kana::ConvertJapanese;
-use lindera::{dictionary::load_dictionary_from_kind};
-use lindera::mode::Mode;
-use lindera::segmenter::Segmenter;
-use lindera::tokenizer::Tokenizer;
 use dioxus::prelude::*;
-use crate::{utils::{get_pos_color_class, PartOfSpeech}};
+use crate::{utils::{align_furigana, get_pos_color_class, parse_partial_json, PartOfSpeech, RubySegment}};
 use std::sync::Arc;
+use std::collections::HashSet;
 use std::error::Error;
-use ollama_rs::{Ollama, generation::completion::request::GenerationRequest};
 use crate::footer::{StatusMessage, StatusLevel};
+use crate::llm::LlmBackend;
+use crate::llm_json::{generate_json, object_schema, string_schema, JsonProgress};
+use crate::stories::*;
+use crate::word_lookup::WordLookupPanel;
+use crate::tokenizer::tokenize;
+use crate::furigana::{known_words, FuriganaSettings, Ruby};
+use crate::Route;
 use tts::*;
 use crate::return_voice;
 
-// use futures_util::StreamExt;
 
 /// this struct represents a word token with its properties
 #[derive(Clone, Debug, PartialEq)]
@@ -22,9 +23,20 @@ pub struct WordToken {
     pub surface: String,      // The word as it appears in the text (e.g., "食べ")
     pub part_of_speech: String, // e.g., "動詞" (Verb)
     pub base_form: String,      // The dictionary form (e.g., "食べる")
+    pub reading: String,        // The hiragana reading of the surface (e.g., "たべ")
     pub romaji: String,         // The romaji reading (e.g., "tabe")
 }
 
+impl WordToken {
+    /// The surface with furigana over its kanji, tagged with the base form.
+    pub fn ruby(&self) -> Vec<RubySegment> {
+        align_furigana(&self.surface, &self.reading)
+            .into_iter()
+            .map(|segment| RubySegment { base_form: self.base_form.clone(), ..segment })
+            .collect()
+    }
+}
+
 /// This struct is used to deserialize the JSON response from the LLM
 #[derive(serde::Deserialize)]
 struct StoryResponse {
@@ -32,102 +44,257 @@ struct StoryResponse {
     english: String,
 }
 
-/// --- Parsing function is the same ---
-fn parse_llm_story_json(raw_text: &str) -> Result<(String, String), Box<dyn Error>> {
-    // (Your robust parsing logic from the previous step goes here)
-    let start_byte = raw_text.find('{').ok_or("Could not find opening '{'")?;
-    let end_byte = raw_text.rfind('}').ok_or("Could not find closing '}'")? + 1;
-    let mut json_slice = &raw_text[start_byte..end_byte];
+/// JSON schema of `StoryResponse`, sent to backends with a structured output mode.
+fn story_schema() -> serde_json::Value {
+    object_schema(&[("story", string_schema()), ("english", string_schema())])
+}
 
-    // Remove control characters (except \n, \r, \t if you want to keep them)
-    let cleaned: String = json_slice.chars()
-        .filter(|c| !c.is_control())
-        .collect();
-    let resp: StoryResponse = serde_json::from_str(&cleaned)?;
-    Ok((resp.story, resp.english))
+/// How hard and how long a generated story should be.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoryOptions {
+    /// Target JLPT level, "n5" (easiest) to "n1".
+    /// None targets the hardest level of the listed words, so none of them is left out.
+    pub jlpt: Option<String>,
+    /// Approximate length of the story in words.
+    pub length: usize,
+    /// How many of the practiced words the story must use.
+    pub word_count: usize,
 }
 
-/// this function generates a story using a list of words.
-async fn get_story(
-    ollama_client: Arc<Ollama>, 
-    all_words: &[String]
-)  -> Result<(String, String), Box<dyn Error>> {
-    // Ensure we have at least 10 words to choose from
-    if all_words.len() < 10 {
-        return Err("Not enough words to generate a story.".to_string().into());
+impl Default for StoryOptions {
+    fn default() -> Self {
+        StoryOptions {
+            jlpt: None,
+            length: 150,
+            word_count: 10,
+        }
+    }
+}
+
+/// Stories using a smaller share of the requested words are generated again.
+pub const MIN_COVERAGE: f64 = 0.8;
+/// Stories generated at most to reach MIN_COVERAGE, the best one is kept.
+const STORY_ATTEMPTS: usize = 2;
+
+/// Which requested words a story really uses, and which of its words are too hard.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StoryCoverage {
+    pub used: Vec<String>,
+    pub missing: Vec<String>,
+    /// Base forms of the story above the target level, with their level, e.g. ("概念", "n1").
+    pub above_level: Vec<(String, String)>,
+}
+
+impl StoryCoverage {
+    /// Share of the requested words used by the story, 1.0 when none were requested.
+    pub fn ratio(&self) -> f64 {
+        let total = self.used.len() + self.missing.len();
+        if total == 0 {
+            1.0
+        } else {
+            self.used.len() as f64 / total as f64
+        }
     }
 
-    let model = "gemma3:4b".to_string();
+    pub fn is_poor(&self) -> bool {
+        self.ratio() < MIN_COVERAGE
+    }
+}
 
-    // Randomly select 10 words
-    let mut thrng = rand::rng();
-    let selected_words: Vec<String> = all_words
-        .choose_multiple(&mut thrng, 10)
-        .cloned()
+/// Whether the tokens use `word`: the base form of a token, or of a run of tokens for compound
+/// words lindera splits into parts, e.g. "勉強" + "し" read as "勉強する". Conjugated verbs match
+/// by their base form, a word inside a longer one (人 of 人間) doesn't match.
+fn uses_word(tokens: &[WordToken], word: &str) -> bool {
+    (0..tokens.len()).any(|first| {
+        let mut surfaces = String::new();
+        for token in &tokens[first..] {
+            if format!("{}{}", surfaces, token.base_form) == word {
+                return true;
+            }
+            surfaces.push_str(&token.surface);
+            if !word.starts_with(surfaces.as_str()) {
+                return false;
+            }
+        }
+        false
+    })
+}
+
+/// Tokenizes `story` and checks it against the requested words and the target JLPT level.
+/// A requested word counts as used when it is the base form of a token or of a run of tokens,
+/// see `uses_word`. Requested words are never reported as above the level.
+pub async fn check_story_coverage(
+    pool: &sqlx::SqlitePool,
+    story: &str,
+    requested: &[String],
+    target_jlpt: &str,
+) -> Result<StoryCoverage, Box<dyn Error>> {
+    let tokens = process_story_text(story)?;
+
+    let mut coverage = StoryCoverage::default();
+    for word in requested {
+        if uses_word(&tokens, word) {
+            coverage.used.push(word.clone());
+        } else {
+            coverage.missing.push(word.clone());
+        }
+    }
+
+    let mut base_forms: Vec<String> = tokens
+        .iter()
+        .map(|token| token.base_form.clone())
+        .filter(|form| form != "*" && !requested.contains(form))
         .collect();
-    
+    base_forms.sort();
+    base_forms.dedup();
+    let levels = jlpt_levels(pool, &base_forms).await?;
+    coverage.above_level = base_forms
+        .into_iter()
+        .filter_map(|form| {
+            let level = levels.get(&form)?;
+            is_above_level(level, target_jlpt).then(|| (form.clone(), level.clone()))
+        })
+        .collect();
+    Ok(coverage)
+}
+
+/// The story prompt, `missing` lists the words a previous attempt left out.
+fn story_prompt(words: &[String], options: &StoryOptions, missing: &[String]) -> String {
     // Join the words into a comma-separated string for the prompt
-    let word_list = selected_words.join("、 ");
+    let word_list = words.join("、 ");
+    let level = options.jlpt.as_deref().unwrap_or("n1").to_uppercase();
+    let length = options.length;
+    let reminder = if missing.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n        Your previous story did not use these words: {}. This time use every word of the list.\n",
+            missing.join("、 ")
+        )
+    };
 
     // Construct the final, robust prompt
-    let prompt = format!(
-        r#"You are a creative writer for a Japanese language learner.
+    format!(
+        r#"You are a creative writer for a Japanese language learner at JLPT {level} level.
 
         Follow these instructions precisely:
-        1. Write a short story in Japanese, approximately 100-150 words long.
+        1. Write a short story in Japanese, approximately {length} words long.
         2. The story MUST include the following Japanese words: {word_list}
-        3. The story must ONLY contain Japanese characters (Kanji, Hiragana, Katakana). Do NOT include furigana, Romaji, or any parenthetical notes in the story.
-        4. Provide a separate, full English translation of the story.
-        5. Structure your entire response as a single, valid JSON object following the template below. Do not add any text before or after the JSON object.
-
+        3. Apart from these words, only use vocabulary and grammar of JLPT {level} or easier.
+        4. The story must ONLY contain Japanese characters (Kanji, Hiragana, Katakana). Do NOT include furigana, Romaji, or any parenthetical notes in the story.
+        5. Provide a separate, full English translation of the story.
+        6. Structure your entire response as a single, valid JSON object following the template below. Do not add any text before or after the JSON object.
+        {reminder}
         JSON Template:
         {{
           "story": "...",
           "english": "..."
         }}"#
-    );
+    )
+}
 
-    eprintln!("Requesting word list for story are '{}'\n", word_list); 
+/// this function generates a story using a list of words.
+/// Only words at the target level or easier are picked, without a target level the hardest
+/// level of the words is used. The story is checked with
+/// `check_story_coverage` and generated again, naming the left out words, when the coverage is poor.
+/// The response is streamed, `on_progress` gets the text generated so far after every chunk
+/// and is told when an answer is asked for again.
+/// The returned record is not saved yet, its JLPT level is the target level.
+async fn get_story(
+    llm: Arc<dyn LlmBackend>, 
+    pool: &sqlx::SqlitePool,
+    all_words: &[String],
+    options: &StoryOptions,
+    mut on_progress: impl FnMut(JsonProgress),
+)  -> Result<(StoryRecord, StoryCoverage), Box<dyn Error>> {
+    // words above the target level would make the story too hard
+    let levels = jlpt_levels(pool, all_words).await?;
+    let target = match &options.jlpt {
+        Some(level) => level.clone(),
+        None => hardest_level(levels.values().map(String::as_str)).unwrap_or("n1").to_string(),
+    };
+    let options = &StoryOptions { jlpt: Some(target.clone()), ..options.clone() };
+    let candidates: Vec<String> = all_words
+        .iter()
+        .filter(|word| !levels.get(*word).is_some_and(|level| is_above_level(level, &target)))
+        .cloned()
+        .collect();
+    if candidates.len() < options.word_count {
+        return Err(format!(
+            "Not enough words to generate a story: {} of {} are at {} or easier, {} needed.",
+            candidates.len(),
+            all_words.len(),
+            target.to_uppercase(),
+            options.word_count
+        )
+        .into());
+    }
 
-    let request = GenerationRequest::new(model, prompt);
-    let response = ollama_client.generate(request).await?;
-    eprintln!("Response from Ollama: {}\n", response.response);
-    parse_llm_story_json(&response.response)
-        
+    // Randomly select the words
+    let mut thrng = rand::rng();
+    let selected_words: Vec<String> = candidates
+        .choose_multiple(&mut thrng, options.word_count)
+        .cloned()
+        .collect();
+    eprintln!("Requesting word list for story are '{}'\n", selected_words.join("、 ")); 
+
+    let mut best: Option<(StoryResponse, StoryCoverage)> = None;
+    let mut missing = Vec::new();
+    for attempt in 1..=STORY_ATTEMPTS {
+        if attempt > 1 {
+            on_progress(JsonProgress::Retry {
+                attempt,
+                attempts: STORY_ATTEMPTS,
+                error: format!("it left out {}", missing.join("、")),
+            });
+        }
+        let prompt = story_prompt(&selected_words, options, &missing);
+        let response: StoryResponse = generate_json(&*llm, &prompt, &story_schema(), &mut on_progress).await?;
+        let coverage = check_story_coverage(pool, &response.story, &selected_words, &target).await?;
+        eprintln!("Story attempt {} uses {}/{} words", attempt, coverage.used.len(), selected_words.len());
+
+        missing = coverage.missing.clone();
+        let poor = coverage.is_poor();
+        if best.as_ref().is_none_or(|(_, best)| coverage.ratio() > best.ratio()) {
+            best = Some((response, coverage));
+        }
+        if !poor {
+            break;
+        }
+    }
+
+    let (response, coverage) = best.ok_or("No story was generated.")?;
+    Ok((
+        StoryRecord {
+            story: response.story,
+            english: response.english,
+            words: selected_words,
+            model: llm.model(),
+            jlpt: target,
+            ..Default::default()
+        },
+        coverage,
+    ))
 }
 
 
 
 
 /// This function takes the raw story text and processes it into our structured data.
+/// Words missing from the dictionary are left out.
 pub fn process_story_text(text: &str) -> Result<Vec<WordToken>, Box<dyn std::error::Error>> {
-    let dictionary = load_dictionary_from_kind(lindera::dictionary::DictionaryKind::IPADIC)?;
-    let segmenter = Segmenter::new(Mode::Normal, dictionary, None);
-    let tokenizer = Tokenizer::new(segmenter);
-
-    let mut word_tokens = Vec::new();
-
-
-    let mut tokens = tokenizer.tokenize(&text)?;
-
-
-    for token in tokens.iter_mut() {
-        // This is the key: call .details() to populate the field.
-        // We don't need to use the return value of this function call.
-        token.details();
-
-        if let Some(details) = &token.details {
-            if details.len() > 8 {
-                word_tokens.push(WordToken {
-                    surface: token.text.to_string(),
-                    part_of_speech: details[0].to_string(), // e.g., "名詞"
-                    base_form: details[6].to_string(),      // e.g., "猫"
-                    // Lindera provides Katakana reading, so we convert it to Romaji
-                    romaji: details[8].to_romaji(),
-                });
-            }
-        }
-    }
+    let word_tokens = tokenize(text)?
+        .into_iter()
+        .filter(|token| token.known)
+        .map(|token| WordToken {
+            part_of_speech: token.part_of_speech().to_string(), // e.g., "名詞"
+            reading: token.reading_hiragana(),
+            // Lindera provides Katakana reading, so we convert it to Romaji
+            romaji: token.romaji(),
+            surface: token.surface,
+            base_form: token.base_form, // e.g., "猫"
+        })
+        .collect();
 
     Ok(word_tokens)
 }
@@ -137,66 +304,203 @@ pub fn process_story_text(text: &str) -> Result<Vec<WordToken>, Box<dyn std::err
 pub fn StoryGenerator() -> Element {
     let mut status_message = use_context::<Signal<StatusMessage>>();
 
-    let ollama_client = use_context::<Arc<Ollama>>();
+    let llm = use_context::<Signal<Arc<dyn LlmBackend>>>();
 
     let words_to_use = use_context::<Signal<Vec<String>>>();
+    let db_pool = use_context::<sqlx::SqlitePool>();
+    let navigator = use_navigator();
     
     // State to hold the generated story and its translation
     let mut story_data = use_signal(|| None as Option<(String, String)>);
 
+    // the raw text streamed so far, shown until the story field is complete
+    let mut raw_output = use_signal(String::new);
+    // the request in flight, None when idle
+    let mut current_task = use_signal(|| None::<Task>);
+    let mut options = use_signal(StoryOptions::default);
+    // the requested words the story really uses, known once it is complete
+    let mut coverage = use_signal(|| None::<StoryCoverage>);
+    // id of the story in the library, for the quiz
+    let mut saved_story_id = use_signal(|| None::<i64>);
+
 
     rsx! {
         div { class: "container py-4",
             h2 { "Story Generator" }
             p { class: "text-secondary", "Generate a new story using your practiced words."}
 
+            div { class: "row g-2 mb-3",
+                div { class: "col-md-3",
+                    label { class: "form-label", r#for: "storyLevelSelect", "JLPT level" }
+                    select {
+                        class: "form-select",
+                        id: "storyLevelSelect",
+                        value: options.read().jlpt.clone().unwrap_or_default(),
+                        oninput: move |evt| {
+                            let level = evt.value();
+                            options.write().jlpt = (!level.is_empty()).then_some(level);
+                        },
+                        option { value: "", "Level of your words" }
+                        for level in JLPT_LEVELS {
+                            option { value: level, "{level.to_uppercase()}" }
+                        }
+                    }
+                }
+                div { class: "col-md-3",
+                    label { class: "form-label", r#for: "storyLengthSelect", "Length" }
+                    select {
+                        class: "form-select",
+                        id: "storyLengthSelect",
+                        value: "{options.read().length}",
+                        oninput: move |evt| {
+                            if let Ok(length) = evt.value().parse() {
+                                options.write().length = length;
+                            }
+                        },
+                        option { value: "80", "Short (~80 words)" }
+                        option { value: "150", "Medium (~150 words)" }
+                        option { value: "300", "Long (~300 words)" }
+                    }
+                }
+            }
+
             // --- The "Smart" Button ---
             div { class: "mb-4",
                 button {
                     class: "btn btn-primary",
+                    disabled: current_task.read().is_some(),
                     onclick: move |_| {
 
-                        
-
                         let words = words_to_use.clone();
-                        let client = ollama_client.clone();
+                        let client = llm();
+                        let pool = db_pool.clone();
+                        let story_options = options();
+
+                        eprintln!("Generating story with words: {:?}", words());
+                        if words.is_empty() {
+                            status_message.set(StatusMessage {
+                                message: "No words available to generate a story.".to_string(),
+                                level: StatusLevel::Error,
+                            });
+                            return;
+                        }
 
                         status_message.set(StatusMessage {
-                            message: format!("Waiting for LLM, please be patient..."),
+                            message: format!("Waiting for {}...", client.describe()),
                             level: StatusLevel::Info,
                         });
-                    
-                        async move {
-                            eprintln!("Generating story with words: {:?}", words());
-                            if words.is_empty() {
-                                status_message.set(StatusMessage {
-                                    message: "No words available to generate a story.".to_string(),
-                                    level: StatusLevel::Error,
-                                });
-                                return;
-                            }
-                            match get_story(client, &words()).await {
-                                Ok((story, english)) => {
+                        story_data.set(None);
+                        coverage.set(None);
+                        saved_story_id.set(None);
+                        raw_output.set(String::new());
+
+                        let task = spawn(async move {
+                            let result = get_story(client, &pool, &words(), &story_options, |progress| {
+                                let partial = match progress {
+                                    JsonProgress::Partial(partial) => partial,
+                                    JsonProgress::Retry { attempt, attempts, error } => {
+                                        // start over with the new answer
+                                        story_data.set(None);
+                                        raw_output.set(String::new());
+                                        status_message.set(StatusMessage {
+                                            message: format!("Unusable story ({}), asking again ({}/{})...", error, attempt, attempts),
+                                            level: StatusLevel::Warning,
+                                        });
+                                        return;
+                                    }
+                                };
+                                raw_output.set(partial.to_string());
+                                // show the story as soon as its field is complete, the translation follows
+                                let Some(value) = parse_partial_json(partial) else {
+                                    return;
+                                };
+                                if let Some(story) = value.get("story").and_then(|story| story.as_str()) {
+                                    let english = value.get("english").and_then(|english| english.as_str()).unwrap_or_default();
+                                    let changed = match &*story_data.peek() {
+                                        Some((old_story, old_english)) => old_story != story || old_english != english,
+                                        None => true,
+                                    };
+                                    if changed {
+                                        story_data.set(Some((story.to_string(), english.to_string())));
+                                    }
+                                }
+                            })
+                            .await;
+
+                            match result {
+                                Ok((record, story_coverage)) => {
                                     eprintln!("Story generated successfully.");
-                                    story_data.set(Some((story, english))); // Store the story and translation
+                                    story_data.set(Some((record.story.clone(), record.english.clone()))); // Store the story and translation
+
+                                    // keep it in the story library
+                                    let message = match save_story(&pool, &record).await {
+                                        Ok(id) => {
+                                            saved_story_id.set(Some(id));
+                                            "Story generated successfully and saved to the library.".to_string()
+                                        }
+                                        Err(e) => {
+                                            eprintln!("Error saving story: {}", e);
+                                            "Story generated successfully, but it could not be saved.".to_string()
+                                        }
+                                    };
+                                    let level = if story_coverage.is_poor() {
+                                        StatusLevel::Warning
+                                    } else {
+                                        StatusLevel::Success
+                                    };
                                     status_message.set(StatusMessage {
-                                        message: format!("Story generated successfully."),
-                                        level: StatusLevel::Success,
+                                        message: format!("{} It uses {} of {} words.", message, story_coverage.used.len(), record.words.len()),
+                                        level,
                                     });
+                                    coverage.set(Some(story_coverage));
                                 }
                                 Err(e) => {
                                     eprintln!("Error generating story: {}", e);
+                                    // don't leave a half streamed story without its translation on screen
+                                    story_data.set(None);
                                     status_message.set(StatusMessage {
                                         message: format!("Error generating story: {}", e),
                                         level: StatusLevel::Error,
                                     });
-                                    
                                 }
                             };
-                        }
+                            current_task.set(None);
+                        });
+                        current_task.set(Some(task));
                     },
                     "Generate Story from Recent Words"
                 }
+                button {
+                    class: "btn btn-outline-secondary ms-2",
+                    onclick: move |_| {
+                        navigator.push(Route::StoryLibrary {});
+                    },
+                    "Story Library"
+                }
+                if let Some(story_id) = saved_story_id() {
+                    button {
+                        class: "btn btn-outline-primary ms-2",
+                        onclick: move |_| {
+                            navigator.push(Route::StoryQuiz { story_id });
+                        },
+                        "Take the Quiz"
+                    }
+                }
+                if current_task.read().is_some() {
+                    button {
+                        class: "btn btn-outline-danger ms-2",
+                        onclick: move |_| {
+                            if let Some(task) = current_task.take() {
+                                task.cancel();
+                            }
+                            status_message.set(StatusMessage {
+                                message: "Story generation cancelled.".to_string(),
+                                level: StatusLevel::Warning,
+                            });
+                        },
+                        "Cancel"
+                    }
+                }
                 // You could add a dropdown here for other options
             }
         }
@@ -207,12 +511,42 @@ pub fn StoryGenerator() -> Element {
         // If a story has been generated, render the InteractiveStory component.
         // Otherwise, show a placeholder.
         if let Some((story, english)) = story_data() {
-            
+
+            if let Some(report) = coverage() {
+                div { class: if report.is_poor() { "alert alert-warning" } else { "alert alert-secondary" },
+                    div { "Uses {report.used.len()} of {report.used.len() + report.missing.len()} words." }
+                    if !report.missing.is_empty() {
+                        div { class: "mt-1",
+                            "Missing: "
+                            for word in report.missing.iter() {
+                                span { class: "badge bg-danger me-1", "{word}" }
+                            }
+                        }
+                    }
+                    if !report.above_level.is_empty() {
+                        div { class: "mt-1",
+                            "Above the level: "
+                            for (word, level) in report.above_level.iter() {
+                                span { class: "badge bg-warning text-dark me-1", "{word} ({level})" }
+                            }
+                        }
+                    }
+                }
+            }
+
             InteractiveStory {
                 story_text: story,
                 english_translation: english,
+                highlight: coverage.read().as_ref().map(|report| report.above_level.iter().map(|(word, _)| word.clone()).collect()).unwrap_or_default(),
             }
         
+        } else if current_task.read().is_some() {
+
+            div { class: "p-3 border rounded",
+                div { class: "spinner-border spinner-border-sm text-info me-2", role: "status" }
+                pre { class: "small text-secondary text-wrap mb-0 mt-2", "{raw_output}" }
+            }
+
         } else {
         
             div { class: "text-center text-muted p-5 border rounded",
@@ -225,8 +559,158 @@ pub fn StoryGenerator() -> Element {
     }   
 }
 
+/// Lists the saved stories, newest first, to re-open or delete them.
+#[component]
+pub fn StoryLibrary() -> Element {
+    let navigator = use_navigator();
+    let db_pool = use_context::<sqlx::SqlitePool>();
+    let pool_delete = db_pool.clone();
+    let mut status_message = use_context::<Signal<StatusMessage>>();
+
+    let mut stories = use_resource(move || {
+        let pool = db_pool.clone();
+        async move { list_stories(&pool).await }
+    });
+
+    rsx! {
+        div { class: "container py-4 d-flex flex-column h-75",
+            div { class: "d-flex justify-content-between align-items-center mb-3",
+                h2 { class: "mb-0", "Story Library" }
+                button { class: "btn btn-primary",
+                    onclick: move |_| {
+                        navigator.push(Route::StoryGenerator {});
+                    },
+                    "New Story"
+                }
+            }
+
+            match &*stories.read_unchecked() {
+                Some(Ok(list)) if !list.is_empty() => rsx! {
+                    div { class: "flex-grow-1 overflow-auto",
+                        table { class: "table table-dark table-striped table-hover",
+                            thead {
+                                tr {
+                                    th { "Story" }
+                                    th { "JLPT" }
+                                    th { "Words" }
+                                    th { "Created" }
+                                    th {}
+                                }
+                            }
+                            tbody {
+                                for story in list.clone() {
+                                    tr {
+                                        td {
+                                            Link {
+                                                to: Route::SavedStory { story_id: story.id },
+                                                class: "text-reset text-decoration-none",
+                                                "{story.preview(40)}"
+                                            }
+                                        }
+                                        td { "{story.jlpt}" }
+                                        td { class: "small text-secondary", title: "{story.word_list()}", "{story.words.len()}" }
+                                        td { class: "small text-secondary",
+                                            if story.age_days() == 0 {
+                                                "today"
+                                            } else {
+                                                "{story.age_days()} days ago"
+                                            }
+                                        }
+                                        td { class: "text-end",
+                                            button {
+                                                class: "btn btn-outline-danger btn-sm",
+                                                onclick: {
+                                                    let pool = pool_delete.clone();
+                                                    let id = story.id;
+                                                    move |_| {
+                                                        let pool = pool.clone();
+                                                        async move {
+                                                            if let Err(e) = delete_story(&pool, id).await {
+                                                                eprintln!("Error deleting story: {}", e);
+                                                                status_message.set(StatusMessage {
+                                                                    message: "A database error occurred.".to_string(),
+                                                                    level: StatusLevel::Error,
+                                                                });
+                                                            }
+                                                            stories.restart();
+                                                        }
+                                                    }
+                                                },
+                                                "Delete"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(Ok(_)) => rsx! {
+                    div { class: "text-center text-muted p-5 border rounded",
+                        "No saved stories yet, generated stories are saved here automatically."
+                    }
+                },
+                Some(Err(e)) => rsx! { div { class: "alert alert-danger", "Error loading stories: {e}" } },
+                None => rsx! { p { "Loading stories..." } },
+            }
+        }
+    }
+}
+
+/// Re-opens a story from the library in InteractiveStory.
 #[component]
-pub fn InteractiveStory(story_text: String, english_translation: String) -> Element { 
+pub fn SavedStory(story_id: i64) -> Element {
+    let navigator = use_navigator();
+    let db_pool = use_context::<sqlx::SqlitePool>();
+
+    let story = use_resource(move || {
+        let pool = db_pool.clone();
+        async move { find_story(&pool, story_id).await }
+    });
+
+    rsx! {
+        div { class: "container py-4",
+            div { class: "d-flex justify-content-between align-items-center mb-3",
+                button { class: "btn btn-secondary",
+                    onclick: move |_| {
+                        navigator.push(Route::StoryLibrary {});
+                    },
+                    "Go Back"
+                }
+                button { class: "btn btn-primary",
+                    onclick: move |_| {
+                        navigator.push(Route::StoryQuiz { story_id });
+                    },
+                    "Take the Quiz"
+                }
+            }
+
+            match &*story.read_unchecked() {
+                Some(Ok(Some(story))) => rsx! {
+                    div { class: "mb-3",
+                        if !story.jlpt.is_empty() {
+                            span { class: "badge bg-info text-dark me-2", "{story.jlpt}" }
+                        }
+                        for word in story.words.iter() {
+                            span { class: "badge bg-secondary me-1", "{word}" }
+                        }
+                    }
+                    InteractiveStory {
+                        story_text: story.story.clone(),
+                        english_translation: story.english.clone(),
+                    }
+                },
+                Some(Ok(None)) => rsx! { div { class: "alert alert-warning", "This story was deleted." } },
+                Some(Err(e)) => rsx! { div { class: "alert alert-danger", "Error loading the story: {e}" } },
+                None => rsx! { p { "Loading story..." } },
+            }
+        }
+    }
+}
+
+/// `highlight` lists base forms to underline, e.g. the words above the target level.
+#[component]
+pub fn InteractiveStory(story_text: String, english_translation: String, #[props(default)] highlight: Vec<String>) -> Element { 
 
     // will be used for tts 
     let story = story_text.clone();
@@ -234,6 +718,28 @@ pub fn InteractiveStory(story_text: String, english_translation: String) -> Elem
 
 
     let processed_tokens = process_story_text(&story_text).ok();
+    // the word clicked last, looked up in the words table
+    let mut selected_token = use_signal(|| None::<WordToken>);
+
+    // words shown without furigana because the user knows them
+    let db_pool = use_context::<sqlx::SqlitePool>();
+    let furigana_settings = use_context::<Signal<FuriganaSettings>>();
+    let base_forms: Vec<String> = processed_tokens
+        .iter()
+        .flatten()
+        .map(|token| token.base_form.clone())
+        .collect();
+    let known = use_resource(use_reactive!(|base_forms| {
+        let pool = db_pool.clone();
+        let settings = furigana_settings();
+        async move {
+            known_words(&pool, &base_forms, &settings).await.unwrap_or_else(|e| {
+                eprintln!("Error looking up known words: {}", e);
+                HashSet::new()
+            })
+        }
+    }));
+    let hidden = known.read().clone().unwrap_or_default();
 
     
     rsx! {
@@ -272,11 +778,20 @@ pub fn InteractiveStory(story_text: String, english_translation: String) -> Elem
             }
 
             if let Some(tokens) = processed_tokens.as_ref() {
-                for token in tokens {
+                for token in tokens.clone() {
                     div { class: "word-unit",
+                        style: "cursor: pointer;",
+                        onclick: {
+                            let token = token.clone();
+                            move |_| selected_token.set(Some(token.clone()))
+                        },
                         div {
                             class: "tooltip-container",
-                            span { class: "main-word {get_pos_color_class(&PartOfSpeech::from(&*token.part_of_speech.as_str()))}", "{token.surface}" }
+                            span {
+                                class: "main-word {get_pos_color_class(&PartOfSpeech::from(&*token.part_of_speech.as_str()))}",
+                                class: if highlight.contains(&token.base_form) { "border-bottom border-warning" },
+                                Ruby { segments: token.ruby(), hidden: hidden.clone() }
+                            }
                             span {
                                 class: "tooltip-text",
                                 "Type: {token.part_of_speech}\nBase: {token.base_form}"
@@ -287,6 +802,13 @@ pub fn InteractiveStory(story_text: String, english_translation: String) -> Elem
                 }
             }
         }
+
+        if let Some(token) = selected_token() {
+            WordLookupPanel {
+                token,
+                on_close: move |_| selected_token.set(None),
+            }
+        }
     }
 }
 
@@ -295,6 +817,69 @@ pub fn InteractiveStory(story_text: String, english_translation: String) -> Elem
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::*;
+    use crate::llm::MockBackend;
+
+    async fn pool_with_words() -> sqlx::SqlitePool {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![test_word("食べる", "", "", "n5"), test_word("図書館", "", "", "n4"), test_word("概念", "", "", "n1"), test_word("泳ぐ", "", "", "n5")]).await.unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_story_coverage() {
+        let pool = pool_with_words().await;
+        let requested = vec!["食べる".to_string(), "図書館".to_string(), "泳ぐ".to_string()];
+        let coverage = check_story_coverage(&pool, "私は図書館でりんごを食べました。概念は難しい。", &requested, "n4").await.unwrap();
+        assert_eq!(coverage.used, vec!["食べる", "図書館"]);
+        assert_eq!(coverage.missing, vec!["泳ぐ"]);
+        assert_eq!(coverage.above_level, vec![("概念".to_string(), "n1".to_string())]);
+        assert!(coverage.is_poor());
+
+        // a word only counts as a whole token, not inside a longer word
+        let coverage = check_story_coverage(&pool, "人間は毎日勉強します。", &["人".to_string(), "勉強する".to_string()], "n4").await.unwrap();
+        assert_eq!(coverage.used, vec!["勉強する"]);
+        assert_eq!(coverage.missing, vec!["人"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_story_regenerates_poor_coverage() {
+        let pool = pool_with_words().await;
+        let mock = Arc::new(MockBackend::new(vec![
+            r#"{"story": "図書館は静かです。", "english": "The library is quiet."}"#.to_string(),
+            r#"{"story": "図書館で泳ぐ夢を見ました。", "english": "I dreamed of swimming in the library."}"#.to_string(),
+        ]));
+        // 概念 is above N4 and is never picked
+        let words = vec!["図書館".to_string(), "泳ぐ".to_string(), "概念".to_string()];
+        let options = StoryOptions { jlpt: Some("n4".to_string()), length: 80, word_count: 2 };
+
+        let mut retries = 0;
+        let (record, coverage) = get_story(mock.clone(), &pool, &words, &options, |progress| {
+            if let JsonProgress::Retry { .. } = progress {
+                retries += 1;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(record.english, "I dreamed of swimming in the library.");
+        assert_eq!(record.jlpt, "n4");
+        assert_eq!(coverage.missing, Vec::<String>::new());
+        assert_eq!(retries, 1);
+        assert!(mock.prompts()[0].contains("JLPT N4"));
+        assert!(mock.prompts()[1].contains("did not use these words: 泳ぐ"));
+
+        let options = StoryOptions { word_count: 3, ..options };
+        assert!(get_story(mock, &pool, &words, &options, |_| {}).await.is_err());
+
+        // without a level the hardest listed word sets it, so all three can be picked
+        let mock = Arc::new(MockBackend::new(vec![
+            r#"{"story": "図書館で泳ぐ概念を学びました。", "english": "I learned the concept of swimming at the library."}"#.to_string(),
+        ]));
+        let options = StoryOptions { jlpt: None, ..options };
+        let (record, coverage) = get_story(mock.clone(), &pool, &words, &options, |_| {}).await.unwrap();
+        assert_eq!((record.jlpt.as_str(), coverage.used.len()), ("n1", 3));
+        assert!(mock.prompts()[0].contains("JLPT N1"));
+    }
 
     #[test]
     fn test_process_story() {
@@ -7,12 +7,17 @@ use crate::Route;
 use tts::*;
 use crate::return_voice;
 use crate::utils::speak_text;
+use crate::review_log::{log_review, ReviewEntry, ReviewMode};
+use crate::scheduler::{grade_word, Grade};
+use crate::pitch::{pitch_accents, PitchReading};
+use crate::recall::RecallAnswer;
+use crate::distractor::pick_distractors;
+use crate::wordexplain_gemma::cached_explanation;
 
 use futures_util::StreamExt;
+use std::time::Instant;
 use rand::seq::SliceRandom;
 use rand::{rng, Rng};
-// Use the IndexedRandom trait for random selection
-use rand::prelude::IndexedRandom;
 
 /// This the componenet used to generate test for the selected words
 #[component]
@@ -25,6 +30,10 @@ pub fn GnerateTestCard() -> Element {
 
 
     let mut j_to_e= use_signal(|| true);
+    // "choice" for multiple choice, otherwise the RecallAnswer typed in
+    let mut answer_by = use_signal(|| "choice".to_string());
+    // play the words instead of showing them
+    let mut listening = use_signal(|| false);
 
 
     // let db_pool = use_context::<sqlx::SqlitePool>();
@@ -45,7 +54,7 @@ pub fn GnerateTestCard() -> Element {
                     "Go Back" }
                 }
             }
-            // 2nd Row: Number of Cards and jToE Checkbox
+            // 2nd Row: Number of Cards, how to answer and jToE Checkbox
             div { class: "row mb-3 g-3 align-items-end", // g-3 for gutters between columns
                 div { class: "col-md-6", // Takes half width on medium screens and up
                     label { class: "form-label", r#for: "numCardsSelect", "Number of Cards:" }
@@ -69,17 +78,45 @@ pub fn GnerateTestCard() -> Element {
                         }
                     }
                 
-                div { class: "col-md-6",
+                div { class: "col-md-3",
+                    label { class: "form-label", r#for: "answerBySelect", "Answer by:" }
+                    select {
+                        class: "form-select",
+                        id: "answerBySelect",
+                        value: "{answer_by}",
+                        oninput: move |evt| answer_by.set(evt.value()),
+                        option { value: "choice", "Multiple choice" }
+                        option { value: RecallAnswer::Reading.to_string(), "Typing the reading" }
+                        option { value: RecallAnswer::Meaning.to_string(), "Typing the meaning" }
+                        option { value: "cloze-choice", "Sentence blanks, multiple choice" }
+                        option { value: "cloze-typing", "Sentence blanks, typing" }
+                    }
+                }
+                div { class: "col-md-3",
                         div { class: "form-check",
                             input {
                                 class: "form-check-input",
                                 r#type: "checkbox",
                                 id: "jToECheck",
+                                // typed answers always show the Japanese side
+                                disabled: answer_by() != "choice",
                                 checked: j_to_e(),
                                 oninput: move |evt| j_to_e.set(evt.checked()),
                             }
                             label { class: "form-check-label", r#for: "jToECheck", "J to E" }
                         }
+                        div { class: "form-check",
+                            input {
+                                class: "form-check-input",
+                                r#type: "checkbox",
+                                id: "listeningCheck",
+                                // sentence blanks are read
+                                disabled: answer_by().starts_with("cloze"),
+                                checked: listening(),
+                                oninput: move |evt| listening.set(evt.checked()),
+                            }
+                            label { class: "form-check-label", r#for: "listeningCheck", "Listening" }
+                        }
                 }
             }
 
@@ -113,7 +150,14 @@ pub fn GnerateTestCard() -> Element {
                                 message: "Generating test cards successfully".to_string(),
                                 level: StatusLevel::Info,
                             });
-                            navigator.push(Route::TestCard { j_to_e: j_to_e() });
+                            match (answer_by().as_str(), answer_by().parse::<RecallAnswer>(), listening()) {
+                                ("cloze-choice", _, _) => navigator.push(Route::ClozeTest { typing: false }),
+                                ("cloze-typing", _, _) => navigator.push(Route::ClozeTest { typing: true }),
+                                (_, Ok(answer), false) => navigator.push(Route::RecallCard { answer }),
+                                (_, Ok(answer), true) => navigator.push(Route::ListeningRecall { answer }),
+                                (_, Err(_), false) => navigator.push(Route::TestCard { j_to_e: j_to_e() }),
+                                (_, Err(_), true) => navigator.push(Route::ListeningCard { j_to_e: j_to_e() }),
+                            };
 
 
                         },
@@ -132,18 +176,19 @@ pub fn GnerateTestCard() -> Element {
 #[derive(Debug, Clone, Copy)]
 enum TestcardAction {
     ShowQuestion,
-    MarkFamiliar,
-    MarkUnfamiliar,
+    Grade(Grade),
     UserMark,
     UserPickAnswer(usize),
     Pronounce,
+    PronounceExample,
 }
 
 
 /// this component is the main test card interface
 /// It's most the same as flashcard, but with multiple choice answers
+/// When `listening`, the word is played instead of shown until an answer is picked.
 #[component]
-pub fn TestCard(j_to_e: bool) -> Element {
+pub fn TestCard(j_to_e: bool, #[props(default)] listening: bool) -> Element {
     let navigator = use_navigator();
     let mut index = use_signal(|| 0 as usize); // current index in select_words
     let select_words = use_context::<Signal<Vec<WordRecord>>>();
@@ -155,21 +200,55 @@ pub fn TestCard(j_to_e: bool) -> Element {
     let mut show_reading = use_signal(|| true);
     let mut show_answer = use_signal(|| false);
     let mut is_marked = use_signal(|| false);
+    let mut card_shown_at = use_signal(Instant::now); // used to measure the response time
     let mut selected_answer = use_signal(|| None as Option<usize>);
     let mut correct_answer_index = use_signal(|| 0 as usize);
     
     // --- content for UI ---
     let mut question = use_signal(|| "".to_string());
     let mut reading = use_signal(|| "".to_string());
-    let mut vector_of_answers = use_signal(|| vec!["".to_string(); 4]);
+    let mut vector_of_answers = use_signal(Vec::<String>::new); // up to 4, fewer for a small words table
     let mut score = use_signal(|| 0);
 
     // --- pool for db op ---
     let db_pool = use_context::<sqlx::SqlitePool>();
     let pool_action = db_pool.clone(); // pool for km_actions
+    let pool_pitch = db_pool.clone(); // pool for the pitch accent lookup
+    let pool_examples = db_pool.clone(); // pool for the example sentences
+    let pool_distractors = db_pool.clone(); // pool for picking the wrong answers
+
+    // pitch accents of the current card, empty when none were imported
+    let accents = use_resource(move || {
+        let pool = pool_pitch.clone();
+        async move {
+            let Some(word) = select_words.read().get(index()).cloned() else {
+                return Vec::new();
+            };
+            pitch_accents(&pool, &word.expression, &word.reading).await.unwrap_or_default()
+        }
+    });
+
+    // example sentences of the current card from its cached explanation, played when listening
+    let examples = use_resource(move || {
+        let pool = pool_examples.clone();
+        async move {
+            let Some(word) = select_words.read().get(index()).cloned() else {
+                return Vec::new();
+            };
+            if !listening {
+                return Vec::new();
+            }
+            match cached_explanation(&pool, &word.expression).await {
+                Ok(Some(cached)) => cached.examples.into_iter().map(|example| example.sentence).collect(),
+                _ => Vec::new(),
+            }
+        }
+    });
+    let mut example_index = use_signal(|| 0_usize);
 
     // --- voice for tts ---
     let voice = return_voice("ja", Gender::Male)?;
+    let voice_load = voice.clone(); // used to play the word when a card is loaded
 
     // --- setup button class and icon for user_mark
     let button_class = if is_marked() {
@@ -182,61 +261,50 @@ pub fn TestCard(j_to_e: bool) -> Element {
     // 1. --- Create a reusable closure to load a card ---
     // This closure takes the index of the card to load.
     let mut load_card = move |card_index: usize| {
-        if let Some(word) = select_words.get(card_index) {
+        if let Some(word) = select_words.read().get(card_index).cloned() {
             eprintln!("Loading card at index {}: {:?}", card_index, word);
             reading.set(word.reading.clone());
             is_marked.set(word.user_mark);
-
-            // randomly select an the correct answer index from 0 to 3
-            let idx = rng().random_range(0..=3);
-            correct_answer_index.set(idx);
-            eprintln!("the correct answer index is: {}", correct_answer_index());
-
-            // pick the rest of the answers from the select_words
-            // Exclude the current word index from the selection
-            let mut possible_numbers: Vec<usize> = (0..=select_words.len() - 1).collect();
-            possible_numbers.retain(|&x| x != card_index); // Exclude the current word index
-            
-            // from the possible numbers, randomly select 3 indices
-            let mut rngen = rng();
-            let mut chosen_numbers: Vec<usize> = possible_numbers
-                .choose_multiple(&mut rngen, 3) // Choose 3 random indices
-                .cloned() // Clone the &usize references into usize values
-                .collect(); // Collect the values into a new Vec
-
-            eprintln!("Chosen numbers for other answers: {:?}", chosen_numbers);
+            card_shown_at.set(Instant::now());
+            example_index.set(0);
+            if listening {
+                let spoken = if word.reading.is_empty() { word.expression.clone() } else { word.reading.clone() };
+                speak_text(spoken, voice_load.clone(), 5, Some(1.1), Some(0.9));
+            }
 
             if j_to_e {
                 question.set(word.expression.clone());
-
-                // Fill the correct answer at the random index
-                vector_of_answers.write()[correct_answer_index()] = word.meaning.clone();
-                for i in 0..4 {
-                    if i != correct_answer_index() {
-              
-                        let idx = chosen_numbers.pop().unwrap();
-                        let random_word = select_words.read()[idx].clone();
-                        vector_of_answers.write()[i] = random_word.meaning.clone();
-                    }
-                }
-                
             } else {
                 question.set(word.meaning.clone());
-
-                vector_of_answers.write()[correct_answer_index()] = word.expression.clone();
-                for i in 0..4 {
-                    if i != correct_answer_index() {
-              
-                        let idx = chosen_numbers.pop().unwrap();
-                        let random_word = select_words.read()[idx].clone();
-                        vector_of_answers.write()[i] = random_word.expression.clone();
-                    }
-                }
             }
-            eprintln!("vector_of_answers: {:?}", vector_of_answers());
-            // eprintln!("4th answer: {}", vector_of_answers()[3]);
             // Always hide the answer when loading a new card
             show_answer.set(false);
+            selected_answer.set(None);
+            vector_of_answers.set(Vec::new());
+
+            // pick the wrong answers from the words table, a small table gives fewer choices
+            let pool = pool_distractors.clone();
+            spawn(async move {
+                let distractors = match pick_distractors(&pool, &word, 3).await {
+                    Ok(distractors) => distractors,
+                    Err(e) => {
+                        eprintln!("Failed to pick the other answers: {}", e);
+                        Vec::new()
+                    }
+                };
+                if index() != card_index {
+                    return; // the user already moved on
+                }
+                let answer_of = |word: &WordRecord| if j_to_e { word.meaning.clone() } else { word.expression.clone() };
+                let mut answers: Vec<String> = distractors.iter().map(answer_of).collect();
+
+                // put the correct answer at a random place
+                let idx = rng().random_range(0..=answers.len());
+                answers.insert(idx, answer_of(&word));
+                correct_answer_index.set(idx);
+                eprintln!("answers: {:?}, the correct one is {}", answers, idx);
+                vector_of_answers.set(answers);
+            });
         }
     };
 
@@ -291,45 +359,28 @@ pub fn TestCard(j_to_e: bool) -> Element {
                         eprintln!("Showing question for word {}", word_id);
                         show_question.set(!show_question());
                     }
-                    TestcardAction::MarkUnfamiliar => {
+                    TestcardAction::Grade(grade) => {
                         if !show_answer() {
-                            eprintln!("Cannot mark as 'Needs Practice' without showing the answer first");
+                            eprintln!("Cannot grade the word without showing the answer first");
                             status_message.set(StatusMessage {
-                                message: "Please show the answer before marking as 'Needs Practice'.".to_string(),
+                                message: "Please pick an answer before grading the word.".to_string(),
                                 level: StatusLevel::Warning,
                             });
                             continue; // Skip if answer is not shown
                         }
-                        eprintln!("Marking word {} as 'Needs Practice'", word_id);
-                        match ProgressUpdate::new()
-                            .increment_practice_time()
-                            .set_familiar(false)
-                            .execute(&pool, word_id)
-                            .await
-                        {
-                            Ok(_) => eprintln!("id: {:?} updated unfamiliar successfully", word_id),
-                            Err(e) => eprintln!("Background update failed: {}", e),
-                        }
-                         go_to_next_card();
-                    }
-                    TestcardAction::MarkFamiliar => {
-                        if !show_answer() {
-                            eprintln!("Cannot mark as 'Got It!' without showing the answer first");
-                            status_message.set(StatusMessage {
-                                message: "Please show the answer before marking as 'Got It!'.".to_string(),
-                                level: StatusLevel::Warning,
-                            });
-                            continue; // Skip if answer is not shown
+                        eprintln!("Grading word {} as {:?}", word_id, grade);
+                        // listening is logged on its own and leaves the schedule to the reading based reviews
+                        if !listening {
+                            match grade_word(&pool, word_id, grade).await {
+                                Ok(state) => eprintln!("id: {:?} graded, next review in {} days", word_id, state.interval_days),
+                                Err(e) => eprintln!("Background update failed: {}", e),
+                            }
                         }
-                     
-                        match ProgressUpdate::new()
-                            .increment_practice_time()
-                            .set_familiar(true)
-                            .execute(&pool, word_id)
-                            .await
-                        {
-                            Ok(_) => eprintln!("id: {:?} updated familiar successfully", word_id),
-                            Err(e) => eprintln!("Background update failed: {}", e),
+                        let mode = if listening { ReviewMode::Listening } else { ReviewMode::Test };
+                        let response_ms = card_shown_at().elapsed().as_millis() as i64;
+                        let entry = ReviewEntry::new(word_id, mode, j_to_e, grade, response_ms);
+                        if let Err(e) = log_review(&pool, &entry).await {
+                            eprintln!("Failed to log review: {}", e);
                         }
                          go_to_next_card();
                     }
@@ -350,6 +401,9 @@ pub fn TestCard(j_to_e: bool) -> Element {
                             eprintln!("Answer already displayed for word {}", word_id);
                             continue; // Skip if answer is already shown
                         }
+                        if selected >= vector_of_answers.len() {
+                            continue; // fewer choices than the key pressed
+                        }
                         eprintln!("Displaying answer for word {}", word_id);
                         selected_answer.set(Some(selected));
                         show_answer.set(true);
@@ -362,13 +416,31 @@ pub fn TestCard(j_to_e: bool) -> Element {
                     TestcardAction::Pronounce => {
                         eprintln!("Pronouncing word {}", word_id);
 
-                        let text_to_speak = reading(); // Clone the text to speak
+                        // kana words have no reading, their expression is read
+                        let text_to_speak = select_words
+                            .get(index())
+                            .map(|word| if word.reading.is_empty() { word.expression.clone() } else { word.reading.clone() })
+                            .unwrap_or_default();
                         let voice_to_use = voice_to_use.clone(); // Clone the voice configuration
                         
                         // --- SPAWN THE THREAD ---
                         // This moves the entire block of work to a background thread.
                         speak_text(text_to_speak, voice_to_use, 5, Some(1.1), Some(0.9));
                     }
+                    TestcardAction::PronounceExample => {
+                        let sentences = examples().unwrap_or_default();
+                        if sentences.is_empty() {
+                            status_message.set(StatusMessage {
+                                message: "No example sentence yet, explain the word once to get some.".to_string(),
+                                level: StatusLevel::Info,
+                            });
+                            continue;
+                        }
+                        // each press plays the next example
+                        let sentence = sentences[example_index() % sentences.len()].clone();
+                        example_index.set(example_index() + 1);
+                        speak_text(sentence, voice_to_use.clone(), 10, Some(1.0), Some(0.9));
+                    }
                 }
 
 
@@ -420,13 +492,21 @@ pub fn TestCard(j_to_e: bool) -> Element {
                         km_actions.send(TestcardAction::ShowQuestion);
                     },
                     Key::Character(s) if s.eq_ignore_ascii_case("n") => {
-                        eprintln!("n key pressed, marking as unfamiliar");
-                        km_actions.send(TestcardAction::MarkUnfamiliar);
+                        eprintln!("n key pressed, grading as again");
+                        km_actions.send(TestcardAction::Grade(Grade::Again));
+                    },
+                    Key::Character(s) if s.eq_ignore_ascii_case("h") => {
+                        eprintln!("h key pressed, grading as hard");
+                        km_actions.send(TestcardAction::Grade(Grade::Hard));
                     },
                     // Add a key for the second button, e.g., 'G' for "Got it!"
                     Key::Character(s) if s.eq_ignore_ascii_case("g") => {
-                        eprintln!("g key pressed, marking as familiar");
-                        km_actions.send(TestcardAction::MarkFamiliar);
+                        eprintln!("g key pressed, grading as good");
+                        km_actions.send(TestcardAction::Grade(Grade::Good));
+                    },
+                    Key::Character(s) if s.eq_ignore_ascii_case("e") => {
+                        eprintln!("e key pressed, grading as easy");
+                        km_actions.send(TestcardAction::Grade(Grade::Easy));
                     },
                     Key::Character(s) if s.eq_ignore_ascii_case("m") => {
                         eprintln!("m key pressed, toggling user mark");
@@ -452,6 +532,10 @@ pub fn TestCard(j_to_e: bool) -> Element {
                         eprintln!("p key pressed, pronouncing word");
                         km_actions.send(TestcardAction::Pronounce);
                     },
+                    Key::Character(s) if listening && s.eq_ignore_ascii_case("s") => {
+                        eprintln!("s key pressed, pronouncing an example sentence");
+                        km_actions.send(TestcardAction::PronounceExample);
+                    },
                     // Ignore any other key presses
                     _ => {}
                 }
@@ -487,7 +571,8 @@ pub fn TestCard(j_to_e: bool) -> Element {
                 div { class: "col d-flex justify-content-between align-items-center",
             
                     p { class: "lead my-3",
-                        if show_question() { "{question()}" } else { "" }
+                        // when listening the question is only shown with the answer
+                        if show_question() && (!listening || show_answer()) { "{question()}" } else { "" }
                     }
 
                     button { 
@@ -497,9 +582,24 @@ pub fn TestCard(j_to_e: bool) -> Element {
                     }
                 }
 
-                if show_reading() {
+                if listening {
+                    div { class: "col d-flex gap-3 align-items-center",
+                        button { class: "btn btn-light",
+                            onclick: move |_| km_actions.send(TestcardAction::Pronounce),
+                            "🔊 ", u {"P"}, "lay again"
+                        }
+                        button { class: "btn btn-outline-light",
+                            onclick: move |_| km_actions.send(TestcardAction::PronounceExample),
+                            "🔊 Example ", u {"s"}, "entence"
+                        }
+                    }
+                }
+
+                if show_reading() && (!listening || show_answer()) {
                     div { class: "col d-flex justify-content-between align-items-center",
-                        p { class: "lead my-3", "{reading()}" }
+                        p { class: "lead my-3",
+                            PitchReading { reading: reading(), accents: accents().unwrap_or_default() }
+                        }
                         button { class: "btn btn-light",
                             onclick: move |_| km_actions.send(TestcardAction::Pronounce),
                             "🔊"}
@@ -511,7 +611,7 @@ pub fn TestCard(j_to_e: bool) -> Element {
              
                         div { class: "d-flex flex-column gap-3",
 
-                            for i in 0..4 {                                
+                            for i in 0..vector_of_answers.len() {
                                 div {
                                     class: get_card_class(i),
                                     style: "cursor: pointer;",
@@ -560,16 +660,32 @@ pub fn TestCard(j_to_e: bool) -> Element {
             div { class: "row my-3",
                 
                 div { class: "col",
-                    button { class: "btn btn-warning w-100", 
-                    onclick: move |_| km_actions.send(TestcardAction::MarkUnfamiliar),
+                    button { class: "btn btn-danger w-100", 
+                    onclick: move |_| km_actions.send(TestcardAction::Grade(Grade::Again)),
                     u {"N"}, "eed more practice" }
                 }
+                div { class: "col",
+                    button { class: "btn btn-warning w-100", 
+                    onclick: move |_| km_actions.send(TestcardAction::Grade(Grade::Hard)),
+                    u {"H"}, "ard" }
+                }
                 div { class: "col",
                     button { class: "btn btn-success w-100", 
-                    onclick: move |_| km_actions.send(TestcardAction::MarkFamiliar),                 
+                    onclick: move |_| km_actions.send(TestcardAction::Grade(Grade::Good)),                 
                     u {"G"}, "ot it!" }
                 }
+                div { class: "col",
+                    button { class: "btn btn-info w-100", 
+                    onclick: move |_| km_actions.send(TestcardAction::Grade(Grade::Easy)),                 
+                    u {"E"}, "asy" }
+                }
             }
         }
     )
-}
\ No newline at end of file
+}
+
+/// The test card for listening, the word is played and the choices are its meaning (J to E) or its expression.
+#[component]
+pub fn ListeningCard(j_to_e: bool) -> Element {
+    rsx! { TestCard { j_to_e, listening: true } }
+}
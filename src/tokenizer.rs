@@ -0,0 +1,155 @@
+use std::error::Error;
+use std::sync::OnceLock;
+
+use lindera::dictionary::{load_dictionary_from_kind, DictionaryKind};
+use lindera::mode::Mode;
+use lindera::segmenter::Segmenter;
+use lindera::tokenizer::Tokenizer;
+use wana_kana::ConvertJapanese;
+
+/// A word of a segmented text with the IPADIC analysis of it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Token {
+    /// The word as it appears in the text, e.g. "食べ".
+    pub surface: String,
+    /// Part of speech followed by its subtypes, e.g. ["名詞", "固有名詞", "地域"], without the "*" fillers.
+    pub pos: Vec<String>,
+    /// e.g. "一段", empty for words that don't conjugate.
+    pub conjugation_type: String,
+    /// e.g. "連用形", empty for words that don't conjugate.
+    pub conjugation_form: String,
+    /// The dictionary form, e.g. "食べる". The surface for unknown words.
+    pub base_form: String,
+    /// Reading of the surface in katakana, e.g. "タベ".
+    pub reading: String,
+    /// How the surface is pronounced in katakana, e.g. "ワ" for the particle "は".
+    pub pronunciation: String,
+    /// False when the word is not in the dictionary, it then only has a surface.
+    pub known: bool,
+}
+
+impl Token {
+    /// The top level part of speech, e.g. "動詞", empty for unknown words.
+    pub fn part_of_speech(&self) -> &str {
+        self.pos.first().map(String::as_str).unwrap_or("")
+    }
+
+    pub fn reading_hiragana(&self) -> String {
+        self.reading.to_hiragana()
+    }
+
+    pub fn romaji(&self) -> String {
+        self.pronunciation.to_romaji()
+    }
+
+    /// Builds a token from the surface and the IPADIC details:
+    /// POS, 3 POS subtypes, conjugation type and form, base form, reading, pronunciation.
+    fn from_details(surface: &str, details: &[&str]) -> Self {
+        if details.len() <= 8 {
+            return Token {
+                surface: surface.to_string(),
+                base_form: surface.to_string(),
+                ..Default::default()
+            };
+        }
+        let field = |value: &str| if value == "*" { String::new() } else { value.to_string() };
+        Token {
+            surface: surface.to_string(),
+            pos: details[..4].iter().filter(|pos| **pos != "*").map(|pos| pos.to_string()).collect(),
+            conjugation_type: field(details[4]),
+            conjugation_form: field(details[5]),
+            base_form: if details[6] == "*" { surface.to_string() } else { details[6].to_string() },
+            reading: field(details[7]),
+            pronunciation: field(details[8]),
+            known: true,
+        }
+    }
+}
+
+fn build_tokenizer() -> Result<Tokenizer, Box<dyn Error>> {
+    let dictionary = load_dictionary_from_kind(DictionaryKind::IPADIC)?;
+    let segmenter = Segmenter::new(Mode::Normal, dictionary, None);
+    Ok(Tokenizer::new(segmenter))
+}
+
+/// The IPADIC tokenizer shared by the whole app, the dictionary is loaded on first use.
+fn shared_tokenizer() -> Result<&'static Tokenizer, Box<dyn Error>> {
+    static TOKENIZER: OnceLock<Result<Tokenizer, String>> = OnceLock::new();
+    TOKENIZER
+        .get_or_init(|| build_tokenizer().map_err(|e| e.to_string()))
+        .as_ref()
+        .map_err(|e| format!("Could not load the IPADIC dictionary: {}", e).into())
+}
+
+/// Segments Japanese text into words with the shared tokenizer.
+pub fn tokenize(text: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    tokenize_with(shared_tokenizer()?, text)
+}
+
+fn tokenize_with(tokenizer: &Tokenizer, text: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let tokens = tokenizer
+        .tokenize(text)?
+        .into_iter()
+        .map(|mut token| {
+            let surface = token.text.to_string();
+            Token::from_details(&surface, &token.details())
+        })
+        .collect();
+    Ok(tokens)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("東京で食べました。").unwrap();
+        let surfaces: Vec<&str> = tokens.iter().map(|t| t.surface.as_str()).collect();
+        assert_eq!(surfaces, vec!["東京", "で", "食べ", "まし", "た", "。"]);
+
+        let tokyo = &tokens[0];
+        assert_eq!(tokyo.pos, vec!["名詞", "固有名詞", "地域", "一般"]);
+        assert_eq!(tokyo.conjugation_type, "");
+
+        let tabe = &tokens[2];
+        assert_eq!(tabe.part_of_speech(), "動詞");
+        assert_eq!(tabe.conjugation_type, "一段");
+        assert_eq!(tabe.conjugation_form, "連用形");
+        assert_eq!(tabe.base_form, "食べる");
+        assert_eq!(tabe.reading_hiragana(), "たべ");
+        assert_eq!(tabe.romaji(), "tabe");
+        assert!(tabe.known);
+
+        // the particle は is pronounced わ
+        let wa = &tokenize("私は").unwrap()[1];
+        assert_eq!((wa.reading.as_str(), wa.pronunciation.as_str()), ("ハ", "ワ"));
+    }
+
+    /// Loading IPADIC dominates the cost of tokenizing a sentence, the shared tokenizer pays it once.
+    /// Timing based, run it on demand with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn bench_shared_tokenizer() {
+        let text = "毎日、私は図書館で赤いりんごを食べます。";
+        let runs = 5;
+        tokenize(text).unwrap();
+
+        let start = Instant::now();
+        for _ in 0..runs {
+            tokenize_with(&build_tokenizer().unwrap(), text).unwrap();
+        }
+        let fresh = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..runs {
+            tokenize(text).unwrap();
+        }
+        let shared = start.elapsed();
+
+        eprintln!("{} runs: new tokenizer each time {:?}, shared tokenizer {:?}", runs, fresh, shared);
+        assert!(shared * 10 < fresh, "shared {:?} is not much faster than fresh {:?}", shared, fresh);
+    }
+}
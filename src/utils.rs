@@ -1,11 +1,7 @@
-// use lindera::LinderaResult;
-use lindera::dictionary::load_dictionary_from_kind;
-use lindera::mode::Mode;
-use lindera::segmenter::Segmenter;
-use lindera::tokenizer::Tokenizer;
-
 use wana_kana::ConvertJapanese;
 
+use crate::tokenizer::tokenize;
+
 use serde::Deserialize;
 use std::fmt;
 use tts::*;
@@ -73,7 +69,7 @@ pub fn get_pos_color_class(pos: &PartOfSpeech) -> &'static str {
     }
 }
 
-/// after llm output, use extract_json_from_llm_output to extract the json and put it to this struct
+/// after llm output, use llm_json::repair_json to extract the json and put it to this struct
 #[derive(Deserialize, Debug)]
 pub struct Story {
     pub story: String,
@@ -84,37 +80,11 @@ pub struct Story {
 /// it use lindera and IPADIC dic to analyze the Japanese text and return pronounciate in katakana
 /// then use wana_kana to convert it to romaji
 pub fn romaji_pronounciation(text: &String) -> Result<String, Box<dyn std::error::Error>> {
-    let dictionary = load_dictionary_from_kind(lindera::dictionary::DictionaryKind::IPADIC)?;
-    let segmenter = Segmenter::new(Mode::Normal, dictionary, None);
-    let tokenizer = Tokenizer::new(segmenter);
-
-    let mut romaji_words: Vec<String> = Vec::new();
-    let mut tokens = tokenizer.tokenize(&text)?;
-
-    // the data we want is in the details field, index 8, 
-    // but to access it, we have to call token.details() 1st, then it'll populate the details field
-    for token in tokens.iter_mut() {
-        // This is the key: call .details() to populate the field.
-        // We don't need to use the return value of this function call.
-        token.details();
-
-        // Now that the field is populated, we can safely access it.
-        // This `if let` will always succeed after the call above.
-        if let Some(details) = &token.details {
-            // Check if the pronunciation field exists (IPADIC has 9+ fields)
-            eprintln!("surface form: {}", token.text);
-            eprintln!("Token details: {:?}", details);
-            if details.len() > 8 {
-                let pronunciation_katakana = &details[8];
-
-                // Convert this specific token's pronunciation to Romaji using wana_kana's trait for String.
-                let romaji = pronunciation_katakana.to_romaji();
-                
-                // Add the resulting Romaji to our vector
-                romaji_words.push(romaji);
-            }
-        }
-    }
+    let romaji_words: Vec<String> = tokenize(text)?
+        .iter()
+        .filter(|token| token.known)
+        .map(|token| token.romaji())
+        .collect();
 
     let final_romaji_spaced = romaji_words.join(" ");
 
@@ -128,39 +98,12 @@ pub fn romaji_pronounciation(text: &String) -> Result<String, Box<dyn std::error
 /// jlpt word process, it will segment the text into words using lindera,
 /// and return a tuple of (verb_origins, romaji_pronounciation)
 pub fn word_process(text: &String) -> Result<(Vec<String>, String), Box<dyn std::error::Error>> {
-    let dictionary = load_dictionary_from_kind(lindera::dictionary::DictionaryKind::IPADIC)?;
-    let segmenter = Segmenter::new(Mode::Normal, dictionary, None);
-    let tokenizer = Tokenizer::new(segmenter);
-
     let mut verb_origins: Vec<String> = Vec::new();
     let mut romaji_words: Vec<String> = Vec::new();
-    let mut tokens = tokenizer.tokenize(&text)?;
-
-    // the data we want is in the details field, index 8, 
-    // but to access it, we have to call token.details() 1st, then it'll populate the details field
-    for token in tokens.iter_mut() {
-        // This is the key: call .details() to populate the field.
-        // We don't need to use the return value of this function call.
-        token.details();
-
-        // Now that the field is populated, we can safely access it.
-        // This `if let` will always succeed after the call above.
-        if let Some(details) = &token.details {
-            // Check if the pronunciation field exists (IPADIC has 9+ fields)
-            eprintln!("Token details: {:?}", details);
-            if details.len() > 8 {
-                let verb_origin = &details[6];
-                verb_origins.push(verb_origin.to_string());
-
-                let pronunciation_katakana = &details[8];
-
-                // Convert this specific token's pronunciation to Romaji using wana_kana's trait for String.
-                let romaji = pronunciation_katakana.to_romaji();
-                
-                // Add the resulting Romaji to our vector
-                romaji_words.push(romaji);
-            }
-        }
+
+    for token in tokenize(text)?.into_iter().filter(|token| token.known) {
+        romaji_words.push(token.romaji());
+        verb_origins.push(token.base_form);
     }
 
     let final_romaji_spaced = romaji_words.join(" ");
@@ -171,44 +114,159 @@ pub fn word_process(text: &String) -> Result<(Vec<String>, String), Box<dyn std:
 
 }
 
-/// Extract JSON from the messy llm output,
-/// the output is expected to contains a JSON with two keys,
-/// story and english, 
-/// story: a Japanese short story
-/// english: the english translation of the story.
-/// if it cannot find JSON, return None
-pub fn extract_json_from_llm_output(text: &str) -> Option<String> {
-    let mut first_brace_index: Option<usize> = None;
-    let mut brace_counter = 0;
-
-    // Find the starting position of the JSON object
-    if let Some(start) = text.find('{') {
-        first_brace_index = Some(start);
-        brace_counter = 1;
-    } else {
-        // No opening brace found, so no JSON object
-        return None;
+/// A piece of text with the kana reading shown above it, None for kana and punctuation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RubySegment {
+    pub text: String,
+    pub reading: Option<String>,
+    /// Dictionary form of the word the segment belongs to, used to hide furigana on known words.
+    pub base_form: String,
+}
+
+fn is_kanji_like(c: char) -> bool {
+    crate::kanji::is_kanji(c) || matches!(c, '々' | 'ヶ' | '〆')
+}
+
+/// Splits a word into kanji and kana segments and gives each kanji segment its part of `reading`,
+/// so okurigana stays outside the ruby: 食べる/たべる gives 食(た) べる.
+/// The reading may be in katakana. When the kana of the word can't be found in the reading,
+/// the whole word gets the whole reading.
+pub fn align_furigana(surface: &str, reading: &str) -> Vec<RubySegment> {
+    // runs of kanji and of kana, with whether the run is kanji
+    let mut runs: Vec<(String, bool)> = Vec::new();
+    for c in surface.chars() {
+        let kanji = is_kanji_like(c);
+        match runs.last_mut() {
+            Some((text, run_kanji)) if *run_kanji == kanji => text.push(c),
+            _ => runs.push((c.to_string(), kanji)),
+        }
+    }
+
+    let reading = reading.to_hiragana();
+    let segment = |text: String, reading: Option<String>| RubySegment {
+        text,
+        reading,
+        base_form: surface.to_string(),
+    };
+    if reading.is_empty() || !runs.iter().any(|(_, kanji)| *kanji) {
+        return vec![segment(surface.to_string(), None)];
     }
 
-    let start_index = first_brace_index.unwrap();
-    // Start searching for the closing brace from the character after the first brace
-    for (i, char) in text[start_index + 1..].char_indices() {
-        match char {
-            '{' => brace_counter += 1,
-            '}' => brace_counter -= 1,
-            _ => (),
+    let reading_chars: Vec<char> = reading.chars().collect();
+    match match_runs(&runs, &reading_chars) {
+        Some(readings) => runs
+            .into_iter()
+            .zip(readings)
+            .map(|((text, kanji), reading)| segment(text, kanji.then_some(reading)))
+            .collect(),
+        None => vec![segment(surface.to_string(), Some(reading))],
+    }
+}
+
+/// The reading of each run, kana runs must appear as is in the reading and
+/// every kanji run takes at least one kana, the shortest that lets the rest match.
+fn match_runs(runs: &[(String, bool)], reading: &[char]) -> Option<Vec<String>> {
+    let Some(((text, kanji), rest)) = runs.split_first() else {
+        return reading.is_empty().then(Vec::new);
+    };
+    let lengths: Vec<usize> = if *kanji {
+        (1..=reading.len()).collect()
+    } else {
+        let kana: Vec<char> = text.to_hiragana().chars().collect();
+        if reading.starts_with(&kana) { vec![kana.len()] } else { Vec::new() }
+    };
+    for len in lengths {
+        if let Some(mut readings) = match_runs(rest, &reading[len..]) {
+            readings.insert(0, reading[..len].iter().collect());
+            return Some(readings);
         }
+    }
+    None
+}
+
+/// Segments `text` with lindera and returns it as ruby segments with the reading of every kanji.
+pub fn furigana(text: &str) -> Result<Vec<RubySegment>, Box<dyn std::error::Error>> {
+    let mut segments = Vec::new();
+    // unknown words have no reading, they are shown without furigana
+    for token in tokenize(text)? {
+        segments.extend(align_furigana(&token.surface, &token.reading).into_iter().map(|segment| RubySegment {
+            base_form: token.base_form.clone(),
+            ..segment
+        }));
+    }
+    Ok(segments)
+}
 
-        // When the counter is zero, we've found the matching closing brace
-        if brace_counter == 0 {
-            // The slice should be from the start brace to the current (closing) brace
-            let end_index = start_index + 1 + i;
-            return Some(text[start_index..=end_index].to_string());
+/// Turns the beginning of a JSON object that is still being generated into valid JSON,
+/// keeping only the members whose value is complete and closing the open brackets.
+/// A string is only kept once its closing quote arrived, so a field never shows half written.
+/// Returns None when no '{' has been generated yet.
+pub fn close_partial_json(text: &str) -> Option<String> {
+    let start = text.find('{')?;
+    let json = &text[start..];
+
+    let mut open: Vec<char> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut after_colon = false;
+    // byte offset up to which the prefix only holds complete values, with the brackets open there
+    let mut safe_end = 0;
+    let mut safe_open: Vec<char> = Vec::new();
+
+    for (i, c) in json.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+                // a string closed in an array or after a ':' is a complete value, otherwise it was a key
+                if after_colon || open.last() == Some(&'[') {
+                    safe_end = i + 1;
+                    safe_open = open.clone();
+                }
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            ':' => after_colon = true,
+            '{' | '[' => {
+                open.push(c);
+                after_colon = false;
+                safe_end = i + 1;
+                safe_open = open.clone();
+            }
+            '}' | ']' => {
+                open.pop();
+                after_colon = false;
+                safe_end = i + 1;
+                safe_open = open.clone();
+                if open.is_empty() {
+                    break;
+                }
+            }
+            ',' => {
+                after_colon = false;
+                safe_end = i;
+                safe_open = open.clone();
+            }
+            _ => {}
         }
     }
 
-    // If we finish the loop and the counter isn't zero, the JSON is malformed
-    None
+    let mut closed = json[..safe_end].to_string();
+    for bracket in safe_open.iter().rev() {
+        closed.push(if *bracket == '{' { '}' } else { ']' });
+    }
+    Some(closed)
+}
+
+/// Parses the JSON an LLM is still streaming, see close_partial_json.
+/// Use it to show the fields that are already complete while the rest is generated.
+pub fn parse_partial_json(text: &str) -> Option<serde_json::Value> {
+    serde_json::from_str(&close_partial_json(text)?).ok()
 }
 
 /// A function to speak text using TTS in a separate thread
@@ -272,7 +330,66 @@ mod tests {
     }
 
     #[test]
-    fn test_extract_json_from_llm_output_and_parse() {
+    fn test_align_furigana() {
+        let ruby = |segments: Vec<RubySegment>| -> Vec<(String, Option<String>)> {
+            segments.into_iter().map(|s| (s.text, s.reading)).collect()
+        };
+        let seg = |text: &str, reading: Option<&str>| (text.to_string(), reading.map(str::to_string));
+
+        assert_eq!(ruby(align_furigana("食べる", "タベル")), vec![seg("食", Some("た")), seg("べる", None)]);
+        assert_eq!(
+            ruby(align_furigana("取り扱い", "とりあつかい")),
+            vec![seg("取", Some("と")), seg("り", None), seg("扱", Some("あつか")), seg("い", None)]
+        );
+        // the kanji reading may end with the same kana as the okurigana
+        assert_eq!(ruby(align_furigana("大きい", "オオキイ")), vec![seg("大", Some("おお")), seg("きい", None)]);
+        assert_eq!(ruby(align_furigana("お茶", "オチャ")), vec![seg("お", None), seg("茶", Some("ちゃ"))]);
+        assert_eq!(ruby(align_furigana("人々", "ヒトビト")), vec![seg("人々", Some("ひとびと"))]);
+        assert_eq!(ruby(align_furigana("コーヒー", "コーヒー")), vec![seg("コーヒー", None)]);
+        // a reading that doesn't fit the kana goes over the whole word
+        assert_eq!(ruby(align_furigana("食べ", "ニャ")), vec![seg("食べ", Some("にゃ"))]);
+        assert_eq!(align_furigana("食べる", "たべる")[1].base_form, "食べる");
+    }
+
+    #[test]
+    fn test_furigana() {
+        let segments = furigana("私は本を読みました。").unwrap();
+        let text: String = segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(text, "私は本を読みました。");
+        let yomi = segments.iter().find(|s| s.text == "読").unwrap();
+        assert_eq!(yomi.reading.as_deref(), Some("よ"));
+        assert_eq!(yomi.base_form, "読む");
+        assert_eq!(segments.iter().find(|s| s.text == "は").unwrap().reading, None);
+    }
+
+    #[test]
+    fn test_parse_partial_json() {
+        let full = r#"```json
+            {"explain": "a \"quoted\" word, ok", "example1": {"sentence": "空が青い。", "translation": "The sky is blue."}}
+            ```"#;
+        let cut = |marker: &str| &full[..full.find(marker).unwrap() + marker.len()];
+
+        assert_eq!(parse_partial_json("```json\n"), None);
+        assert_eq!(parse_partial_json(cut("a \\\"quo")).unwrap(), serde_json::json!({}));
+        // a field shows up once its string is closed
+        let value = parse_partial_json(cut(r#"ok", "exam"#)).unwrap();
+        assert_eq!(value["explain"], "a \"quoted\" word, ok");
+        assert!(value.get("example1").is_none());
+
+        let value = parse_partial_json(cut("The sky is")).unwrap();
+        assert_eq!(value["example1"]["sentence"], "空が青い。");
+        assert!(value["example1"].get("translation").is_none());
+
+        let value = parse_partial_json(full).unwrap();
+        assert_eq!(value["example1"]["translation"], "The sky is blue.");
+
+        // numbers and arrays are kept once they are followed by a ',' or a bracket
+        assert_eq!(close_partial_json(r#"{"a": [1, 2, "x"], "b": tru"#).unwrap(), r#"{"a": [1, 2, "x"]}"#);
+        assert_eq!(close_partial_json(r#"{"a": [1, 2"#).unwrap(), r#"{"a": [1]}"#);
+    }
+
+    #[test]
+    fn test_repair_llm_output_and_parse() {
         let messy_output = r#"
             <|user|>
             Can you add more details to the story to make it more interesting for a beginner?
@@ -286,7 +403,7 @@ mod tests {
             }, {
             "#;
 
-        let json_str = extract_json_from_llm_output(messy_output).unwrap();
+        let json_str = crate::llm_json::repair_json(messy_output).unwrap();
         let parse_story: Story = serde_json::from_str(&json_str).unwrap();
 
         let expected_story = "毎日、私は図書館で赤いりんごを食べます。図書館は赤いです。時々、僕は図書館の中で歩くのが好きです。特別な日は、赤いりんごが図書館の中にあり".to_string();
@@ -294,7 +411,7 @@ mod tests {
         assert_eq!(parse_story.story, expected_story);
 
 
-        // if let Some(json_str) = extract_json_from_llm_output(messy_output) {
+        // if let Some(json_str) = crate::llm_json::repair_json(messy_output) {
         //     println!("--- Clean JSON Extracted ---");
         //     println!("{}", json_str);
 
@@ -0,0 +1,268 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use sqlx::Row;
+use crate::db::{add_word_tags, WordRecord};
+
+/// Options for `merge_import_words`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportOptions {
+    /// Soft delete words that are in the database but no longer in the imported lists.
+    pub soft_delete_missing: bool,
+    /// Compute the report without writing anything.
+    pub dry_run: bool,
+}
+
+/// One word whose list data changed, e.g. a corrected meaning.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordChange {
+    pub expression: String,
+    pub reading: String,
+    pub changes: Vec<String>,
+}
+
+/// What a merge import did (or would do in a dry run).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    pub inserted: Vec<String>,
+    pub updated: Vec<WordChange>,
+    pub restored: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for word in &self.inserted {
+            writeln!(f, "+ {}", word)?;
+        }
+        for change in &self.updated {
+            writeln!(f, "~ {} [{}]: {}", change.expression, change.reading, change.changes.join("; "))?;
+        }
+        for word in &self.restored {
+            writeln!(f, "^ {}", word)?;
+        }
+        for word in &self.removed {
+            writeln!(f, "- {}", word)?;
+        }
+        write!(
+            f,
+            "{} inserted, {} updated, {} restored, {} removed, {} unchanged",
+            self.inserted.len(),
+            self.updated.len(),
+            self.restored.len(),
+            self.removed.len(),
+            self.unchanged
+        )
+    }
+}
+
+/// The list data of a word already in the database.
+struct ExistingWord {
+    id: i64,
+    meaning: String,
+    jlpt: String,
+    deleted: bool,
+    tags: Vec<String>,
+}
+
+fn word_label(expression: &str, reading: &str) -> String {
+    format!("{} [{}]", expression, reading)
+}
+
+/// Imports word lists into an existing database without touching user progress.
+/// Words are matched on (expression, reading):
+/// - matched words get their meaning, jlpt level and tags updated, progress columns are kept
+/// - new words are inserted
+/// - words missing from `records` are soft deleted when `options.soft_delete_missing` is set,
+///   a soft deleted word that shows up again is restored with its old progress
+pub async fn merge_import_words(
+    pool: &sqlx::SqlitePool,
+    records: Vec<WordRecord>,
+    options: ImportOptions,
+) -> Result<ImportReport, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let mut report = ImportReport::default();
+
+    // load what we already have, keyed by (expression, reading)
+    let rows = sqlx::query(
+        r#"
+        SELECT words.id, words.expression, words.reading, words.meaning, words.jlpt, words.deleted,
+               (SELECT GROUP_CONCAT(tags.name, ' ') FROM word_tags JOIN tags ON tags.id = word_tags.tag_id WHERE word_tags.word_id = words.id) AS tags
+        FROM words
+        ORDER BY words.id
+        "#,
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    let mut existing: HashMap<(String, String), ExistingWord> = HashMap::new();
+    for row in rows {
+        let key = (row.get::<String, _>("expression"), row.get::<String, _>("reading"));
+        // keep the oldest row if the same word was added twice
+        existing.entry(key).or_insert(ExistingWord {
+            id: row.get("id"),
+            meaning: row.get("meaning"),
+            jlpt: row.get("jlpt"),
+            deleted: row.get("deleted"),
+            tags: row
+                .get::<Option<String>, _>("tags")
+                .map(|tags| tags.split_whitespace().map(|t| t.to_string()).collect())
+                .unwrap_or_default(),
+        });
+    }
+
+    let mut seen: HashSet<(String, String)> = HashSet::new();
+
+    for record in records {
+        let key = (record.expression.clone(), record.reading.clone());
+        if !seen.insert(key.clone()) {
+            continue; // the same word twice in the imported lists, first one wins
+        }
+
+        let Some(old) = existing.get(&key) else {
+            let result = sqlx::query("INSERT INTO words (expression, reading, meaning, jlpt) VALUES (?, ?, ?, ?)")
+                .bind(&record.expression)
+                .bind(&record.reading)
+                .bind(&record.meaning)
+                .bind(&record.jlpt)
+                .execute(&mut *transaction)
+                .await?;
+            add_word_tags(&mut transaction, result.last_insert_rowid(), &record.tags).await?;
+            report.inserted.push(word_label(&record.expression, &record.reading));
+            continue;
+        };
+
+        let mut changes = Vec::new();
+        if old.meaning != record.meaning {
+            changes.push(format!("meaning: {} -> {}", old.meaning, record.meaning));
+        }
+        if old.jlpt != record.jlpt {
+            changes.push(format!("jlpt: {} -> {}", old.jlpt, record.jlpt));
+        }
+        let old_tags: HashSet<&String> = old.tags.iter().collect();
+        let new_tags: HashSet<&String> = record.tags.iter().collect();
+        if old_tags != new_tags {
+            changes.push(format!("tags: {} -> {}", old.tags.join(" "), record.tags.join(" ")));
+        }
+
+        if old.deleted {
+            sqlx::query("UPDATE words SET deleted = 0 WHERE id = ?")
+                .bind(old.id)
+                .execute(&mut *transaction)
+                .await?;
+            report.restored.push(word_label(&record.expression, &record.reading));
+        }
+
+        if changes.is_empty() {
+            if !old.deleted {
+                report.unchanged += 1;
+            }
+            continue;
+        }
+
+        // only list data is updated, practice_time, familiar, user_mark and the schedule stay as they are
+        sqlx::query("UPDATE words SET meaning = ?, jlpt = ? WHERE id = ?")
+            .bind(&record.meaning)
+            .bind(&record.jlpt)
+            .bind(old.id)
+            .execute(&mut *transaction)
+            .await?;
+        if old_tags != new_tags {
+            sqlx::query("DELETE FROM word_tags WHERE word_id = ?")
+                .bind(old.id)
+                .execute(&mut *transaction)
+                .await?;
+            add_word_tags(&mut transaction, old.id, &record.tags).await?;
+        }
+        report.updated.push(WordChange {
+            expression: record.expression.clone(),
+            reading: record.reading.clone(),
+            changes,
+        });
+    }
+
+    if options.soft_delete_missing {
+        let mut missing: Vec<(&(String, String), &ExistingWord)> = existing
+            .iter()
+            .filter(|(key, word)| !word.deleted && !seen.contains(*key))
+            .collect();
+        missing.sort_by_key(|(_, word)| word.id);
+
+        for ((expression, reading), word) in missing {
+            sqlx::query("UPDATE words SET deleted = 1 WHERE id = ?")
+                .bind(word.id)
+                .execute(&mut *transaction)
+                .await?;
+            report.removed.push(word_label(expression, reading));
+        }
+    }
+
+    if options.dry_run {
+        transaction.rollback().await?;
+    } else {
+        transaction.commit().await?;
+    }
+
+    Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::*;
+    use crate::scheduler::{grade_word, Grade};
+
+    #[tokio::test]
+    async fn test_merge_import_keeps_progress() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            WordRecord { tags: vec!["JLPT_5".to_string()], ..test_word("青", "あお", "blue", "n5") },
+            WordRecord { tags: vec!["JLPT_3".to_string()], ..test_word("作法", "さほう", "manners", "n3") },
+            WordRecord { tags: vec!["JLPT_3".to_string()], ..test_word("様々", "さまざま", "varied", "n3") },
+        ]).await.unwrap();
+
+        grade_word(&pool, 1, Grade::Good).await.unwrap();
+        ProgressUpdate::new().set_user_mark(true).execute(&pool, 1).await.unwrap();
+
+        // edited csv: 青 meaning changed, 様々 removed, 会う added
+        let edited = vec![
+            WordRecord { tags: vec!["JLPT_5".to_string()], ..test_word("青", "あお", "blue, green", "n5") },
+            WordRecord { tags: vec!["JLPT_3".to_string()], ..test_word("作法", "さほう", "manners", "n3") },
+            WordRecord { tags: vec!["JLPT_5".to_string()], ..test_word("会う", "あう", "to meet", "n5") },
+        ];
+
+        let dry = merge_import_words(&pool, edited.clone(), ImportOptions { soft_delete_missing: true, dry_run: true })
+            .await
+            .unwrap();
+        assert_eq!(dry.inserted.len(), 1);
+        assert_eq!(find_word_by_ids(&pool, vec![1]).await.unwrap()[0].meaning, "blue");
+
+        let report = merge_import_words(&pool, edited, ImportOptions { soft_delete_missing: true, dry_run: false })
+            .await
+            .unwrap();
+        assert_eq!(report, dry);
+        assert_eq!(report.inserted, vec!["会う [あう]".to_string()]);
+        assert_eq!(report.updated.len(), 1);
+        assert_eq!(report.removed, vec!["様々 [さまざま]".to_string()]);
+        assert_eq!(report.unchanged, 1);
+
+        let blue = find_word_by_ids(&pool, vec![1]).await.unwrap();
+        assert_eq!(blue[0].meaning, "blue, green");
+        assert_eq!(blue[0].practice_time, 1);
+        assert!(blue[0].familiar);
+        assert!(blue[0].user_mark);
+
+        // the removed word is hidden from selections
+        let n3 = ProgressSelect::new().select_jlpt(JLPTlv::N3).execute(&pool).await.unwrap();
+        assert_eq!(n3.len(), 1);
+
+        // importing it again restores it
+        let report = merge_import_words(&pool, vec![WordRecord { tags: vec!["JLPT_3".to_string()], ..test_word("様々", "さまざま", "varied", "n3") }], ImportOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.restored, vec!["様々 [さまざま]".to_string()]);
+        let n3 = ProgressSelect::new().select_jlpt(JLPTlv::N3).execute(&pool).await.unwrap();
+        assert_eq!(n3.len(), 2);
+    }
+}
@@ -0,0 +1,325 @@
+use std::error::Error;
+
+use dioxus::prelude::*;
+
+use crate::db::*;
+use crate::footer::{StatusMessage, StatusLevel};
+use crate::jmdict::{word_details, WordDetails};
+use crate::scheduler::today;
+use crate::story::WordToken;
+use crate::Route;
+
+/// A word of the words table with its dictionary data, as shown when a story word is clicked.
+#[derive(Debug, Clone)]
+pub struct WordLookup {
+    pub word: WordRecord,
+    /// None when the word was not matched to a JMdict entry.
+    pub details: Option<WordDetails>,
+}
+
+/// The id of the word with `value` in `field`, words removed from the word lists
+/// (soft deleted, see word_import.rs) don't count.
+async fn find_active_word_id(pool: &sqlx::SqlitePool, field: WordField, value: &str) -> Result<Option<i64>, sqlx::Error> {
+    let query = format!("SELECT id FROM words WHERE {} = ? AND deleted = 0 ORDER BY id LIMIT 1", field.to_string());
+    sqlx::query_scalar(&query).bind(value).fetch_optional(pool).await
+}
+
+/// Finds the word of `token` in the words table: by base form, then by surface,
+/// then by reading for words the story writes in kana.
+pub async fn lookup_token(pool: &sqlx::SqlitePool, token: &WordToken) -> Result<Option<WordLookup>, sqlx::Error> {
+    let candidates = [
+        (WordField::Expression, token.base_form.as_str()),
+        (WordField::Expression, token.surface.as_str()),
+        (WordField::Reading, token.base_form.as_str()),
+    ];
+    for (field, value) in candidates {
+        if value.is_empty() || value == "*" {
+            continue;
+        }
+        let Some(id) = find_active_word_id(pool, field, value).await? else {
+            continue;
+        };
+        let Some(word) = find_word_by_ids(pool, vec![id]).await?.into_iter().next() else {
+            continue;
+        };
+        let details = word_details(pool, word.id).await?;
+        return Ok(Some(WordLookup { word, details }));
+    }
+    Ok(None)
+}
+
+/// Adds a word the user met in a story to the words table and returns it.
+/// A word removed from the word lists is restored with its progress instead.
+pub async fn add_to_vocabulary(
+    pool: &sqlx::SqlitePool,
+    expression: &str,
+    reading: &str,
+    meaning: &str,
+) -> Result<WordRecord, Box<dyn Error>> {
+    let expression = expression.trim();
+    if expression.is_empty() {
+        return Err("The word is empty.".into());
+    }
+    if find_active_word_id(pool, WordField::Expression, expression).await?.is_some() {
+        return Err(format!("'{}' is already in your vocabulary.", expression).into());
+    }
+    let restored = sqlx::query("UPDATE words SET deleted = 0 WHERE id = (SELECT MIN(id) FROM words WHERE expression = ?)")
+        .bind(expression)
+        .execute(pool)
+        .await?;
+    if restored.rows_affected() == 0 {
+        insert_words(pool, vec![WordRecord {
+            expression: expression.to_string(),
+            reading: reading.trim().to_string(),
+            meaning: meaning.trim().to_string(),
+            ..Default::default()
+        }])
+        .await?;
+    }
+    let id = find_active_word_id(pool, WordField::Expression, expression)
+        .await?
+        .ok_or("The new word could not be read back.")?;
+    find_word_by_ids(pool, vec![id])
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "The new word could not be read back.".into())
+}
+
+/// The panel under InteractiveStory showing the clicked word, `on_close` hides it.
+#[component]
+pub fn WordLookupPanel(token: WordToken, on_close: EventHandler<()>) -> Element {
+    let navigator = use_navigator();
+    let mut status_message = use_context::<Signal<StatusMessage>>();
+    let mut select_words = use_context::<Signal<Vec<WordRecord>>>();
+    let db_pool = use_context::<sqlx::SqlitePool>();
+    let pool_mark = db_pool.clone();
+    let pool_add = db_pool.clone();
+
+    // fields of the "add to my vocabulary" form, filled from the token
+    let mut new_reading = use_signal(|| token.reading.clone());
+    let mut new_meaning = use_signal(String::new);
+
+    // a new word was clicked, start the form over
+    let token_form = token.clone();
+    use_effect(use_reactive!(|token_form| {
+        new_reading.set(token_form.reading.clone());
+        new_meaning.set(String::new());
+    }));
+
+    let token_lookup = token.clone();
+    let mut lookup = use_resource(use_reactive!(|token_lookup| {
+        let pool = db_pool.clone();
+        async move { lookup_token(&pool, &token_lookup).await }
+    }));
+
+    let explain_word = token.base_form.clone();
+    let new_expression = token.base_form.clone();
+
+    rsx! {
+        div { class: "card bg-dark border-info mt-3",
+            div { class: "card-header d-flex justify-content-between align-items-center",
+                span { class: "fs-5", "{token.surface}" }
+                button { class: "btn-close btn-close-white", onclick: move |_| on_close.call(()) }
+            }
+            div { class: "card-body",
+                match &*lookup.read_unchecked() {
+                    Some(Ok(Some(found))) => {
+                        let word = found.word.clone();
+                        let in_queue = select_words.read().iter().any(|w| w.id == word.id);
+                        rsx! {
+                            div { class: "d-flex align-items-baseline gap-2 mb-2",
+                                span { class: "fs-4", "{word.expression}" }
+                                span { class: "text-secondary", "{word.reading}" }
+                                if !word.jlpt.is_empty() {
+                                    span { class: "badge bg-info text-dark", "{word.jlpt}" }
+                                }
+                                if word.user_mark {
+                                    span { class: "text-warning", "★" }
+                                }
+                            }
+                            p { class: "mb-1", "{word.meaning}" }
+                            if let Some(details) = found.details.as_ref() {
+                                pre { class: "small text-secondary text-wrap mb-2", "{details.senses_text()}" }
+                            }
+                            p { class: "small text-secondary mb-3",
+                                if word.practice_time == 0 {
+                                    "Not practiced yet."
+                                } else {
+                                    "Practiced {word.practice_time} times, "
+                                    if word.familiar { "familiar" } else { "not familiar yet" }
+                                    if word.due_day <= today() {
+                                        ", due for review."
+                                    } else {
+                                        ", next review in {word.due_day - today()} days."
+                                    }
+                                }
+                            }
+                            div { class: "d-flex gap-2",
+                                button {
+                                    class: if word.user_mark { "btn btn-warning btn-sm" } else { "btn btn-outline-warning btn-sm" },
+                                    onclick: {
+                                        let pool = pool_mark.clone();
+                                        let word = word.clone();
+                                        move |_| {
+                                            let pool = pool.clone();
+                                            let word = word.clone();
+                                            async move {
+                                                match ProgressUpdate::new().set_user_mark(!word.user_mark).execute(&pool, word.id).await {
+                                                    Ok(_) => lookup.restart(),
+                                                    Err(e) => {
+                                                        eprintln!("Error marking word {}: {}", word.id, e);
+                                                        status_message.set(StatusMessage {
+                                                            message: "A database error occurred.".to_string(),
+                                                            level: StatusLevel::Error,
+                                                        });
+                                                    }
+                                                }
+                                            }
+                                        }
+                                    },
+                                    if word.user_mark { "★ Marked" } else { "☆ Mark" }
+                                }
+                                button {
+                                    class: "btn btn-outline-light btn-sm",
+                                    disabled: in_queue,
+                                    onclick: {
+                                        let word = word.clone();
+                                        move |_| {
+                                            select_words.push(word.clone());
+                                            status_message.set(StatusMessage {
+                                                message: format!("'{}' added to the review queue ({} words).", word.expression, select_words.len()),
+                                                level: StatusLevel::Success,
+                                            });
+                                        }
+                                    },
+                                    if in_queue { "In the review queue" } else { "Add to review queue" }
+                                }
+                                button {
+                                    class: "btn btn-outline-info btn-sm",
+                                    onclick: {
+                                        let word = word.expression.clone();
+                                        move |_| {
+                                            navigator.push(Route::WordExplainer { word_to_explain: word.clone() });
+                                        }
+                                    },
+                                    "Explain"
+                                }
+                            }
+                        }
+                    },
+                    Some(Ok(None)) => rsx! {
+                        p { class: "text-secondary",
+                            "{token.base_form} is not in your vocabulary yet. ({token.part_of_speech})"
+                        }
+                        div { class: "row g-2 align-items-end",
+                            div { class: "col-md-3",
+                                label { class: "form-label small", r#for: "newWordReading", "Reading" }
+                                input {
+                                    class: "form-control form-control-sm",
+                                    id: "newWordReading",
+                                    value: "{new_reading}",
+                                    oninput: move |evt| new_reading.set(evt.value()),
+                                }
+                            }
+                            div { class: "col-md-5",
+                                label { class: "form-label small", r#for: "newWordMeaning", "Meaning" }
+                                input {
+                                    class: "form-control form-control-sm",
+                                    id: "newWordMeaning",
+                                    value: "{new_meaning}",
+                                    oninput: move |evt| new_meaning.set(evt.value()),
+                                }
+                            }
+                            div { class: "col-auto",
+                                button {
+                                    class: "btn btn-primary btn-sm",
+                                    onclick: {
+                                        let pool = pool_add.clone();
+                                        let expression = new_expression.clone();
+                                        move |_| {
+                                            let pool = pool.clone();
+                                            let expression = expression.clone();
+                                            async move {
+                                                match add_to_vocabulary(&pool, &expression, &new_reading(), &new_meaning()).await {
+                                                    Ok(word) => {
+                                                        status_message.set(StatusMessage {
+                                                            message: format!("'{}' added to your vocabulary.", word.expression),
+                                                            level: StatusLevel::Success,
+                                                        });
+                                                        lookup.restart();
+                                                    }
+                                                    Err(e) => status_message.set(StatusMessage {
+                                                        message: format!("Could not add the word: {}", e),
+                                                        level: StatusLevel::Error,
+                                                    }),
+                                                }
+                                            }
+                                        }
+                                    },
+                                    "Add to my vocabulary"
+                                }
+                            }
+                            div { class: "col-auto",
+                                button {
+                                    class: "btn btn-outline-info btn-sm",
+                                    onclick: move |_| {
+                                        navigator.push(Route::WordExplainer { word_to_explain: explain_word.clone() });
+                                    },
+                                    "Explain"
+                                }
+                            }
+                        }
+                    },
+                    Some(Err(e)) => rsx! { div { class: "alert alert-danger", "Error looking up the word: {e}" } },
+                    None => rsx! { p { "Looking up..." } },
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(surface: &str, base_form: &str) -> WordToken {
+        WordToken {
+            surface: surface.to_string(),
+            part_of_speech: "動詞".to_string(),
+            base_form: base_form.to_string(),
+            reading: String::new(),
+            romaji: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lookup_and_add() {
+        let pool = setup_test_db().await;
+        bulk_insert_words(&pool, vec![
+            test_word("食べる", "たべる", "to eat", "n5"),
+            test_word("為る", "する", "to do", "n5"),
+        ]).await.unwrap();
+
+        let found = lookup_token(&pool, &token("食べ", "食べる")).await.unwrap().unwrap();
+        assert_eq!(found.word.meaning, "to eat");
+        assert_eq!(found.details, None);
+        // written in kana in the story
+        let found = lookup_token(&pool, &token("し", "する")).await.unwrap().unwrap();
+        assert_eq!(found.word.expression, "為る");
+        assert!(lookup_token(&pool, &token("泳い", "泳ぐ")).await.unwrap().is_none());
+
+        let added = add_to_vocabulary(&pool, "泳ぐ", "およぐ", " to swim ").await.unwrap();
+        assert_eq!(added.meaning, "to swim");
+        assert_eq!(lookup_token(&pool, &token("泳い", "泳ぐ")).await.unwrap().unwrap().word.id, added.id);
+        assert!(add_to_vocabulary(&pool, "泳ぐ", "およぐ", "").await.is_err());
+        assert!(add_to_vocabulary(&pool, " ", "", "").await.is_err());
+
+        // a word removed from the word lists is not found and comes back when added
+        sqlx::query("UPDATE words SET deleted = 1 WHERE id = 1").execute(&pool).await.unwrap();
+        assert!(lookup_token(&pool, &token("食べ", "食べる")).await.unwrap().is_none());
+        let added = add_to_vocabulary(&pool, "食べる", "たべる", "").await.unwrap();
+        assert_eq!((added.id, added.meaning.as_str()), (1, "to eat"));
+    }
+}
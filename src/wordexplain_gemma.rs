@@ -1,30 +1,63 @@
 use futures_util::StreamExt;
-use ollama_rs::{Ollama, generation::completion::request::GenerationRequest};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
 use std::error::Error;
 use std::sync::Arc;
 
 use dioxus::prelude::*;
-use crate::utils::{word_process, speak_text};
+use crate::utils::{word_process, speak_text, parse_partial_json};
 use crate::return_voice;
+use crate::furigana::RubyText;
 use crate::footer::{StatusMessage, StatusLevel};
+use crate::llm::LlmBackend;
+use crate::llm_json::{generate_json, object_schema, string_schema, JsonProgress};
+use crate::review_log::now_timestamp;
+use crate::scheduler::SECONDS_PER_DAY;
 use tts::*;
 
 
 
+/// Bump when the explanation prompt changes, cached explanations of older prompts are regenerated.
+pub const EXPLANATION_PROMPT_VERSION: i64 = 1;
+
 // The structs to hold our parsed data (same as before)
-#[derive(Deserialize, Debug)]
-struct WordExplanation {
-    explain: String,
-    example1: Example,
-    example2: Example,
-    example3: Example,
+#[derive(Deserialize, Debug, Clone)]
+pub struct WordExplanation {
+    pub explain: String,
+    pub example1: Example,
+    pub example2: Example,
+    pub example3: Example,
 }
 
-#[derive(Deserialize, Debug)]
-struct Example {
-    sentence: String,
-    translation: String,
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Example {
+    pub sentence: String,
+    pub translation: String,
+}
+
+/// An explanation stored in the `word_explanations` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedExplanation {
+    pub word: String,
+    pub explain: String,
+    pub examples: Vec<Example>,
+    pub model: String,
+    pub prompt_version: i64,
+    pub created_at: i64, // unix timestamp in seconds
+}
+
+impl CachedExplanation {
+    fn from_row(row: &SqliteRow) -> Self {
+        CachedExplanation {
+            word: row.get("word"),
+            explain: row.get("explain"),
+            examples: serde_json::from_str(&row.get::<String, _>("examples")).unwrap_or_default(),
+            model: row.get("model"),
+            prompt_version: row.get("prompt_version"),
+            created_at: row.get("created_at"),
+        }
+    }
 }
 
 // A struct to hold all the data for one processed sentence
@@ -37,24 +70,41 @@ struct ProcessedSentence {
 }
 
 
-/// --- Parsing function is the same ---
-fn parse_llm_json(raw_text: &str) -> Result<WordExplanation, Box<dyn Error>> {
-    // (Your robust parsing logic from the previous step goes here)
-    let start_byte = raw_text.find('{').ok_or("Could not find opening '{'")?;
-    let end_byte = raw_text.rfind('}').ok_or("Could not find closing '}'")? + 1;
-    let json_slice = &raw_text[start_byte..end_byte];
-    Ok(serde_json::from_str(json_slice)?)
+/// JSON schema of `WordExplanation`, sent to backends with a structured output mode.
+fn explanation_schema() -> serde_json::Value {
+    let example = object_schema(&[("sentence", string_schema()), ("translation", string_schema())]);
+    object_schema(&[
+        ("explain", string_schema()),
+        ("example1", example.clone()),
+        ("example2", example.clone()),
+        ("example3", example),
+    ])
+}
+
+/// Reads the example stored under `key` once both its sentence and translation are complete.
+fn partial_example(value: &serde_json::Value, key: &str) -> Option<Example> {
+    serde_json::from_value(value.get(key)?.clone()).ok()
+}
+
+/// Tokenizes an example sentence for display, None if lindera fails on it.
+fn process_example(example: Example) -> Option<ProcessedSentence> {
+    let (words, romaji) = word_process(&example.sentence).ok()?;
+    Some(ProcessedSentence {
+        original: example.sentence,
+        translation: example.translation,
+        romaji,
+        words,
+    })
 }
 
 /// Asynchronous function to get word explanation from the LLM
-/// This function uses the Ollama client to send a request and receive a response from Gemma:4b model.
+/// The response is streamed, `on_progress` gets the text generated so far after every chunk
+/// and is told when an unusable answer is asked for again.
 async fn get_word_explanation(
-    ollama_client: Arc<Ollama>,
+    llm: Arc<dyn LlmBackend>,
     word_to_explain: &str,
+    on_progress: impl FnMut(JsonProgress),
 ) -> Result<WordExplanation, Box<dyn Error>> {
-    let model = "gemma3:4b".to_string();
-    
-    
     let prompt = format!(
         r#"Explain the Japanese word '{}'. Provide the explanation and 3 example sentences in Japanese. 
             The 'translation' for each example should be in English not romaji. Structure the output as a JSON object with the following keys: 
@@ -67,13 +117,10 @@ async fn get_word_explanation(
         word_to_explain
     );
 
-    eprint!("Requesting explanation for '{}'\n", word_to_explain); 
+    eprintln!("Requesting explanation for '{}' from {}", word_to_explain, llm.describe()); 
 
-    let request = GenerationRequest::new(model, prompt);
-    let response = ollama_client.generate(request).await?;
-    
-    // Parse the response and return the structured data
-    parse_llm_json(&response.response)
+    // Generate, parse and retry until the response fits the structured data
+    generate_json(&*llm, &prompt, &explanation_schema(), on_progress).await
 }
 
 
@@ -81,10 +128,75 @@ async fn get_word_explanation(
 
 
 
+/// Returns the cached explanation of `word`, None if there is none for the current prompt version.
+pub async fn cached_explanation(pool: &sqlx::SqlitePool, word: &str) -> Result<Option<CachedExplanation>, sqlx::Error> {
+    let row = sqlx::query("SELECT * FROM word_explanations WHERE word = ? AND prompt_version = ?")
+        .bind(word)
+        .bind(EXPLANATION_PROMPT_VERSION)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.as_ref().map(CachedExplanation::from_row))
+}
+
+/// Stores the explanation of `word` generated by `model`, replacing the cached one.
+pub async fn save_explanation(
+    pool: &sqlx::SqlitePool,
+    word: &str,
+    explanation: &WordExplanation,
+    model: &str,
+) -> Result<(), sqlx::Error> {
+    let examples = vec![
+        explanation.example1.clone(),
+        explanation.example2.clone(),
+        explanation.example3.clone(),
+    ];
+    sqlx::query(
+        r#"
+        INSERT OR REPLACE INTO word_explanations (word, explain, examples, model, prompt_version, created_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(word)
+    .bind(&explanation.explain)
+    .bind(serde_json::to_string(&examples).unwrap_or_else(|_| "[]".to_string()))
+    .bind(model)
+    .bind(EXPLANATION_PROMPT_VERSION)
+    .bind(now_timestamp())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Lists the models that have cached explanations, with the number of explanations of each.
+pub async fn explanation_models(pool: &sqlx::SqlitePool) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    let rows = sqlx::query("SELECT model, COUNT(*) AS total FROM word_explanations GROUP BY model ORDER BY model")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.iter().map(|row| (row.get("model"), row.get("total"))).collect())
+}
+
+/// Deletes the cached explanations generated by `model`, returns how many were deleted.
+pub async fn delete_explanations_by_model(pool: &sqlx::SqlitePool, model: &str) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM word_explanations WHERE model = ?")
+        .bind(model)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+
+/// A request sent to the WordExplainer coroutine.
+#[derive(Debug, Clone, PartialEq)]
+struct ExplainRequest {
+    word: String,
+    /// skip the cache and ask the model again
+    regenerate: bool,
+}
+
 #[component]
 pub fn WordExplainer(word_to_explain: String) -> Element {
-    // Get the shared Ollama client from the context.
-    let ollama_client = use_context::<Arc<Ollama>>();
+    // Get the LLM backend chosen on the setting page from the context.
+    let llm = use_context::<Signal<Arc<dyn LlmBackend>>>();
     
     // signal to hold the explaination text generated by the LLM
     let mut explanation_text = use_signal(|| String::new());
@@ -92,74 +204,149 @@ pub fn WordExplainer(word_to_explain: String) -> Element {
     // signal to hold processed sentences
     let mut processed_sentences = use_signal(|| Vec::<ProcessedSentence>::new());
 
+    // the raw text streamed so far, shown until the explanation field is complete
+    let mut raw_output = use_signal(String::new);
+    let mut generating = use_signal(|| false);
+    // the request in flight, cancelled by the Cancel button or when another word is clicked
+    let mut current_task = use_signal(|| None::<Task>);
+    // the word on screen, and where its explanation came from
+    let mut current_word = use_signal(String::new);
+    let mut source_note = use_signal(String::new);
+
 
     let mut status_message = use_context::<Signal<StatusMessage>>();
+    let db_pool = use_context::<sqlx::SqlitePool>();
 
 
 
     // Create a coroutine to handle the async task
-    let coroutine = use_coroutine(move |mut rx: UnboundedReceiver<String>| {
-        // Clone the client and state setter to move into the async block
-        let client = ollama_client.clone();
-
-        status_message.set(StatusMessage {
-            message: format!("Waiting for LLM, please be patient..."),
-            level: StatusLevel::Info,
-        });
-        
+    let coroutine = use_coroutine(move |mut rx: UnboundedReceiver<ExplainRequest>| {
+        let pool = db_pool.clone();
         async move {
-            while let Some(word) = rx.next().await {
+            while let Some(ExplainRequest { word, regenerate }) = rx.next().await {
+                if let Some(task) = current_task.take() {
+                    task.cancel();
+                }
+                generating.set(false);
+                current_word.set(word.clone());
+                explanation_text.set(String::new());
+                processed_sentences.set(Vec::new());
+                raw_output.set(String::new());
+                source_note.set(String::new());
+
+                // serve the cached explanation unless the user asked for a new one
+                if !regenerate {
+                    match cached_explanation(&pool, &word).await {
+                        Ok(Some(cached)) => {
+                            let days = (now_timestamp() - cached.created_at) / SECONDS_PER_DAY;
+                            source_note.set(format!("Cached answer of {}, {} days old", cached.model, days));
+                            explanation_text.set(cached.explain);
+                            processed_sentences.set(cached.examples.into_iter().filter_map(process_example).collect());
+                            status_message.set(StatusMessage {
+                                message: format!("Explanation for '{}' loaded from the cache.", word),
+                                level: StatusLevel::Success,
+                            });
+                            continue;
+                        }
+                        Ok(None) => {}
+                        Err(e) => eprintln!("Error reading the explanation cache: {}", e),
+                    }
+                }
+                generating.set(true);
+
+                // read the backend for every request so a change on the setting page applies right away
+                let llm = llm();
                 status_message.set(StatusMessage {
-                    message: format!("Waiting for LLM, please be patient..."),
+                    message: format!("Asking {} about '{}'...", llm.describe(), word),
                     level: StatusLevel::Info,
                 });
-                // Call the async function to get the explanation
-                match get_word_explanation(client.clone(), &word).await {
-                    Ok(explanation) => {
-                        // Update the explanation text
-                        explanation_text.set(explanation.explain.clone());
-                        
-                        // Process all three example sentences and update our vector
-                        let mut sentences = Vec::new();
-                        let examples = vec![explanation.example1, explanation.example2, explanation.example3];
-                        for ex in examples {
-                            if let Ok((words, romaji)) = word_process(&ex.sentence) {
-                                sentences.push(ProcessedSentence {
-                                    original: ex.sentence,
-                                    translation: ex.translation,
-                                    romaji,
-                                    words,
+
+                let pool = pool.clone();
+                let task = spawn(async move {
+                    // number of examples already complete in the stream
+                    let mut examples_done = 0;
+                    let model = llm.model();
+                    let result = get_word_explanation(llm, &word, |progress| {
+                        let partial = match progress {
+                            JsonProgress::Partial(partial) => partial,
+                            JsonProgress::Retry { attempt, attempts, error } => {
+                                // start over with the new answer
+                                examples_done = 0;
+                                explanation_text.set(String::new());
+                                processed_sentences.set(Vec::new());
+                                raw_output.set(String::new());
+                                status_message.set(StatusMessage {
+                                    message: format!("Unusable answer for '{}' ({}), asking again ({}/{})...", word, error, attempt, attempts),
+                                    level: StatusLevel::Warning,
                                 });
+                                return;
+                            }
+                        };
+                        raw_output.set(partial.to_string());
+                        let Some(value) = parse_partial_json(partial) else {
+                            return;
+                        };
+                        if let Some(explain) = value.get("explain").and_then(|explain| explain.as_str()) {
+                            if *explanation_text.peek() != explain {
+                                explanation_text.set(explain.to_string());
+                            }
+                        }
+                        while examples_done < 3 {
+                            let Some(example) = partial_example(&value, &format!("example{}", examples_done + 1)) else {
+                                break;
+                            };
+                            examples_done += 1;
+                            if let Some(sentence) = process_example(example) {
+                                processed_sentences.push(sentence);
                             }
                         }
-                        processed_sentences.set(sentences);
-
-                        status_message.set(StatusMessage {
-                            message: format!("Explanation for '{}' retrieved successfully.", word),
-                            level: StatusLevel::Success,
-                        });
-                    },
-
-                    Err(e) => {
-                        eprintln!("Error retrieving explanation for '{}': {}", word, e);
-                        status_message.set(StatusMessage {
-                            message: format!("Error retrieving explanation for '{}': {}", word, e),
-                            level: StatusLevel::Error,
-                        });
+                    })
+                    .await;
+
+                    match result {
+                        Ok(explanation) => {
+                            if let Err(e) = save_explanation(&pool, &word, &explanation, &model).await {
+                                eprintln!("Error caching the explanation for '{}': {}", word, e);
+                            }
+                            source_note.set(format!("Generated by {}", model));
+
+                            // Update the explanation text
+                            explanation_text.set(explanation.explain.clone());
+                        
+                            // Process all three example sentences and update our vector
+                            let examples = vec![explanation.example1, explanation.example2, explanation.example3];
+                            processed_sentences.set(examples.into_iter().filter_map(process_example).collect());
+
+                            status_message.set(StatusMessage {
+                                message: format!("Explanation for '{}' retrieved successfully.", word),
+                                level: StatusLevel::Success,
+                            });
+                        },
+
+                        Err(e) => {
+                            eprintln!("Error retrieving explanation for '{}': {}", word, e);
+                            // don't leave a half streamed explanation on screen
+                            explanation_text.set(String::new());
+                            processed_sentences.set(Vec::new());
+                            raw_output.set(String::new());
+                            status_message.set(StatusMessage {
+                                message: format!("Error retrieving explanation for '{}': {}", word, e),
+                                level: StatusLevel::Error,
+                            });
+                        }
                     }
-                }
-             
+                    generating.set(false);
+                    current_task.set(None);
+                });
+                current_task.set(Some(task));
             }
-        
-        }});
-
-    use_effect({
-        let coroutine = coroutine.clone();
-        move || {
-            // coroutine.send("食べちゃいました".to_string());
-            coroutine.send(word_to_explain.to_string());
         }
     });
+
+    use_effect(move || {
+        // coroutine.send("食べちゃいました".to_string());
+        coroutine.send(ExplainRequest { word: word_to_explain.to_string(), regenerate: false });
+    });
     
            
 
@@ -167,9 +354,46 @@ pub fn WordExplainer(word_to_explain: String) -> Element {
     rsx! {
         div { class: "card mb-4",
             div { class: "card-body bg-dark text-light",
-                h3 { class: "card-title", "Explanation" }
+                div { class: "d-flex justify-content-between align-items-center",
+                    h3 { class: "card-title", "Explanation: {current_word}" }
+                    if !generating() && !current_word.read().is_empty() {
+                        div { class: "d-flex align-items-center",
+                            span { class: "small text-secondary me-2", "{source_note}" }
+                            button {
+                                class: "btn btn-outline-info btn-sm",
+                                onclick: move |_| coroutine.send(ExplainRequest { word: current_word(), regenerate: true }),
+                                "Regenerate"
+                            }
+                        }
+                    }
+                    if generating() {
+                        div { class: "d-flex align-items-center",
+                            div { class: "spinner-border spinner-border-sm text-info me-2", role: "status" }
+                            button {
+                                class: "btn btn-outline-danger btn-sm",
+                                onclick: move |_| {
+                                    if let Some(task) = current_task.take() {
+                                        task.cancel();
+                                    }
+                                    generating.set(false);
+                                    explanation_text.set(String::new());
+                                    processed_sentences.set(Vec::new());
+                                    raw_output.set(String::new());
+                                    status_message.set(StatusMessage {
+                                        message: "Request cancelled.".to_string(),
+                                        level: StatusLevel::Warning,
+                                    });
+                                },
+                                "Cancel"
+                            }
+                        }
+                    }
+                }
                 // Render the main explanation text
                 p { class: "card-text", "{explanation_text}" }
+                if generating() && explanation_text.read().is_empty() {
+                    pre { class: "small text-secondary text-wrap mb-0", "{raw_output}" }
+                }
             }
         }
 
@@ -178,7 +402,7 @@ pub fn WordExplainer(word_to_explain: String) -> Element {
             div { class: "card mb-2  bg-dark text-light",
                 InteractiveSentence {
                     sentence_data: sentence_data.clone(),
-                    on_word_click: coroutine.clone()
+                    on_word_click: coroutine
                 }
             }
         }
@@ -193,7 +417,7 @@ pub fn WordExplainer(word_to_explain: String) -> Element {
 fn InteractiveSentence(
     sentence_data: ProcessedSentence,
     // We pass the coroutine down from the parent so this component can send messages to it
-    on_word_click: Coroutine<String>, 
+    on_word_click: Coroutine<ExplainRequest>, 
 ) -> Element {
     // --- voice for tts ---
     let voice_to_use = return_voice("ja", Gender::Male)?;
@@ -206,7 +430,7 @@ fn InteractiveSentence(
                 div { class: "col-10 d-flex flex-column justify-content-center",
                     div { class: "row mb-1",
                         div { class: "col-3 text-secondary small", "Sentence:" }
-                        div { class: "col-9", "{sentence_data.original}" }
+                        div { class: "col-9", RubyText { text: sentence_data.original.clone() } }
                     }
                     div { class: "row mb-1",
                         div { class: "col-3 text-secondary small", "Romaji:" }
@@ -227,8 +451,7 @@ fn InteractiveSentence(
                                     onclick: {
                                         
                                         let word = word.clone();
-                                        let on_word_click = on_word_click.clone();
-                                        move |_| on_word_click.send(word.clone())
+                                        move |_| on_word_click.send(ExplainRequest { word: word.clone(), regenerate: false })
                                     },
                                     "{word}"
                                 }
@@ -283,7 +506,60 @@ mod tests {
                 }
             }
             ```"#;
-        let explanation = parse_llm_json(raw_response).unwrap();
+        let explanation: WordExplanation = crate::llm_json::parse_llm_output(raw_response).unwrap();
         assert_eq!(explanation.example1.sentence, "おやつを食べてしまってごめんね。");
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_get_word_explanation_with_mock() {
+        let response = r#"{"explain": "blue", "example1": {"sentence": "空が青い。", "translation": "The sky is blue."}, "example2": {"sentence": "青い車。", "translation": "A blue car."}, "example3": {"sentence": "青です。", "translation": "It is blue."}}"#;
+        let mock = Arc::new(crate::llm::MockBackend::new(vec![response.to_string()]));
+
+        let mut partials = Vec::new();
+        let explanation = get_word_explanation(mock.clone(), "青", |progress| {
+            if let JsonProgress::Partial(partial) = progress {
+                partials.push(partial.to_string());
+            }
+        }).await.unwrap();
+        assert_eq!(partials.last().map(|p| p.as_str()), Some(response));
+        assert_eq!(explanation.explain, "blue");
+        assert_eq!(explanation.example3.translation, "It is blue.");
+        assert!(mock.prompts()[0].contains("'青'"));
+    }
+
+    #[tokio::test]
+    async fn test_explanation_cache() {
+        let pool = crate::db::setup_test_db().await;
+
+        let example = |sentence: &str| Example { sentence: sentence.to_string(), translation: "blue".to_string() };
+        let explanation = WordExplanation {
+            explain: "the color blue".to_string(),
+            example1: example("空が青い。"),
+            example2: example("青い車。"),
+            example3: example("青です。"),
+        };
+
+        assert_eq!(cached_explanation(&pool, "青").await.unwrap(), None);
+        save_explanation(&pool, "青", &explanation, "gemma3:4b").await.unwrap();
+        save_explanation(&pool, "赤", &explanation, "gemma3:4b").await.unwrap();
+        // regenerating replaces the cached answer
+        save_explanation(&pool, "赤", &WordExplanation { explain: "red".to_string(), ..explanation.clone() }, "qwen3:8b").await.unwrap();
+
+        let cached = cached_explanation(&pool, "青").await.unwrap().unwrap();
+        assert_eq!(cached.explain, "the color blue");
+        assert_eq!(cached.examples[1].sentence, "青い車。");
+        assert_eq!(cached.prompt_version, EXPLANATION_PROMPT_VERSION);
+        assert_eq!(cached_explanation(&pool, "赤").await.unwrap().unwrap().model, "qwen3:8b");
+
+        assert_eq!(
+            explanation_models(&pool).await.unwrap(),
+            vec![("gemma3:4b".to_string(), 1), ("qwen3:8b".to_string(), 1)]
+        );
+        assert_eq!(delete_explanations_by_model(&pool, "gemma3:4b").await.unwrap(), 1);
+        assert_eq!(cached_explanation(&pool, "青").await.unwrap(), None);
+
+        // answers to an older prompt are not served
+        sqlx::query("UPDATE word_explanations SET prompt_version = 0").execute(&pool).await.unwrap();
+        assert_eq!(cached_explanation(&pool, "赤").await.unwrap(), None);
+    }
+}
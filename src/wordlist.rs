@@ -5,6 +5,10 @@ use std::error::Error;
 use crate::db::*;
 use crate::Route;
 use crate::footer::{StatusMessage, StatusLevel};
+use crate::scheduler::today;
+use crate::anki::{export_anki_package, AnkiPackage};
+use crate::jmdict::word_details_map;
+use crate::pitch::{pitch_accent_map, PitchReading};
 
 /// Represents the type of word list to display.
 /// This enum is used to determine which set of words to fetch from the database.
@@ -14,6 +18,7 @@ pub enum WordListType {
     Familiar,
     Unfamiliar,
     Marked,
+    Due,
 }
 
 // We need to manually implement Display trait for Routable trait to work.
@@ -25,6 +30,7 @@ impl Display for WordListType {
             WordListType::Familiar => "familiar",
             WordListType::Unfamiliar => "unfamiliar",
             WordListType::Marked => "marked",
+            WordListType::Due => "due",
         };
         write!(f, "{}", s)
     }
@@ -56,6 +62,7 @@ impl FromStr for WordListType {
             "familiar" => Ok(WordListType::Familiar),
             "unfamiliar" => Ok(WordListType::Unfamiliar),
             "marked" => Ok(WordListType::Marked),
+            "due" => Ok(WordListType::Due),
             _ => Err(ParseWordListTypeError),
         }
     }
@@ -70,6 +77,8 @@ pub fn WordListPage(list_type: WordListType) -> Element {
     // Add a state to control button enabled/disabled
     let mut generate_enabled = use_signal(|| false);
     let db_pool = use_context::<sqlx::SqlitePool>();
+    let pool_details = db_pool.clone();
+    let pool_pitch = db_pool.clone();
 
     let mut status_message = use_context::<Signal<StatusMessage>>();
 
@@ -111,10 +120,41 @@ pub fn WordListPage(list_type: WordListType) -> Element {
                         .await
                         .map_err(|e| e.to_string())
                 },
+                WordListType::Due => {
+                    ProgressSelect::new()
+                        .select_due(today())
+                        .execute(&pool)
+                        .await
+                        .map_err(|e| e.to_string())
+                },
             }
         }
     });
 
+    // JMdict data (senses, part of speech, common flag) of the listed words, empty until imported
+    let word_details = use_resource(move || {
+        let pool = pool_details.clone();
+        async move {
+            let ids: Vec<i64> = match &*word_list.read() {
+                Some(Ok(words)) => words.iter().map(|w| w.id).collect(),
+                _ => Vec::new(),
+            };
+            word_details_map(&pool, &ids).await.unwrap_or_default()
+        }
+    });
+
+    // pitch accents of the listed words, empty until imported
+    let word_accents = use_resource(move || {
+        let pool = pool_pitch.clone();
+        async move {
+            let words = match &*word_list.read() {
+                Some(Ok(words)) => words.clone(),
+                _ => Vec::new(),
+            };
+            pitch_accent_map(&pool, &words).await.unwrap_or_default()
+        }
+    });
+
     // Determine the title based on the list_type
     let title = format!("{} words", list_type);
 
@@ -165,6 +205,28 @@ pub fn WordListPage(list_type: WordListType) -> Element {
                         },
                         "Generate Test"
                     }
+                    button { class: "btn btn-outline-info",
+                        disabled: !generate_enabled(),
+                        onclick: move |_| {
+                            // export the words currently listed as an Anki deck
+                            let words = select_words();
+                            let path = std::path::PathBuf::from(format!("data/export/{}_words.apkg", list_type));
+                            spawn(async move {
+                                let deck_name = format!("JLPT {} words", list_type);
+                                match export_anki_package(&words, &deck_name, &path, AnkiPackage::Deck).await {
+                                    Ok(count) => status_message.set(StatusMessage {
+                                        message: format!("Exported {} words to {}", count, path.display()),
+                                        level: StatusLevel::Success,
+                                    }),
+                                    Err(e) => status_message.set(StatusMessage {
+                                        message: format!("Failed to export Anki deck: {}", e),
+                                        level: StatusLevel::Error,
+                                    }),
+                                }
+                            });
+                        },
+                        "Export to Anki"
+                    }
                 }
             }
             h1 { "{title}" }
@@ -174,6 +236,8 @@ pub fn WordListPage(list_type: WordListType) -> Element {
                 Some(Ok(words)) =>{ 
                     // select_words.set(words.clone()); // Store the words in the context
                     words_to_use.set(words.iter().map(|w| w.expression.clone()).collect()); // Store the words to use in the story generation
+                    let details = word_details().unwrap_or_default();
+                    let accents = word_accents().unwrap_or_default();
                     rsx! {
                     
                     div { class: "flex-grow-1 overflow-auto",
@@ -183,6 +247,7 @@ pub fn WordListPage(list_type: WordListType) -> Element {
                                     th { "Expression" }
                                     th { "Reading" }
                                     th { "Meaning" }
+                                    th { "Dictionary" }
                                     th { "Practice Time" }
                                 }
                             }
@@ -200,8 +265,25 @@ pub fn WordListPage(list_type: WordListType) -> Element {
                                                 "{word.expression}"
                                             }
                                         }
-                                        td { "{word.reading}" }
+                                        td {
+                                            PitchReading {
+                                                reading: word.reading.clone(),
+                                                accents: accents.get(&word.id).cloned().unwrap_or_default(),
+                                            }
+                                        }
                                         td { "{word.meaning}" }
+                                        td {
+                                            if let Some(detail) = details.get(&word.id) {
+                                                if detail.common {
+                                                    span { class: "badge bg-success me-1", "common" }
+                                                }
+                                                span { class: "badge bg-secondary me-1", "{detail.main_pos()}" }
+                                                small { class: "text-muted",
+                                                    title: "{detail.senses_text()}",
+                                                    "{detail.senses.len()} senses"
+                                                }
+                                            }
+                                        }
                                         td { "{word.practice_time}" }
                                     }
                                 }